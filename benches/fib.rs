@@ -0,0 +1,41 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use monkey_lang_lib::compiler::compiler::Compiler;
+use monkey_lang_lib::evaluator::environment::Environment;
+use monkey_lang_lib::evaluator::evaluator::eval;
+use monkey_lang_lib::lexer::lexer::Lexer;
+use monkey_lang_lib::parser::parser::Parser;
+use monkey_lang_lib::vm::vm::Vm;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+const FIB: &str = "
+let fib = fn(n) {
+    if (n < 2) { n } else { fib(n - 1) + fib(n - 2) }
+};
+fib(30);
+";
+
+fn fib_backends(c: &mut Criterion) {
+    let program = Parser::new(Lexer::new(FIB)).parse_program();
+    let mut group = c.benchmark_group("fib(30)");
+    // a single run takes seconds on the evaluator
+    group.sample_size(10);
+
+    group.bench_function("evaluator", |b| {
+        b.iter(|| eval(black_box(&program), &Rc::new(RefCell::new(Environment::new()))))
+    });
+    group.bench_function("vm", |b| {
+        b.iter(|| {
+            let mut compiler = Compiler::new();
+            compiler.compile(black_box(&program)).unwrap();
+            let mut vm = Vm::new(compiler.bytecode());
+            vm.run().unwrap();
+            vm.last_popped().clone()
+        })
+    });
+
+    group.finish();
+}
+
+criterion_group!(benches, fib_backends);
+criterion_main!(benches);
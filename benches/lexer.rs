@@ -0,0 +1,37 @@
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use monkey_lang_lib::lexer::lexer::Lexer;
+
+/// returns a monkey lang program of at least `target_len` bytes, built from
+/// numbered copies of a function definition and call
+fn generate_program(target_len: usize) -> String {
+    let mut source = String::with_capacity(target_len + 128);
+    let mut i = 0;
+
+    while source.len() < target_len {
+        source.push_str(&format!(
+            "let value{i} = fn(x, y) {{\n    if (x < y) {{ return x * {i}; }} else {{ return !(y == {i}); }}\n}};\nvalue{i}(10, {i}) != -{i};\n",
+            i = i
+        ));
+        i += 1;
+    }
+
+    source
+}
+
+fn lexer_throughput(c: &mut Criterion) {
+    let mut group = c.benchmark_group("lexer");
+
+    // throughput should stay flat as the input grows if lexing is linear
+    for size in [10 * 1024, 100 * 1024, 200 * 1024, 1024 * 1024].iter() {
+        let source = generate_program(*size);
+        group.throughput(Throughput::Bytes(source.len() as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &source, |b, source| {
+            b.iter(|| Lexer::new(black_box(source)).count())
+        });
+    }
+
+    group.finish();
+}
+
+criterion_group!(benches, lexer_throughput);
+criterion_main!(benches);
@@ -1,68 +1,423 @@
-use crate::tokens::tokens::Token;
+use crate::tokens::tokens::{Span, TokenType};
+use std::fmt;
 
-trait Node {
-    fn token_literal() -> String;
+/// represents the root node of every monkey lang AST the parser produces
+///
+/// # Parameters
+///
+/// * `statements` - `Vec<Statement>` - the top level statements of the
+///   program, in source order
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct Program {
+    pub statements: Vec<Statement>,
 }
 
-trait Expression {
-    fn expression_node;
+/// represents a `{ ... }` delimited list of statements, used as the body of
+/// `if` branches and function literals
+///
+/// # Parameters
+///
+/// * `statements` - `Vec<Statement>` - the statements inside the braces
+///
+/// * `span` - `Span` - the location of the block, braces included
+///
+#[derive(Clone, Debug, PartialEq)]
+pub struct BlockStatement {
+    pub statements: Vec<Statement>,
+    pub span: Span,
 }
 
-trait Statement {
-    fn statement_node(&Self);
+/// represents the statements of monkey lang. every node carries the `Span`
+/// of the source it was parsed from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    /// `let <name> = <value>;`
+    Let {
+        name: String,
+        value: Expression,
+        span: Span,
+    },
+    /// `return <value>;`
+    Return { value: Expression, span: Span },
+    /// `<name> = <value>;` rebinding an existing name, or a compound
+    /// assignment like `<name> += <value>;` with `operator` the arithmetic
+    /// operator applied to the current value
+    Assign {
+        name: String,
+        operator: Option<TokenType>,
+        value: Expression,
+        span: Span,
+    },
+    /// `while (<condition>) <body>`
+    While {
+        condition: Expression,
+        body: BlockStatement,
+        span: Span,
+    },
+    /// `for (<variable> in <iterable>) <body>`, binds `variable` to each
+    /// element of an array in turn
+    For {
+        variable: String,
+        iterable: Expression,
+        body: BlockStatement,
+        span: Span,
+    },
+    /// `break;`, leaves the innermost loop
+    Break { span: Span },
+    /// `continue;`, starts the next iteration of the innermost loop
+    Continue { span: Span },
+    /// a bare expression used as a statement, ie: `x + 10;`
+    Expression(Expression),
+    /// a standalone `{ ... }` block
+    Block(BlockStatement),
 }
 
-impl<T: Statement> Node for T {}
-impl<T: Expression> Node for T {}
-
+impl Statement {
+    /// returns the location of the statement in source
+    pub fn span(&self) -> Span {
+        match self {
+            Statement::Let { span, .. }
+            | Statement::Return { span, .. }
+            | Statement::Assign { span, .. }
+            | Statement::While { span, .. }
+            | Statement::For { span, .. }
+            | Statement::Break { span }
+            | Statement::Continue { span } => *span,
+            Statement::Expression(expression) => expression.span(),
+            Statement::Block(block) => block.span,
+        }
+    }
+}
 
-// Program
-//
-struct Program {
-    statements: Vec<Statement>,
+/// represents the expressions of monkey lang. every node carries the `Span`
+/// of the source it was parsed from.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Expression {
+    Identifier {
+        name: String,
+        span: Span,
+    },
+    IntegerLiteral {
+        value: i64,
+        span: Span,
+    },
+    FloatLiteral {
+        value: f64,
+        span: Span,
+    },
+    Boolean {
+        value: bool,
+        span: Span,
+    },
+    /// `"<value>"`, holds the value with escape sequences decoded
+    StringLiteral {
+        value: String,
+        span: Span,
+    },
+    /// `<operator><right>`, ie: `!true` or `-5`
+    Prefix {
+        operator: TokenType,
+        right: Box<Expression>,
+        span: Span,
+    },
+    /// `<left> <operator> <right>`, ie: `5 + 5`
+    Infix {
+        left: Box<Expression>,
+        operator: TokenType,
+        right: Box<Expression>,
+        span: Span,
+    },
+    /// `if (<condition>) <consequence> else <alternative>`
+    If {
+        condition: Box<Expression>,
+        consequence: BlockStatement,
+        alternative: Option<BlockStatement>,
+        span: Span,
+    },
+    /// `[<elements>]`
+    ArrayLiteral {
+        elements: Vec<Expression>,
+        span: Span,
+    },
+    /// `<left>[<index>]`
+    Index {
+        left: Box<Expression>,
+        index: Box<Expression>,
+        span: Span,
+    },
+    /// `{<key>: <value>, ...}`, pairs are kept in source order
+    HashLiteral {
+        pairs: Vec<(Expression, Expression)>,
+        span: Span,
+    },
+    /// `fn(<parameters>) <body>`
+    FunctionLiteral {
+        parameters: Vec<String>,
+        body: BlockStatement,
+        span: Span,
+    },
+    /// `macro(<parameters>) <body>`, only bound by top level `let`
+    /// statements, which macro expansion removes
+    MacroLiteral {
+        parameters: Vec<String>,
+        body: BlockStatement,
+        span: Span,
+    },
+    /// `<function>(<arguments>)`
+    Call {
+        function: Box<Expression>,
+        arguments: Vec<Expression>,
+        span: Span,
+    },
 }
 
+impl Expression {
+    /// returns the location of the expression in source
+    pub fn span(&self) -> Span {
+        match self {
+            Expression::Identifier { span, .. }
+            | Expression::IntegerLiteral { span, .. }
+            | Expression::FloatLiteral { span, .. }
+            | Expression::Boolean { span, .. }
+            | Expression::StringLiteral { span, .. }
+            | Expression::Prefix { span, .. }
+            | Expression::Infix { span, .. }
+            | Expression::If { span, .. }
+            | Expression::ArrayLiteral { span, .. }
+            | Expression::Index { span, .. }
+            | Expression::HashLiteral { span, .. }
+            | Expression::FunctionLiteral { span, .. }
+            | Expression::MacroLiteral { span, .. }
+            | Expression::Call { span, .. } => *span,
+        }
+    }
+}
 
-impl Node for Program {
-    fn token_literal (&Self) -> String {
-        match self.statements.len() > 0 {
-            true => self.statements[0].token_literal()
-            _ => "".to_string()
+/// writes `value` as a string literal the lexer reads back as the same value
+fn write_quoted(f: &mut fmt::Formatter, value: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for ch in value.chars() {
+        match ch {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\t' => write!(f, "\\t")?,
+            ch => write!(f, "{}", ch)?,
         }
     }
+    write!(f, "\"")
 }
 
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for statement in &self.statements {
+            write!(f, "{}", statement)?;
+        }
+        Ok(())
+    }
+}
 
-// LetStatement
-//
-struct LetStatement {
-    token: Token,
-    name: *Identifier,
-    value: Expression,
+impl fmt::Display for BlockStatement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.statements.is_empty() {
+            return write!(f, "{{}}");
+        }
+        let statements: Vec<String> = self.statements.iter().map(|s| s.to_string()).collect();
+        write!(f, "{{ {} }}", statements.join(" "))
+    }
 }
 
-impl Node, Statement for LetStatement {
-    // Node
-    fn token_literal(&Self) -> String {
-        Self.token.literal.to_string()
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Statement::Let { name, value, .. } => write!(f, "let {} = {};", name, value),
+            Statement::Return { value, .. } => write!(f, "return {};", value),
+            Statement::Assign {
+                name,
+                operator,
+                value,
+                ..
+            } => match operator {
+                Some(operator) => write!(f, "{} {}= {};", name, operator, value),
+                None => write!(f, "{} = {};", name, value),
+            },
+            Statement::While {
+                condition, body, ..
+            } => write!(f, "while {} {}", condition, body),
+            Statement::For {
+                variable,
+                iterable,
+                body,
+                ..
+            } => write!(f, "for ({} in {}) {}", variable, iterable, body),
+            Statement::Break { .. } => write!(f, "break;"),
+            Statement::Continue { .. } => write!(f, "continue;"),
+            Statement::Expression(expression) => write!(f, "{}", expression),
+            Statement::Block(block) => write!(f, "{}", block),
+        }
     }
-    // Statement
-    fn statement_node(&Self);
 }
 
+impl fmt::Display for Expression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Expression::Identifier { name, .. } => write!(f, "{}", name),
+            Expression::IntegerLiteral { value, .. } => write!(f, "{}", value),
+            // the debug form keeps a fraction or an exponent, so the value reads
+            // back as a float
+            Expression::FloatLiteral { value, .. } => write!(f, "{:?}", value),
+            Expression::Boolean { value, .. } => write!(f, "{}", value),
+            Expression::StringLiteral { value, .. } => write_quoted(f, value),
+            Expression::Prefix {
+                operator, right, ..
+            } => write!(f, "({}{})", operator, right),
+            Expression::Infix {
+                left,
+                operator,
+                right,
+                ..
+            } => write!(f, "({} {} {})", left, operator, right),
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                write!(f, "if {} {}", condition, consequence)?;
+                if let Some(alternative) = alternative {
+                    write!(f, " else {}", alternative)?;
+                }
+                Ok(())
+            }
+            Expression::ArrayLiteral { elements, .. } => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Expression::Index { left, index, .. } => write!(f, "({}[{}])", left, index),
+            Expression::HashLiteral { pairs, .. } => {
+                let pairs: Vec<String> = pairs
+                    .iter()
+                    .map(|(key, value)| format!("{}: {}", key, value))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Expression::FunctionLiteral {
+                parameters, body, ..
+            } => write!(f, "fn({}) {}", parameters.join(", "), body),
+            Expression::MacroLiteral {
+                parameters, body, ..
+            } => write!(f, "macro({}) {}", parameters.join(", "), body),
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => {
+                let arguments: Vec<String> = arguments.iter().map(|a| a.to_string()).collect();
+                write!(f, "{}({})", function, arguments.join(", "))
+            }
+        }
+    }
+}
 
-// Identifier
 //
-struct Identifier {
-    token: Token,
-    value: String,
-}
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ident(name: &str) -> Expression {
+        Expression::Identifier {
+            name: name.to_string(),
+            span: Span::default(),
+        }
+    }
+
+    #[test]
+    fn display_statements() {
+        let program = Program {
+            statements: vec![
+                Statement::Let {
+                    name: "my_var".to_string(),
+                    value: ident("another_var"),
+                    span: Span::default(),
+                },
+                Statement::Return {
+                    value: Expression::Prefix {
+                        operator: TokenType::MINUS,
+                        right: Box::new(Expression::IntegerLiteral {
+                            value: 5,
+                            span: Span::default(),
+                        }),
+                        span: Span::default(),
+                    },
+                    span: Span::default(),
+                },
+            ],
+        };
 
-impl Node, Expression for Identifier {
-    // Node 
-    fn token_literal(&Self) -> String {
-        Self.token.literal.to_string()
+        assert_eq!(program.to_string(), "let my_var = another_var;return (-5);");
+    }
+
+    #[test]
+    fn display_expressions() {
+        let function = Expression::FunctionLiteral {
+            parameters: vec!["x".to_string(), "y".to_string()],
+            body: BlockStatement {
+                statements: vec![Statement::Expression(Expression::Infix {
+                    left: Box::new(ident("x")),
+                    operator: TokenType::PLUS,
+                    right: Box::new(ident("y")),
+                    span: Span::default(),
+                })],
+                span: Span::default(),
+            },
+            span: Span::default(),
+        };
+        assert_eq!(function.to_string(), "fn(x, y) { (x + y) }");
+
+        let call = Expression::Call {
+            function: Box::new(function),
+            arguments: vec![
+                Expression::IntegerLiteral {
+                    value: 1,
+                    span: Span::default(),
+                },
+                Expression::Boolean {
+                    value: true,
+                    span: Span::default(),
+                },
+            ],
+            span: Span::default(),
+        };
+        assert_eq!(call.to_string(), "fn(x, y) { (x + y) }(1, true)");
+
+        let string = Expression::StringLiteral {
+            value: "say \"hi\"\n\\o/".to_string(),
+            span: Span::default(),
+        };
+        assert_eq!(string.to_string(), r#""say \"hi\"\n\\o/""#);
+    }
+
+    #[test]
+    fn pattern_match_cloned_tree() {
+        let statement = Statement::Block(BlockStatement {
+            statements: vec![Statement::Return {
+                value: ident("x"),
+                span: Span::new(2, 11, 1, 3),
+            }],
+            span: Span::new(0, 13, 1, 1),
+        });
+
+        match statement.clone() {
+            Statement::Block(BlockStatement { statements, .. }) => {
+                assert_eq!(statements[0].span(), Span::new(2, 11, 1, 3))
+            }
+            other => panic!("expected block statement, got {:?}", other),
+        }
+        assert_eq!(statement.span(), Span::new(0, 13, 1, 1));
+        assert_eq!(statement.to_string(), "{ return x; }");
     }
-    // Expression
-    fn expression_node(&Self);
 }
@@ -1 +1,2 @@
-pub mod ast;
\ No newline at end of file
+pub mod ast;
+pub mod modify;
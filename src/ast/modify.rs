@@ -0,0 +1,316 @@
+use crate::ast::ast::{BlockStatement, Expression, Program, Statement};
+
+/// rebuilds `program` bottom up: every expression is passed to `modifier`
+/// once its children are rebuilt, and replaced by what `modifier` returns.
+/// stops at the first error `modifier` returns.
+///
+/// # Arguments
+///
+/// * `program` - the program to rebuild
+///
+/// * `modifier` - called with each expression of the program, children
+///   before their parents
+///
+pub fn modify_program<F, E>(program: Program, modifier: &mut F) -> Result<Program, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    Ok(Program {
+        statements: modify_statements(program.statements, modifier)?,
+    })
+}
+
+/// rebuilds `block` like `modify_program`
+pub fn modify_block<F, E>(block: BlockStatement, modifier: &mut F) -> Result<BlockStatement, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    Ok(BlockStatement {
+        statements: modify_statements(block.statements, modifier)?,
+        span: block.span,
+    })
+}
+
+/// rebuilds `statement` like `modify_program`
+pub fn modify_statement<F, E>(statement: Statement, modifier: &mut F) -> Result<Statement, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    let statement = match statement {
+        Statement::Let { name, value, span } => Statement::Let {
+            name,
+            value: modify_expression(value, modifier)?,
+            span,
+        },
+        Statement::Return { value, span } => Statement::Return {
+            value: modify_expression(value, modifier)?,
+            span,
+        },
+        Statement::Assign {
+            name,
+            operator,
+            value,
+            span,
+        } => Statement::Assign {
+            name,
+            operator,
+            value: modify_expression(value, modifier)?,
+            span,
+        },
+        Statement::While {
+            condition,
+            body,
+            span,
+        } => Statement::While {
+            condition: modify_expression(condition, modifier)?,
+            body: modify_block(body, modifier)?,
+            span,
+        },
+        Statement::For {
+            variable,
+            iterable,
+            body,
+            span,
+        } => Statement::For {
+            variable,
+            iterable: modify_expression(iterable, modifier)?,
+            body: modify_block(body, modifier)?,
+            span,
+        },
+        Statement::Expression(expression) => {
+            Statement::Expression(modify_expression(expression, modifier)?)
+        }
+        Statement::Block(block) => Statement::Block(modify_block(block, modifier)?),
+        statement @ (Statement::Break { .. } | Statement::Continue { .. }) => statement,
+    };
+    Ok(statement)
+}
+
+/// rebuilds `expression` like `modify_program`, `expression` itself is passed
+/// to `modifier` last
+pub fn modify_expression<F, E>(expression: Expression, modifier: &mut F) -> Result<Expression, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    let expression = match expression {
+        Expression::Prefix {
+            operator,
+            right,
+            span,
+        } => Expression::Prefix {
+            operator,
+            right: modify_boxed(right, modifier)?,
+            span,
+        },
+        Expression::Infix {
+            left,
+            operator,
+            right,
+            span,
+        } => Expression::Infix {
+            left: modify_boxed(left, modifier)?,
+            operator,
+            right: modify_boxed(right, modifier)?,
+            span,
+        },
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+            span,
+        } => Expression::If {
+            condition: modify_boxed(condition, modifier)?,
+            consequence: modify_block(consequence, modifier)?,
+            alternative: match alternative {
+                Some(alternative) => Some(modify_block(alternative, modifier)?),
+                None => None,
+            },
+            span,
+        },
+        Expression::ArrayLiteral { elements, span } => Expression::ArrayLiteral {
+            elements: modify_expressions(elements, modifier)?,
+            span,
+        },
+        Expression::Index { left, index, span } => Expression::Index {
+            left: modify_boxed(left, modifier)?,
+            index: modify_boxed(index, modifier)?,
+            span,
+        },
+        Expression::HashLiteral { pairs, span } => {
+            let mut modified = Vec::with_capacity(pairs.len());
+            for (key, value) in pairs {
+                modified.push((
+                    modify_expression(key, modifier)?,
+                    modify_expression(value, modifier)?,
+                ));
+            }
+            Expression::HashLiteral {
+                pairs: modified,
+                span,
+            }
+        }
+        Expression::FunctionLiteral {
+            parameters,
+            body,
+            span,
+        } => Expression::FunctionLiteral {
+            parameters,
+            body: modify_block(body, modifier)?,
+            span,
+        },
+        Expression::MacroLiteral {
+            parameters,
+            body,
+            span,
+        } => Expression::MacroLiteral {
+            parameters,
+            body: modify_block(body, modifier)?,
+            span,
+        },
+        Expression::Call {
+            function,
+            arguments,
+            span,
+        } => Expression::Call {
+            function: modify_boxed(function, modifier)?,
+            arguments: modify_expressions(arguments, modifier)?,
+            span,
+        },
+        leaf => leaf,
+    };
+    modifier(expression)
+}
+
+fn modify_statements<F, E>(
+    statements: Vec<Statement>,
+    modifier: &mut F,
+) -> Result<Vec<Statement>, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    statements
+        .into_iter()
+        .map(|statement| modify_statement(statement, modifier))
+        .collect()
+}
+
+fn modify_expressions<F, E>(
+    expressions: Vec<Expression>,
+    modifier: &mut F,
+) -> Result<Vec<Expression>, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    expressions
+        .into_iter()
+        .map(|expression| modify_expression(expression, modifier))
+        .collect()
+}
+
+/// rebuilds a boxed child expression in its existing allocation
+fn modify_boxed<F, E>(
+    mut expression: Box<Expression>,
+    modifier: &mut F,
+) -> Result<Box<Expression>, E>
+where
+    F: FnMut(Expression) -> Result<Expression, E>,
+{
+    *expression = modify_expression(*expression, modifier)?;
+    Ok(expression)
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+    use crate::tokens::tokens::Span;
+
+    fn parse(input: &str) -> Program {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert_eq!(parser.errors(), &[], "input should parse");
+        program
+    }
+
+    /// turns every `1` into a `2`
+    fn one_into_two(expression: Expression) -> Result<Expression, ()> {
+        match expression {
+            Expression::IntegerLiteral { value: 1, span } => {
+                Ok(Expression::IntegerLiteral { value: 2, span })
+            }
+            other => Ok(other),
+        }
+    }
+
+    #[test]
+    fn modify_every_node() {
+        let tests = [
+            ("1", "2"),
+            ("1 + -1", "(2 + (-2))"),
+            ("if (1) { 1 } else { 1 }", "if 2 { 2 } else { 2 }"),
+            ("[1, 1][1]", "([2, 2][2])"),
+            ("{1: 1}", "{2: 2}"),
+            ("fn(x) { return 1; }(1)", "fn(x) { return 2; }(2)"),
+            ("macro(x) { 1 }", "macro(x) { 2 }"),
+            ("let x = 1; x = 1; x += 1;", "let x = 2;x = 2;x += 2;"),
+            (
+                "while (1) { 1 } for (x in [1]) { break; }",
+                "while 2 { 2 }for (x in [2]) { break; }",
+            ),
+            ("{ 1 }", "{ 2 }"),
+        ];
+
+        for (input, expected) in tests.iter() {
+            let modified = modify_program(parse(input), &mut one_into_two).unwrap();
+            assert_eq!(&modified.to_string(), expected);
+        }
+    }
+
+    #[test]
+    fn modify_children_first() {
+        let mut seen = vec![];
+        let program = modify_program(parse("f(a + b)"), &mut |expression| {
+            seen.push(expression.to_string());
+            Ok::<_, ()>(expression)
+        })
+        .unwrap();
+
+        assert_eq!(seen, ["f", "a", "b", "(a + b)", "f((a + b))"]);
+        assert_eq!(program.to_string(), "f((a + b))");
+    }
+
+    #[test]
+    fn modify_stops_at_error() {
+        let mut calls = 0;
+        let result = modify_expression(
+            Expression::ArrayLiteral {
+                elements: vec![
+                    Expression::Boolean {
+                        value: true,
+                        span: Span::default(),
+                    },
+                    Expression::IntegerLiteral {
+                        value: 1,
+                        span: Span::default(),
+                    },
+                ],
+                span: Span::default(),
+            },
+            &mut |expression| {
+                calls += 1;
+                match expression {
+                    Expression::Boolean { .. } => Err("no booleans"),
+                    other => Ok(other),
+                }
+            },
+        );
+
+        assert_eq!(result, Err("no booleans"));
+        assert_eq!(calls, 1);
+    }
+}
@@ -0,0 +1,247 @@
+//! the `monkey` command line interpreter
+//!
+//! * `monkey` - starts the repl
+//!
+//! * `monkey script.mk` - runs a script, writing the value it evaluates to
+//!
+//! * `monkey -e '<src>'` - runs `<src>`, writing the value it evaluates to
+//!
+//! * `--dump tokens|ast|bytecode` - writes a stage of the pipeline instead of
+//!   running the source. the parse tree is written before macro expansion,
+//!   the bytecode is compiled after it.
+//!
+//! the exit status tells apart how a run failed, see the `EXIT_*` constants.
+
+use monkey_lang_lib::compiler::compiler::Compiler;
+use monkey_lang_lib::diagnostics::diagnostics::Diagnostic;
+use monkey_lang_lib::evaluator::environment::Environment;
+use monkey_lang_lib::evaluator::evaluator::eval;
+use monkey_lang_lib::evaluator::macro_expansion::{define_macros, expand_macros};
+use monkey_lang_lib::evaluator::object::Object;
+use monkey_lang_lib::lexer::lexer::Lexer;
+use monkey_lang_lib::parser::parser::{ParseErrorKind, Parser};
+use monkey_lang_lib::repl::repl;
+use monkey_lang_lib::Program;
+use std::cell::RefCell;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::process;
+use std::rc::Rc;
+
+/// the source couldn't be read, or the repl couldn't read or write its stdio
+const EXIT_IO: i32 = 1;
+/// the command line arguments are invalid
+const EXIT_USAGE: i32 = 2;
+/// the source is malformed: an unterminated string, an invalid escape...
+const EXIT_LEX: i32 = 3;
+/// the source lexes but doesn't parse
+const EXIT_PARSE: i32 = 4;
+/// the program failed to compile or to run
+const EXIT_RUNTIME: i32 = 5;
+
+const USAGE: &str = "usage: monkey [--dump tokens|ast|bytecode] [script.mk | -e <src>]";
+
+/// a stage of the pipeline `--dump` can write instead of running the source
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum Stage {
+    Tokens,
+    Ast,
+    Bytecode,
+}
+
+/// where the source to run comes from
+enum Source {
+    Repl,
+    File(String),
+    Inline(String),
+}
+
+/// the parsed command line
+struct Options {
+    source: Source,
+    dump: Option<Stage>,
+}
+
+fn main() {
+    let options = match parse_args(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            process::exit(EXIT_USAGE);
+        }
+    };
+
+    let (name, source) = match options.source {
+        Source::Repl if options.dump.is_some() => {
+            eprintln!("--dump needs a script or -e <src>\n{}", USAGE);
+            process::exit(EXIT_USAGE);
+        }
+        Source::Repl => {
+            let stdin = io::stdin();
+            if let Err(err) = repl::start(stdin.lock(), io::stdout(), io::stderr()) {
+                eprintln!("{}", err);
+                process::exit(EXIT_IO);
+            }
+            return;
+        }
+        Source::File(path) => match fs::read_to_string(&path) {
+            Ok(source) => (path, source),
+            Err(err) => {
+                eprintln!("could not read {}: {}", path, err);
+                process::exit(EXIT_IO);
+            }
+        },
+        Source::Inline(source) => ("<eval>".to_string(), source),
+    };
+
+    process::exit(run(&name, &source, options.dump));
+}
+
+/// parses the command line arguments, not including the program name
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut options = Options {
+        source: Source::Repl,
+        dump: None,
+    };
+
+    while let Some(arg) = args.next() {
+        let source = match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                process::exit(0);
+            }
+            "--dump" => {
+                let stage = args.next().ok_or("--dump needs a stage")?;
+                options.dump = Some(parse_stage(&stage)?);
+                continue;
+            }
+            "-e" => Source::Inline(args.next().ok_or("-e needs source to evaluate")?),
+            _ if arg.starts_with("--dump=") => {
+                options.dump = Some(parse_stage(&arg["--dump=".len()..])?);
+                continue;
+            }
+            _ if arg.starts_with('-') && arg != "-" => {
+                return Err(format!("unknown option {}", arg))
+            }
+            _ => Source::File(arg),
+        };
+
+        if let Source::Repl = options.source {
+            options.source = source;
+        } else {
+            return Err("expected a single script or -e <src>".to_string());
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_stage(stage: &str) -> Result<Stage, String> {
+    match stage {
+        "tokens" => Ok(Stage::Tokens),
+        "ast" => Ok(Stage::Ast),
+        "bytecode" => Ok(Stage::Bytecode),
+        _ => Err(format!(
+            "unknown stage {}, expected tokens, ast or bytecode",
+            stage
+        )),
+    }
+}
+
+/// runs or dumps `source`, read from `name`, returning the exit status
+fn run(name: &str, source: &str, dump: Option<Stage>) -> i32 {
+    if dump == Some(Stage::Tokens) {
+        let mut lexer = Lexer::new(source);
+        for token in lexer.by_ref() {
+            println!("{}\t{:?}\t{}", token.span, token.token_type, token.literal);
+        }
+
+        let errors = lexer.take_errors();
+        for err in &errors {
+            report(name, source, Diagnostic::from(err));
+        }
+        return if errors.is_empty() { 0 } else { EXIT_LEX };
+    }
+
+    let mut program = match parse(name, source) {
+        Ok(program) => program,
+        Err(status) => return status,
+    };
+
+    if dump == Some(Stage::Ast) {
+        for statement in &program.statements {
+            println!("{:#?}", statement);
+        }
+        return 0;
+    }
+
+    let macros = Rc::new(RefCell::new(Environment::new()));
+    define_macros(&mut program, &macros);
+    let program = match expand_macros(program, &macros) {
+        Ok(program) => program,
+        Err(err) => return runtime_error(name, source, err),
+    };
+
+    match dump {
+        Some(Stage::Bytecode) => {
+            let mut compiler = Compiler::new();
+            match compiler.compile(&program) {
+                Ok(()) => {
+                    print!("{}", compiler.bytecode().disassemble());
+                    0
+                }
+                Err(err) => {
+                    report(name, source, Diagnostic::from(&err));
+                    EXIT_RUNTIME
+                }
+            }
+        }
+        _ => match eval(&program, &Rc::new(RefCell::new(Environment::new()))) {
+            err @ Object::Error { .. } => runtime_error(name, source, err),
+            Object::Null => 0,
+            result => {
+                println!("{}", result);
+                0
+            }
+        },
+    }
+}
+
+/// parses `source`, or writes its errors and returns the exit status telling
+/// whether it failed to lex or to parse
+fn parse(name: &str, source: &str) -> Result<Program, i32> {
+    let mut parser = Parser::new(Lexer::new(source));
+    let program = parser.parse_program();
+
+    let errors = parser.errors();
+    if errors.is_empty() {
+        return Ok(program);
+    }
+
+    for err in errors {
+        report(name, source, Diagnostic::from(err));
+    }
+    let lex_failed = errors
+        .iter()
+        .any(|err| matches!(err.kind, ParseErrorKind::Lex(_)));
+    Err(if lex_failed { EXIT_LEX } else { EXIT_PARSE })
+}
+
+/// writes the runtime error `err` and returns the exit status
+fn runtime_error(name: &str, source: &str, err: Object) -> i32 {
+    if let Object::Error { message, span } = err {
+        let mut diagnostic = Diagnostic::error(message);
+        diagnostic.span = span;
+        report(name, source, diagnostic);
+    }
+    EXIT_RUNTIME
+}
+
+/// writes `diagnostic` to stderr, coloured when stderr is a terminal
+fn report(name: &str, source: &str, diagnostic: Diagnostic) {
+    eprint!(
+        "{}",
+        diagnostic.render(name, source, io::stderr().is_terminal())
+    );
+}
@@ -0,0 +1,121 @@
+//! the `monkeyfmt` source formatter
+//!
+//! * `monkeyfmt` - formats stdin, writing the result to stdout
+//!
+//! * `monkeyfmt file.mk...` - formats the files in place
+//!
+//! * `--check` - writes the names of the files that aren't formatted instead
+//!   of formatting them, `<stdin>` for stdin
+//!
+//! the exit status tells apart how a run failed, see the `EXIT_*` constants.
+
+use monkey_lang_lib::diagnostics::diagnostics::Diagnostic;
+use monkey_lang_lib::formatter::formatter;
+use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::process;
+
+/// `--check` found source that isn't formatted
+const EXIT_UNFORMATTED: i32 = 1;
+/// the command line arguments are invalid
+const EXIT_USAGE: i32 = 2;
+/// source couldn't be read, written or parsed
+const EXIT_ERROR: i32 = 3;
+
+const USAGE: &str = "usage: monkeyfmt [--check] [file.mk...]";
+
+fn main() {
+    let mut check = false;
+    let mut paths = vec![];
+
+    for arg in env::args().skip(1) {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--check" => check = true,
+            _ if arg.starts_with('-') => {
+                eprintln!("unknown option {}\n{}", arg, USAGE);
+                process::exit(EXIT_USAGE);
+            }
+            _ => paths.push(arg),
+        }
+    }
+
+    let status = if paths.is_empty() {
+        format_stdin(check)
+    } else {
+        paths
+            .iter()
+            .map(|path| format_file(path, check))
+            .max()
+            .unwrap_or(0)
+    };
+    process::exit(status);
+}
+
+/// formats stdin to stdout, or checks it's formatted. returns the exit status
+fn format_stdin(check: bool) -> i32 {
+    let mut source = String::new();
+    if let Err(err) = io::stdin().read_to_string(&mut source) {
+        eprintln!("could not read <stdin>: {}", err);
+        return EXIT_ERROR;
+    }
+
+    match format("<stdin>", &source) {
+        Some(formatted) if check && formatted != source => {
+            println!("<stdin>");
+            EXIT_UNFORMATTED
+        }
+        Some(_) if check => 0,
+        Some(formatted) => {
+            print!("{}", formatted);
+            0
+        }
+        None => EXIT_ERROR,
+    }
+}
+
+/// formats the file at `path` in place, or checks it's formatted. returns the
+/// exit status
+fn format_file(path: &str, check: bool) -> i32 {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(err) => {
+            eprintln!("could not read {}: {}", path, err);
+            return EXIT_ERROR;
+        }
+    };
+
+    match format(path, &source) {
+        Some(formatted) if formatted == source => 0,
+        Some(_) if check => {
+            println!("{}", path);
+            EXIT_UNFORMATTED
+        }
+        Some(formatted) => match fs::write(path, formatted) {
+            Ok(()) => 0,
+            Err(err) => {
+                eprintln!("could not write {}: {}", path, err);
+                EXIT_ERROR
+            }
+        },
+        None => EXIT_ERROR,
+    }
+}
+
+/// returns `source` formatted, or writes its parse errors to stderr
+fn format(name: &str, source: &str) -> Option<String> {
+    match formatter::format(source) {
+        Ok(formatted) => Some(formatted),
+        Err(errors) => {
+            let color = io::stderr().is_terminal();
+            for err in &errors {
+                eprint!("{}", Diagnostic::from(err).render(name, source, color));
+            }
+            None
+        }
+    }
+}
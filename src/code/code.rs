@@ -0,0 +1,430 @@
+use std::convert::TryFrom;
+use std::fmt::Write;
+
+/// a sequence of encoded instructions. operands are stored big endian right
+/// after their opcode.
+pub type Instructions = Vec<u8>;
+
+/// maps the instructions starting at `offset` to the source `line` they were
+/// compiled from, up to the offset of the next entry
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct LineEntry {
+    pub offset: usize,
+    pub line: usize,
+}
+
+/// returns the source line of the instruction at `offset`, `None` when the
+/// table doesn't cover it
+///
+/// # Arguments
+///
+/// * `lines` - a line table, sorted by offset
+///
+/// * `offset` - the position of an instruction
+///
+pub fn line_at(lines: &[LineEntry], offset: usize) -> Option<usize> {
+    let entries = lines.partition_point(|entry| entry.offset <= offset);
+    entries.checked_sub(1).map(|i| lines[i].line)
+}
+
+/// represents the operations of the virtual machine, one byte each
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Opcode {
+    /// pushes the constant at the operand index
+    Constant,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    /// pops the top of the stack
+    Pop,
+    True,
+    False,
+    Equal,
+    NotEqual,
+    GreaterThan,
+    LessThan,
+    Minus,
+    Bang,
+    /// jumps to the operand offset when the popped condition isn't truthy
+    JumpNotTruthy,
+    Jump,
+    Null,
+    GetGlobal,
+    SetGlobal,
+    /// builds an array from the operand count of elements on the stack
+    Array,
+    /// builds a hash from the operand count of keys and values on the stack
+    Hash,
+    Index,
+    /// calls the function below the operand count of arguments on the stack
+    Call,
+    ReturnValue,
+    /// returns `null` from the current function
+    Return,
+    GetLocal,
+    SetLocal,
+    GetBuiltin,
+    /// wraps the function constant at the first operand in a closure over the
+    /// second operand count of free variables on the stack
+    Closure,
+    GetFree,
+    /// pushes the closure being executed, used for recursive local functions
+    CurrentClosure,
+    // added after the first release, kept last so existing bytecode decodes
+    // the same
+    Mod,
+    Pow,
+    GreaterEqual,
+    LessEqual,
+    /// pops an index and an array, pushes the element at the index or jumps
+    /// to the operand once the array is exhausted
+    IterNext,
+    /// pops a value into the cell of the free variable at the operand index
+    SetFree,
+    /// turns the local at the operand index into a cell shared with the
+    /// closures capturing it, and pushes the cell
+    CaptureLocal,
+    /// pushes the cell of the free variable at the operand index, for a
+    /// closure capturing it in turn
+    CaptureFree,
+    /// pops a value into the local at the operand index, through its cell
+    /// once a closure captured it. `OpSetLocal` binds a new value instead.
+    AssignLocal,
+    /// turns the global at the operand index into a cell shared with the
+    /// closures capturing it, and pushes the cell
+    CaptureGlobal,
+    /// pops a value into the global at the operand index, through its cell
+    /// once a closure captured it. `OpSetGlobal` binds a new value instead.
+    AssignGlobal,
+}
+
+/// every opcode, in encoding order
+const OPCODES: [Opcode; 42] = [
+    Opcode::Constant,
+    Opcode::Add,
+    Opcode::Sub,
+    Opcode::Mul,
+    Opcode::Div,
+    Opcode::Pop,
+    Opcode::True,
+    Opcode::False,
+    Opcode::Equal,
+    Opcode::NotEqual,
+    Opcode::GreaterThan,
+    Opcode::LessThan,
+    Opcode::Minus,
+    Opcode::Bang,
+    Opcode::JumpNotTruthy,
+    Opcode::Jump,
+    Opcode::Null,
+    Opcode::GetGlobal,
+    Opcode::SetGlobal,
+    Opcode::Array,
+    Opcode::Hash,
+    Opcode::Index,
+    Opcode::Call,
+    Opcode::ReturnValue,
+    Opcode::Return,
+    Opcode::GetLocal,
+    Opcode::SetLocal,
+    Opcode::GetBuiltin,
+    Opcode::Closure,
+    Opcode::GetFree,
+    Opcode::CurrentClosure,
+    Opcode::Mod,
+    Opcode::Pow,
+    Opcode::GreaterEqual,
+    Opcode::LessEqual,
+    Opcode::IterNext,
+    Opcode::SetFree,
+    Opcode::CaptureLocal,
+    Opcode::CaptureFree,
+    Opcode::AssignLocal,
+    Opcode::CaptureGlobal,
+    Opcode::AssignGlobal,
+];
+
+/// describes how an opcode is printed and how its operands are encoded
+///
+/// # Parameters
+///
+/// * `name` - the name used in listings, ie: `OpConstant`
+///
+/// * `operand_widths` - the width in bytes of each operand
+///
+#[derive(Debug, PartialEq)]
+pub struct Definition {
+    pub name: &'static str,
+    pub operand_widths: &'static [usize],
+}
+
+impl Opcode {
+    /// returns the name and operand layout of the opcode
+    pub fn definition(self) -> Definition {
+        let (name, operand_widths): (&'static str, &'static [usize]) = match self {
+            Opcode::Constant => ("OpConstant", &[2]),
+            Opcode::Add => ("OpAdd", &[]),
+            Opcode::Sub => ("OpSub", &[]),
+            Opcode::Mul => ("OpMul", &[]),
+            Opcode::Div => ("OpDiv", &[]),
+            Opcode::Pop => ("OpPop", &[]),
+            Opcode::True => ("OpTrue", &[]),
+            Opcode::False => ("OpFalse", &[]),
+            Opcode::Equal => ("OpEqual", &[]),
+            Opcode::NotEqual => ("OpNotEqual", &[]),
+            Opcode::GreaterThan => ("OpGreaterThan", &[]),
+            Opcode::LessThan => ("OpLessThan", &[]),
+            Opcode::Minus => ("OpMinus", &[]),
+            Opcode::Bang => ("OpBang", &[]),
+            Opcode::JumpNotTruthy => ("OpJumpNotTruthy", &[2]),
+            Opcode::Jump => ("OpJump", &[2]),
+            Opcode::Null => ("OpNull", &[]),
+            Opcode::GetGlobal => ("OpGetGlobal", &[2]),
+            Opcode::SetGlobal => ("OpSetGlobal", &[2]),
+            Opcode::Array => ("OpArray", &[2]),
+            Opcode::Hash => ("OpHash", &[2]),
+            Opcode::Index => ("OpIndex", &[]),
+            Opcode::Call => ("OpCall", &[1]),
+            Opcode::ReturnValue => ("OpReturnValue", &[]),
+            Opcode::Return => ("OpReturn", &[]),
+            Opcode::GetLocal => ("OpGetLocal", &[1]),
+            Opcode::SetLocal => ("OpSetLocal", &[1]),
+            Opcode::GetBuiltin => ("OpGetBuiltin", &[1]),
+            Opcode::Closure => ("OpClosure", &[2, 1]),
+            Opcode::GetFree => ("OpGetFree", &[1]),
+            Opcode::CurrentClosure => ("OpCurrentClosure", &[]),
+            Opcode::Mod => ("OpMod", &[]),
+            Opcode::Pow => ("OpPow", &[]),
+            Opcode::GreaterEqual => ("OpGreaterEqual", &[]),
+            Opcode::LessEqual => ("OpLessEqual", &[]),
+            Opcode::IterNext => ("OpIterNext", &[2]),
+            Opcode::SetFree => ("OpSetFree", &[1]),
+            Opcode::CaptureLocal => ("OpCaptureLocal", &[1]),
+            Opcode::CaptureFree => ("OpCaptureFree", &[1]),
+            Opcode::AssignLocal => ("OpAssignLocal", &[1]),
+            Opcode::CaptureGlobal => ("OpCaptureGlobal", &[2]),
+            Opcode::AssignGlobal => ("OpAssignGlobal", &[2]),
+        };
+
+        Definition {
+            name,
+            operand_widths,
+        }
+    }
+}
+
+impl TryFrom<u8> for Opcode {
+    type Error = u8;
+
+    /// returns the opcode encoded as `byte`, or the byte if it isn't one
+    fn try_from(byte: u8) -> Result<Opcode, u8> {
+        OPCODES.get(byte as usize).copied().ok_or(byte)
+    }
+}
+
+/// returns the largest value an operand `width` bytes wide holds
+pub fn max_operand(width: usize) -> usize {
+    (1 << (8 * width)) - 1
+}
+
+/// returns the encoded instruction for `op` with the given operands
+///
+/// # Arguments
+///
+/// * `op` - the opcode of the instruction
+///
+/// * `operands` - one value per operand of `op`, truncated to the operand's
+///   width. check them against `max_operand` first.
+///
+pub fn make(op: Opcode, operands: &[usize]) -> Instructions {
+    let definition = op.definition();
+    let len = 1 + definition.operand_widths.iter().sum::<usize>();
+    let mut instruction = Vec::with_capacity(len);
+    instruction.push(op as u8);
+
+    for (operand, width) in operands.iter().zip(definition.operand_widths) {
+        match width {
+            2 => instruction.extend_from_slice(&(*operand as u16).to_be_bytes()),
+            1 => instruction.push(*operand as u8),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+    }
+
+    instruction
+}
+
+/// decodes the operands of an instruction, returning them with the number of
+/// bytes they take up
+///
+/// # Arguments
+///
+/// * `definition` - the definition of the instruction's opcode
+///
+/// * `ins` - the encoded operands, starting right after the opcode
+///
+pub fn read_operands(definition: &Definition, ins: &[u8]) -> (Vec<usize>, usize) {
+    let mut operands = Vec::with_capacity(definition.operand_widths.len());
+    let mut offset = 0;
+
+    for width in definition.operand_widths {
+        match width {
+            2 => operands.push(read_u16(&ins[offset..]) as usize),
+            1 => operands.push(ins[offset] as usize),
+            _ => unreachable!("unsupported operand width {}", width),
+        }
+        offset += width;
+    }
+
+    (operands, offset)
+}
+
+/// returns a listing of `ins` with one instruction per line, prefixed by its
+/// offset, ie: `0000 OpConstant 1`. bytes that don't decode to an instruction
+/// are listed as errors.
+///
+/// # Arguments
+///
+/// * `ins` - the encoded instructions to list
+///
+pub fn disassemble(ins: &[u8]) -> String {
+    let mut listing = String::new();
+    let mut offset = 0;
+
+    while offset < ins.len() {
+        let op = match Opcode::try_from(ins[offset]) {
+            Ok(op) => op,
+            Err(byte) => {
+                let _ = writeln!(listing, "{:04} ERROR: unknown opcode {}", offset, byte);
+                offset += 1;
+                continue;
+            }
+        };
+
+        let definition = op.definition();
+        let width: usize = definition.operand_widths.iter().sum();
+        if offset + 1 + width > ins.len() {
+            let _ = writeln!(
+                listing,
+                "{:04} ERROR: truncated {}",
+                offset, definition.name
+            );
+            break;
+        }
+
+        let (operands, read) = read_operands(&definition, &ins[offset + 1..]);
+        let _ = write!(listing, "{:04} {}", offset, definition.name);
+        for operand in operands {
+            let _ = write!(listing, " {}", operand);
+        }
+        listing.push('\n');
+        offset += 1 + read;
+    }
+
+    listing
+}
+
+/// reads a big endian `u16` operand from the start of `ins`
+pub fn read_u16(ins: &[u8]) -> u16 {
+    u16::from_be_bytes([ins[0], ins[1]])
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn make_instructions() {
+        let tests: [(Opcode, &[usize], &[u8]); 4] = [
+            (
+                Opcode::Constant,
+                &[65534],
+                &[Opcode::Constant as u8, 255, 254],
+            ),
+            (Opcode::Add, &[], &[Opcode::Add as u8]),
+            (Opcode::GetLocal, &[255], &[Opcode::GetLocal as u8, 255]),
+            (
+                Opcode::Closure,
+                &[65534, 255],
+                &[Opcode::Closure as u8, 255, 254, 255],
+            ),
+        ];
+
+        for (op, operands, expected) in tests.iter() {
+            assert_eq!(make(*op, operands), *expected);
+        }
+    }
+
+    #[test]
+    fn read_instruction_operands() {
+        let tests: [(Opcode, &[usize], usize); 3] = [
+            (Opcode::Constant, &[65535], 2),
+            (Opcode::GetLocal, &[255], 1),
+            (Opcode::Closure, &[65535, 255], 3),
+        ];
+
+        for (op, operands, bytes_read) in tests.iter() {
+            let instruction = make(*op, operands);
+            let (read, n) = read_operands(&op.definition(), &instruction[1..]);
+            assert_eq!(n, *bytes_read);
+            assert_eq!(read, *operands);
+        }
+    }
+
+    #[test]
+    fn disassemble_instructions() {
+        let ins = [
+            make(Opcode::Add, &[]),
+            make(Opcode::GetLocal, &[1]),
+            make(Opcode::Constant, &[2]),
+            make(Opcode::Constant, &[65535]),
+            make(Opcode::Closure, &[65535, 255]),
+        ]
+        .concat();
+
+        assert_eq!(
+            disassemble(&ins),
+            "0000 OpAdd
+0001 OpGetLocal 1
+0003 OpConstant 2
+0006 OpConstant 65535
+0009 OpClosure 65535 255
+"
+        );
+        assert_eq!(
+            disassemble(&[255, Opcode::Constant as u8, 0]),
+            "0000 ERROR: unknown opcode 255\n0001 ERROR: truncated OpConstant\n"
+        );
+    }
+
+    #[test]
+    fn line_lookup() {
+        let lines = [
+            LineEntry { offset: 0, line: 1 },
+            LineEntry { offset: 4, line: 3 },
+            LineEntry { offset: 9, line: 2 },
+        ];
+
+        assert_eq!(line_at(&lines, 0), Some(1));
+        assert_eq!(line_at(&lines, 3), Some(1));
+        assert_eq!(line_at(&lines, 4), Some(3));
+        assert_eq!(line_at(&lines, 100), Some(2));
+        assert_eq!(line_at(&lines[1..], 0), None);
+    }
+
+    #[test]
+    fn opcode_bytes() {
+        for (byte, op) in OPCODES.iter().enumerate() {
+            assert_eq!(*op as usize, byte);
+            assert_eq!(Opcode::try_from(byte as u8), Ok(*op));
+        }
+        assert_eq!(
+            Opcode::try_from(OPCODES.len() as u8),
+            Err(OPCODES.len() as u8)
+        );
+    }
+}
@@ -0,0 +1 @@
+pub mod code;
\ No newline at end of file
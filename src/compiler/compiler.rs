@@ -0,0 +1,1397 @@
+use crate::ast::ast::*;
+use crate::code::code::{self, make, max_operand, Instructions, LineEntry, Opcode};
+use crate::compiler::symbol_table::{Symbol, SymbolScope, SymbolTable};
+use crate::evaluator::builtins::BUILTINS;
+use crate::evaluator::object::{CompiledFunction, Object};
+use crate::tokens::tokens::{Span, TokenType};
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+/// represents the kinds of failure the compiler can report
+#[derive(Debug, PartialEq, Clone)]
+pub enum CompileErrorKind {
+    /// an identifier isn't bound in any enclosing scope
+    UndefinedVariable(String),
+    /// the operator has no bytecode equivalent
+    UnknownOperator(TokenType),
+    /// an operand doesn't fit the width of its instruction: too many
+    /// constants, bindings, arguments or elements, or a jump too far
+    TooLarge {
+        opcode: Opcode,
+        operand: usize,
+        max: usize,
+    },
+    /// an assignment to the name of a function inside its own body, where the
+    /// name refers to the function being defined
+    FunctionAssignment(String),
+    /// a macro literal left in the program, macro expansion only removes the
+    /// ones bound by top level `let` statements
+    UnexpandedMacro,
+}
+
+impl fmt::Display for CompileErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompileErrorKind::UndefinedVariable(name) => {
+                write!(f, "identifier not found: {}", name)
+            }
+            CompileErrorKind::UnknownOperator(operator) => {
+                write!(f, "unknown operator: {}", operator)
+            }
+            CompileErrorKind::FunctionAssignment(name) => {
+                write!(f, "cannot assign to {} inside its own body", name)
+            }
+            CompileErrorKind::TooLarge {
+                opcode,
+                operand,
+                max,
+            } => write!(
+                f,
+                "program too large: {} operand {} exceeds {}",
+                opcode.definition().name,
+                operand,
+                max
+            ),
+            CompileErrorKind::UnexpandedMacro => {
+                write!(f, "macros can only be bound by a top level let")
+            }
+        }
+    }
+}
+
+/// represents a failure to compile a program, located at the node that
+/// caused it
+///
+/// # Parameters
+///
+/// * `kind` - what went wrong
+///
+/// * `span` - the location of the offending node
+///
+#[derive(Debug, PartialEq)]
+pub struct CompileError {
+    pub kind: CompileErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for CompileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+impl std::error::Error for CompileError {}
+
+/// represents a compiled program, ready to be run by the vm
+///
+/// # Parameters
+///
+/// * `instructions` - the instructions of the top level of the program
+///
+/// * `constants` - the constant pool the instructions refer to by index
+///
+/// * `lines` - the source line of each run of top level instructions
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct Bytecode {
+    pub instructions: Instructions,
+    pub constants: Vec<Object>,
+    pub lines: Vec<LineEntry>,
+}
+
+impl Bytecode {
+    /// returns a listing of the top level instructions followed by those of
+    /// every compiled function in the constant pool
+    pub fn disassemble(&self) -> String {
+        let mut listing = format!("== main ==\n{}", code::disassemble(&self.instructions));
+
+        for (index, constant) in self.constants.iter().enumerate() {
+            if let Object::CompiledFunction(function) = constant {
+                listing.push_str(&format!(
+                    "== constant {}: fn/{}, {} locals ==\n{}",
+                    index,
+                    function.num_parameters,
+                    function.num_locals,
+                    code::disassemble(&function.instructions)
+                ));
+            }
+        }
+
+        listing
+    }
+}
+
+/// the opcode and position of an instruction already emitted
+#[derive(Debug, Clone, Copy)]
+struct EmittedInstruction {
+    opcode: Opcode,
+    position: usize,
+}
+
+/// the positions of the `break` and `continue` jumps of a loop being
+/// compiled, patched once the loop is compiled
+#[derive(Debug, Default)]
+struct Loop {
+    breaks: Vec<usize>,
+    continues: Vec<usize>,
+}
+
+/// the instructions of the function being compiled, the top level of the
+/// program is the outermost scope
+#[derive(Debug, Default)]
+struct CompilationScope {
+    instructions: Instructions,
+    lines: Vec<LineEntry>,
+    last_instruction: Option<EmittedInstruction>,
+    previous_instruction: Option<EmittedInstruction>,
+    loops: Vec<Loop>,
+}
+
+/// Represents a compiler lowering monkey lang ASTs to bytecode for the vm
+///
+/// # Parameters
+///
+/// * `constants` - the constant pool built so far
+///
+/// * `symbol_table` - the names bound in the scope being compiled
+///
+/// * `scopes` - the instructions of the function being compiled and of every
+///   enclosing function, innermost last
+///
+/// * `span` - the location of the node being compiled, its line is recorded
+///   for the instructions it emits
+///
+pub struct Compiler {
+    constants: Vec<Object>,
+    symbol_table: SymbolTable,
+    scopes: Vec<CompilationScope>,
+    span: Span,
+}
+
+impl Compiler {
+    /// returns a new Compiler with the builtin functions in scope
+    pub fn new() -> Compiler {
+        let mut symbol_table = SymbolTable::new();
+        for (index, (name, _)) in BUILTINS.iter().enumerate() {
+            symbol_table.define_builtin(index, name);
+        }
+
+        Compiler {
+            constants: vec![],
+            symbol_table,
+            scopes: vec![CompilationScope::default()],
+            span: Span::default(),
+        }
+    }
+}
+
+impl Default for Compiler {
+    fn default() -> Compiler {
+        Compiler::new()
+    }
+}
+
+impl Compiler {
+    /// compiles the statements of a program into the top level instructions
+    ///
+    /// # Arguments
+    ///
+    /// * `&mut self` - a mutable reference to the Compiler construct being used
+    ///
+    /// * `program` - the `Program` to compile, expected to have parsed without
+    ///   errors
+    ///
+    pub fn compile(&mut self, program: &Program) -> Result<(), CompileError> {
+        for statement in &program.statements {
+            self.compile_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    /// returns the instructions and constants compiled so far
+    pub fn bytecode(&self) -> Bytecode {
+        Bytecode {
+            instructions: self.scope().instructions.clone(),
+            constants: self.constants.clone(),
+            lines: self.scope().lines.clone(),
+        }
+    }
+
+    fn compile_statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        // children restore the line of their parent once compiled
+        let outer = mem::replace(&mut self.span, statement.span());
+        let result = self.lower_statement(statement);
+        self.span = outer;
+        result
+    }
+
+    fn lower_statement(&mut self, statement: &Statement) -> Result<(), CompileError> {
+        match statement {
+            Statement::Let { name, value, .. } => {
+                // functions are bound before their body is compiled so they
+                // can call themselves
+                let symbol = match value {
+                    Expression::FunctionLiteral {
+                        parameters, body, ..
+                    } => {
+                        let symbol = self.symbol_table.define(name);
+                        self.compile_function(Some(name), parameters, body)?;
+                        symbol
+                    }
+                    value => {
+                        self.compile_expression(value)?;
+                        self.symbol_table.define(name)
+                    }
+                };
+
+                self.store_symbol(&symbol)?;
+            }
+            Statement::Assign {
+                name,
+                operator,
+                value,
+                span,
+            } => {
+                let symbol = match self.symbol_table.resolve(name) {
+                    Some(symbol) => symbol,
+                    None => return Err(undefined_variable(name, *span)),
+                };
+                match self.symbol_table.origin(&symbol).scope {
+                    SymbolScope::Global | SymbolScope::BlockGlobal | SymbolScope::Local => {}
+                    SymbolScope::Builtin => return Err(undefined_variable(name, *span)),
+                    SymbolScope::Free | SymbolScope::Function => {
+                        return Err(CompileError {
+                            kind: CompileErrorKind::FunctionAssignment(name.clone()),
+                            span: *span,
+                        })
+                    }
+                }
+
+                match operator {
+                    Some(operator) => {
+                        self.load_symbol(&symbol)?;
+                        self.compile_expression(value)?;
+                        let op = infix_opcode(*operator)
+                            .ok_or_else(|| unknown_operator(*operator, *span))?;
+                        self.emit(op, &[])?;
+                    }
+                    None => self.compile_expression(value)?,
+                }
+                self.assign_symbol(&symbol)?;
+            }
+            Statement::While {
+                condition, body, ..
+            } => self.compile_while_statement(condition, body)?,
+            Statement::For {
+                variable,
+                iterable,
+                body,
+                ..
+            } => self.compile_for_statement(variable, iterable, body)?,
+            Statement::Break { .. } => {
+                let jump = self.emit(Opcode::Jump, &[0])?;
+                self.current_loop().breaks.push(jump);
+            }
+            Statement::Continue { .. } => {
+                let jump = self.emit(Opcode::Jump, &[0])?;
+                self.current_loop().continues.push(jump);
+            }
+            Statement::Return { value, .. } => {
+                self.compile_expression(value)?;
+                self.emit(Opcode::ReturnValue, &[])?;
+            }
+            Statement::Expression(expression) => {
+                self.compile_expression(expression)?;
+                self.emit(Opcode::Pop, &[])?;
+            }
+            Statement::Block(block) => self.compile_block_statement(block)?,
+        }
+        Ok(())
+    }
+
+    fn compile_block_statement(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        for statement in &block.statements {
+            self.compile_statement(statement)?;
+        }
+        Ok(())
+    }
+
+    /// compiles a block used as a value, leaving the value of its last
+    /// expression statement on the stack, or `null`
+    fn compile_block_value(&mut self, block: &BlockStatement) -> Result<(), CompileError> {
+        self.compile_block_statement(block)?;
+        if self.last_instruction_is(Opcode::Pop) {
+            self.remove_last_pop();
+        } else {
+            self.emit(Opcode::Null, &[])?;
+        }
+        Ok(())
+    }
+
+    fn compile_expression(&mut self, expression: &Expression) -> Result<(), CompileError> {
+        let outer = mem::replace(&mut self.span, expression.span());
+        let result = self.lower_expression(expression);
+        self.span = outer;
+        result
+    }
+
+    fn lower_expression(&mut self, expression: &Expression) -> Result<(), CompileError> {
+        match expression {
+            Expression::Identifier { name, span } => match self.symbol_table.resolve(name) {
+                Some(symbol) => self.load_symbol(&symbol)?,
+                None => return Err(undefined_variable(name, *span)),
+            },
+            Expression::IntegerLiteral { value, .. } => {
+                let index = self.add_constant(Object::Integer(*value))?;
+                self.emit(Opcode::Constant, &[index])?;
+            }
+            Expression::FloatLiteral { value, .. } => {
+                let index = self.add_constant(Object::Float(*value))?;
+                self.emit(Opcode::Constant, &[index])?;
+            }
+            Expression::StringLiteral { value, .. } => {
+                let index = self.add_constant(Object::String(value.clone()))?;
+                self.emit(Opcode::Constant, &[index])?;
+            }
+            Expression::Boolean { value: true, .. } => {
+                self.emit(Opcode::True, &[])?;
+            }
+            Expression::Boolean { value: false, .. } => {
+                self.emit(Opcode::False, &[])?;
+            }
+            Expression::Prefix {
+                operator,
+                right,
+                span,
+            } => {
+                self.compile_expression(right)?;
+                let op = match operator {
+                    TokenType::BANG => Opcode::Bang,
+                    TokenType::MINUS => Opcode::Minus,
+                    _ => return Err(unknown_operator(*operator, *span)),
+                };
+                self.emit(op, &[])?;
+            }
+            Expression::Infix {
+                left,
+                operator: operator @ (TokenType::AND | TokenType::OR),
+                right,
+                ..
+            } => self.compile_logical_expression(*operator, left, right)?,
+            Expression::Infix {
+                left,
+                operator,
+                right,
+                span,
+            } => {
+                self.compile_expression(left)?;
+                self.compile_expression(right)?;
+                let op =
+                    infix_opcode(*operator).ok_or_else(|| unknown_operator(*operator, *span))?;
+                self.emit(op, &[])?;
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                self.compile_expression(condition)?;
+                // the jump offsets are patched once the branches are emitted
+                let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[0])?;
+                self.compile_block_value(consequence)?;
+                let jump = self.emit(Opcode::Jump, &[0])?;
+
+                let after_consequence = self.scope().instructions.len();
+                self.change_operand(jump_not_truthy, after_consequence)?;
+
+                match alternative {
+                    Some(alternative) => self.compile_block_value(alternative)?,
+                    None => {
+                        self.emit(Opcode::Null, &[])?;
+                    }
+                }
+
+                let after_alternative = self.scope().instructions.len();
+                self.change_operand(jump, after_alternative)?;
+            }
+            Expression::ArrayLiteral { elements, .. } => {
+                for element in elements {
+                    self.compile_expression(element)?;
+                }
+                self.emit(Opcode::Array, &[elements.len()])?;
+            }
+            Expression::HashLiteral { pairs, .. } => {
+                for (key, value) in pairs {
+                    self.compile_expression(key)?;
+                    self.compile_expression(value)?;
+                }
+                self.emit(Opcode::Hash, &[pairs.len() * 2])?;
+            }
+            Expression::Index { left, index, .. } => {
+                self.compile_expression(left)?;
+                self.compile_expression(index)?;
+                self.emit(Opcode::Index, &[])?;
+            }
+            Expression::FunctionLiteral {
+                parameters, body, ..
+            } => self.compile_function(None, parameters, body)?,
+            Expression::MacroLiteral { span, .. } => {
+                return Err(CompileError {
+                    kind: CompileErrorKind::UnexpandedMacro,
+                    span: *span,
+                })
+            }
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => {
+                self.compile_expression(function)?;
+                for argument in arguments {
+                    self.compile_expression(argument)?;
+                }
+                self.emit(Opcode::Call, &[arguments.len()])?;
+            }
+        }
+        Ok(())
+    }
+
+    /// compiles a function literal into a constant and emits the closure
+    /// creating it. `name` is the binding the function is assigned to, if any.
+    fn compile_function(
+        &mut self,
+        name: Option<&str>,
+        parameters: &[String],
+        body: &BlockStatement,
+    ) -> Result<(), CompileError> {
+        self.enter_scope();
+        if let Some(name) = name {
+            self.symbol_table.define_function_name(name);
+        }
+        for parameter in parameters {
+            self.symbol_table.define(parameter);
+        }
+
+        self.compile_block_statement(body)?;
+        if self.last_instruction_is(Opcode::Pop) {
+            self.replace_last_pop_with_return();
+        }
+        if !self.last_instruction_is(Opcode::ReturnValue) {
+            self.emit(Opcode::Return, &[])?;
+        }
+
+        let (scope, symbol_table) = self.leave_scope();
+        for symbol in &symbol_table.free_symbols {
+            self.capture_symbol(symbol)?;
+        }
+
+        let function = CompiledFunction {
+            instructions: scope.instructions,
+            num_locals: symbol_table.num_definitions,
+            num_parameters: parameters.len(),
+            lines: scope.lines,
+        };
+        let index = self.add_constant(Object::CompiledFunction(Rc::new(function)))?;
+        self.emit(Opcode::Closure, &[index, symbol_table.free_symbols.len()])?;
+        Ok(())
+    }
+
+    fn load_symbol(&mut self, symbol: &Symbol) -> Result<(), CompileError> {
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::BlockGlobal => {
+                self.emit(Opcode::GetGlobal, &[symbol.index])
+            }
+            SymbolScope::Local => self.emit(Opcode::GetLocal, &[symbol.index]),
+            SymbolScope::Builtin => self.emit(Opcode::GetBuiltin, &[symbol.index]),
+            SymbolScope::Free => self.emit(Opcode::GetFree, &[symbol.index]),
+            SymbolScope::Function => self.emit(Opcode::CurrentClosure, &[]),
+        }?;
+        Ok(())
+    }
+
+    /// pushes what a closure captures of `symbol`: the cell shared with the
+    /// enclosing function for a local or free symbol
+    fn capture_symbol(&mut self, symbol: &Symbol) -> Result<(), CompileError> {
+        match symbol.scope {
+            SymbolScope::BlockGlobal => self.emit(Opcode::CaptureGlobal, &[symbol.index])?,
+            SymbolScope::Local => self.emit(Opcode::CaptureLocal, &[symbol.index])?,
+            SymbolScope::Free => self.emit(Opcode::CaptureFree, &[symbol.index])?,
+            _ => return self.load_symbol(symbol),
+        };
+        Ok(())
+    }
+
+    /// assigns the value on the stack to the existing binding of `symbol`,
+    /// seen by the closures capturing it
+    fn assign_symbol(&mut self, symbol: &Symbol) -> Result<(), CompileError> {
+        match symbol.scope {
+            SymbolScope::BlockGlobal => self.emit(Opcode::AssignGlobal, &[symbol.index])?,
+            SymbolScope::Local => self.emit(Opcode::AssignLocal, &[symbol.index])?,
+            SymbolScope::Free => self.emit(Opcode::SetFree, &[symbol.index])?,
+            _ => return self.store_symbol(symbol),
+        };
+        Ok(())
+    }
+
+    fn store_symbol(&mut self, symbol: &Symbol) -> Result<(), CompileError> {
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::BlockGlobal => {
+                self.emit(Opcode::SetGlobal, &[symbol.index])
+            }
+            _ => self.emit(Opcode::SetLocal, &[symbol.index]),
+        }?;
+        Ok(())
+    }
+
+    /// adds `object` to the constant pool and returns its index, which must
+    /// fit the operand of `OpConstant`
+    fn add_constant(&mut self, object: Object) -> Result<usize, CompileError> {
+        let index = self.constants.len();
+        self.check_operand(Opcode::Constant, index, 2)?;
+        self.constants.push(object);
+        Ok(index)
+    }
+
+    /// returns the index of the integer constant `value`, shared by every loop
+    /// rather than added for each
+    fn loop_constant(&mut self, value: i64) -> Result<usize, CompileError> {
+        let existing = self
+            .constants
+            .iter()
+            .position(|constant| matches!(constant, Object::Integer(v) if *v == value));
+        match existing {
+            Some(index) => Ok(index),
+            None => self.add_constant(Object::Integer(value)),
+        }
+    }
+
+    fn scope(&self) -> &CompilationScope {
+        self.scopes.last().expect("compiler has no scope")
+    }
+
+    fn scope_mut(&mut self) -> &mut CompilationScope {
+        self.scopes.last_mut().expect("compiler has no scope")
+    }
+
+    /// returns the jumps of the innermost loop, the parser only accepts
+    /// `break` and `continue` inside one
+    fn current_loop(&mut self) -> &mut Loop {
+        self.scope_mut()
+            .loops
+            .last_mut()
+            .expect("compiler has no loop")
+    }
+
+    /// compiles `&&` and `||` to jumps, so `right` only runs when `left`
+    /// doesn't decide the result. `right` is negated twice to turn it into a
+    /// boolean, as the evaluator does.
+    fn compile_logical_expression(
+        &mut self,
+        operator: TokenType,
+        left: &Expression,
+        right: &Expression,
+    ) -> Result<(), CompileError> {
+        self.compile_expression(left)?;
+        let jump_not_truthy = self.emit(Opcode::JumpNotTruthy, &[0])?;
+
+        if operator == TokenType::AND {
+            self.compile_expression(right)?;
+            self.emit(Opcode::Bang, &[])?;
+            self.emit(Opcode::Bang, &[])?;
+        } else {
+            self.emit(Opcode::True, &[])?;
+        }
+        let jump = self.emit(Opcode::Jump, &[0])?;
+
+        let after_truthy = self.scope().instructions.len();
+        self.change_operand(jump_not_truthy, after_truthy)?;
+
+        if operator == TokenType::AND {
+            self.emit(Opcode::False, &[])?;
+        } else {
+            self.compile_expression(right)?;
+            self.emit(Opcode::Bang, &[])?;
+            self.emit(Opcode::Bang, &[])?;
+        }
+
+        let after_falsy = self.scope().instructions.len();
+        self.change_operand(jump, after_falsy)?;
+        Ok(())
+    }
+
+    /// compiles `while (<condition>) <body>`
+    fn compile_while_statement(
+        &mut self,
+        condition: &Expression,
+        body: &BlockStatement,
+    ) -> Result<(), CompileError> {
+        let start = self.scope().instructions.len();
+        self.compile_expression(condition)?;
+        let exit = self.emit(Opcode::JumpNotTruthy, &[0])?;
+
+        self.scope_mut().loops.push(Loop::default());
+        self.compile_block_statement(body)?;
+        self.emit(Opcode::Jump, &[start])?;
+
+        let end = self.scope().instructions.len();
+        self.change_operand(exit, end)?;
+        self.close_loop(start, end)?;
+        Ok(())
+    }
+
+    /// compiles `for (<variable> in <iterable>) <body>`. the array and the
+    /// index of the next element are kept in hidden bindings, named so no
+    /// identifier can refer to them.
+    fn compile_for_statement(
+        &mut self,
+        variable: &str,
+        iterable: &Expression,
+        body: &BlockStatement,
+    ) -> Result<(), CompileError> {
+        let depth = self.scope().loops.len();
+        let array = self.symbol_table.define(&format!("@array{}", depth));
+        let index = self.symbol_table.define(&format!("@index{}", depth));
+        let zero = self.loop_constant(0)?;
+        let one = self.loop_constant(1)?;
+
+        self.compile_expression(iterable)?;
+        self.store_symbol(&array)?;
+        self.emit(Opcode::Constant, &[zero])?;
+        self.store_symbol(&index)?;
+
+        let start = self.scope().instructions.len();
+        self.load_symbol(&array)?;
+        self.load_symbol(&index)?;
+        let exit = self.emit(Opcode::IterNext, &[0])?;
+
+        // the variable and the bindings of the body belong to the loop
+        self.symbol_table.open_block();
+        let element = self.symbol_table.define(variable);
+        self.store_symbol(&element)?;
+
+        self.scope_mut().loops.push(Loop::default());
+        let body = self.compile_block_statement(body);
+        self.symbol_table.close_block();
+        body?;
+
+        let next = self.scope().instructions.len();
+        self.load_symbol(&index)?;
+        self.emit(Opcode::Constant, &[one])?;
+        self.emit(Opcode::Add, &[])?;
+        self.store_symbol(&index)?;
+        self.emit(Opcode::Jump, &[start])?;
+
+        let end = self.scope().instructions.len();
+        self.change_operand(exit, end)?;
+        self.close_loop(next, end)?;
+        Ok(())
+    }
+
+    /// patches the `continue` jumps of the innermost loop to `next` and its
+    /// `break` jumps to `end`, where the loop leaves `null` as the last popped
+    /// value, what a loop evaluates to
+    fn close_loop(&mut self, next: usize, end: usize) -> Result<(), CompileError> {
+        let jumps = self.scope_mut().loops.pop().expect("compiler has no loop");
+        for position in jumps.continues {
+            self.change_operand(position, next)?;
+        }
+        for position in jumps.breaks {
+            self.change_operand(position, end)?;
+        }
+        self.emit(Opcode::Null, &[])?;
+        self.emit(Opcode::Pop, &[])?;
+        Ok(())
+    }
+
+    /// returns an error located at the node being compiled when `operand`
+    /// doesn't fit an operand of `op` that is `width` bytes wide
+    fn check_operand(&self, op: Opcode, operand: usize, width: usize) -> Result<(), CompileError> {
+        let max = max_operand(width);
+        if operand <= max {
+            return Ok(());
+        }
+        Err(CompileError {
+            kind: CompileErrorKind::TooLarge {
+                opcode: op,
+                operand,
+                max,
+            },
+            span: self.span,
+        })
+    }
+
+    /// appends an instruction to the current scope and returns its position.
+    /// fails if an operand doesn't fit its width.
+    fn emit(&mut self, op: Opcode, operands: &[usize]) -> Result<usize, CompileError> {
+        for (operand, width) in operands.iter().zip(op.definition().operand_widths) {
+            self.check_operand(op, *operand, *width)?;
+        }
+
+        let instruction = make(op, operands);
+        let line = self.span.line;
+        let scope = self.scope_mut();
+        let position = scope.instructions.len();
+        scope.instructions.extend_from_slice(&instruction);
+
+        if scope.lines.last().map(|entry| entry.line) != Some(line) {
+            scope.lines.push(LineEntry {
+                offset: position,
+                line,
+            });
+        }
+
+        scope.previous_instruction = scope.last_instruction;
+        scope.last_instruction = Some(EmittedInstruction {
+            opcode: op,
+            position,
+        });
+        Ok(position)
+    }
+
+    fn last_instruction_is(&self, op: Opcode) -> bool {
+        matches!(self.scope().last_instruction, Some(last) if last.opcode == op)
+    }
+
+    fn remove_last_pop(&mut self) {
+        let scope = self.scope_mut();
+        if let Some(last) = scope.last_instruction {
+            scope.instructions.truncate(last.position);
+            scope.lines.retain(|entry| entry.offset < last.position);
+            scope.last_instruction = scope.previous_instruction;
+        }
+    }
+
+    fn replace_last_pop_with_return(&mut self) {
+        let scope = self.scope_mut();
+        if let Some(last) = scope.last_instruction.as_mut() {
+            scope.instructions[last.position] = Opcode::ReturnValue as u8;
+            last.opcode = Opcode::ReturnValue;
+        }
+    }
+
+    /// rewrites the operand of the instruction at `position`
+    fn change_operand(&mut self, position: usize, operand: usize) -> Result<(), CompileError> {
+        let scope = self.scope();
+        let op = Opcode::try_from(scope.instructions[position]).expect("patched an invalid opcode");
+        self.check_operand(op, operand, op.definition().operand_widths[0])?;
+
+        let instruction = make(op, &[operand]);
+        let scope = self.scope_mut();
+        scope.instructions[position..position + instruction.len()].copy_from_slice(&instruction);
+        Ok(())
+    }
+
+    fn enter_scope(&mut self) {
+        self.scopes.push(CompilationScope::default());
+        let outer = mem::take(&mut self.symbol_table);
+        self.symbol_table = SymbolTable::new_enclosed(outer);
+    }
+
+    /// returns the instructions and symbol table of the scope being left
+    fn leave_scope(&mut self) -> (CompilationScope, SymbolTable) {
+        let scope = self.scopes.pop().expect("compiler has no scope");
+        let outer = self
+            .symbol_table
+            .take_outer()
+            .expect("left the global scope");
+        let inner = mem::replace(&mut self.symbol_table, outer);
+        (scope, inner)
+    }
+}
+
+/// returns the opcode of an arithmetic or comparison operator
+fn infix_opcode(operator: TokenType) -> Option<Opcode> {
+    match operator {
+        TokenType::PLUS => Some(Opcode::Add),
+        TokenType::MINUS => Some(Opcode::Sub),
+        TokenType::ASTERISK => Some(Opcode::Mul),
+        TokenType::SLASH => Some(Opcode::Div),
+        TokenType::PERCENT => Some(Opcode::Mod),
+        TokenType::POWER => Some(Opcode::Pow),
+        TokenType::GT => Some(Opcode::GreaterThan),
+        TokenType::LT => Some(Opcode::LessThan),
+        TokenType::GtEq => Some(Opcode::GreaterEqual),
+        TokenType::LtEq => Some(Opcode::LessEqual),
+        TokenType::EQ => Some(Opcode::Equal),
+        TokenType::NotEq => Some(Opcode::NotEqual),
+        _ => None,
+    }
+}
+
+fn undefined_variable(name: &str, span: Span) -> CompileError {
+    CompileError {
+        kind: CompileErrorKind::UndefinedVariable(name.to_string()),
+        span,
+    }
+}
+
+fn unknown_operator(operator: TokenType, span: Span) -> CompileError {
+    CompileError {
+        kind: CompileErrorKind::UnknownOperator(operator),
+        span,
+    }
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+
+    fn compile(input: &str) -> Result<Bytecode, CompileError> {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert_eq!(parser.errors(), &[], "input should parse");
+
+        let mut compiler = Compiler::new();
+        compiler.compile(&program)?;
+        Ok(compiler.bytecode())
+    }
+
+    /// concatenates the encoded instructions
+    fn instructions(instructions: &[Instructions]) -> Instructions {
+        instructions.concat()
+    }
+
+    fn function(instructions: &[Instructions], num_locals: usize, num_parameters: usize) -> Object {
+        Object::CompiledFunction(Rc::new(CompiledFunction {
+            instructions: instructions.concat(),
+            num_locals,
+            num_parameters,
+            lines: vec![LineEntry { offset: 0, line: 1 }],
+        }))
+    }
+
+    #[test]
+    fn integer_arithmetic() {
+        let bytecode = compile("1 + 2; -3 < 4").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            instructions(&[
+                make(Opcode::Constant, &[0]),
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Add, &[]),
+                make(Opcode::Pop, &[]),
+                make(Opcode::Constant, &[2]),
+                make(Opcode::Minus, &[]),
+                make(Opcode::Constant, &[3]),
+                make(Opcode::LessThan, &[]),
+                make(Opcode::Pop, &[]),
+            ])
+        );
+        assert_eq!(
+            bytecode.constants,
+            vec![
+                Object::Integer(1),
+                Object::Integer(2),
+                Object::Integer(3),
+                Object::Integer(4)
+            ]
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        let bytecode = compile("if (true) { 10 }; 3333;").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            instructions(&[
+                // 0000
+                make(Opcode::True, &[]),
+                // 0001
+                make(Opcode::JumpNotTruthy, &[10]),
+                // 0004
+                make(Opcode::Constant, &[0]),
+                // 0007
+                make(Opcode::Jump, &[11]),
+                // 0010
+                make(Opcode::Null, &[]),
+                // 0011
+                make(Opcode::Pop, &[]),
+                // 0012
+                make(Opcode::Constant, &[1]),
+                make(Opcode::Pop, &[]),
+            ])
+        );
+
+        let bytecode = compile("if (true) { let a = 1; } else { 20 }").unwrap();
+        assert_eq!(
+            bytecode.instructions,
+            instructions(&[
+                // 0000
+                make(Opcode::True, &[]),
+                // 0001
+                make(Opcode::JumpNotTruthy, &[14]),
+                // 0004
+                make(Opcode::Constant, &[0]),
+                // 0007
+                make(Opcode::SetGlobal, &[0]),
+                // 0010
+                make(Opcode::Null, &[]),
+                // 0011
+                make(Opcode::Jump, &[17]),
+                // 0014
+                make(Opcode::Constant, &[1]),
+                // 0017
+                make(Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn logical_operators() {
+        let bytecode = compile("true && false; 1 || 2 >= 3").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            instructions(&[
+                // 0000
+                make(Opcode::True, &[]),
+                // 0001
+                make(Opcode::JumpNotTruthy, &[10]),
+                // 0004
+                make(Opcode::False, &[]),
+                // 0005
+                make(Opcode::Bang, &[]),
+                // 0006
+                make(Opcode::Bang, &[]),
+                // 0007
+                make(Opcode::Jump, &[11]),
+                // 0010
+                make(Opcode::False, &[]),
+                // 0011
+                make(Opcode::Pop, &[]),
+                // 0012
+                make(Opcode::Constant, &[0]),
+                // 0015
+                make(Opcode::JumpNotTruthy, &[22]),
+                // 0018
+                make(Opcode::True, &[]),
+                // 0019
+                make(Opcode::Jump, &[31]),
+                // 0022
+                make(Opcode::Constant, &[1]),
+                // 0025
+                make(Opcode::Constant, &[2]),
+                // 0028
+                make(Opcode::GreaterEqual, &[]),
+                // 0029
+                make(Opcode::Bang, &[]),
+                // 0030
+                make(Opcode::Bang, &[]),
+                // 0031
+                make(Opcode::Pop, &[]),
+            ])
+        );
+    }
+
+    #[test]
+    fn while_loops() {
+        let bytecode =
+            compile("let i = 0; while (i < 3) { i += 1; } while (true) { break; continue; }")
+                .unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            instructions(&[
+                // 0000
+                make(Opcode::Constant, &[0]),
+                // 0003
+                make(Opcode::SetGlobal, &[0]),
+                // 0006
+                make(Opcode::GetGlobal, &[0]),
+                // 0009
+                make(Opcode::Constant, &[1]),
+                // 0012
+                make(Opcode::LessThan, &[]),
+                // 0013
+                make(Opcode::JumpNotTruthy, &[29]),
+                // 0016
+                make(Opcode::GetGlobal, &[0]),
+                // 0019
+                make(Opcode::Constant, &[2]),
+                // 0022
+                make(Opcode::Add, &[]),
+                // 0023
+                make(Opcode::SetGlobal, &[0]),
+                // 0026
+                make(Opcode::Jump, &[6]),
+                // 0029
+                make(Opcode::Null, &[]),
+                // 0030
+                make(Opcode::Pop, &[]),
+                // 0031
+                make(Opcode::True, &[]),
+                // 0032
+                make(Opcode::JumpNotTruthy, &[44]),
+                // 0035
+                make(Opcode::Jump, &[44]),
+                // 0038
+                make(Opcode::Jump, &[31]),
+                // 0041
+                make(Opcode::Jump, &[31]),
+                // 0044
+                make(Opcode::Null, &[]),
+                // 0045
+                make(Opcode::Pop, &[]),
+                // 0046
+            ])
+        );
+    }
+
+    #[test]
+    fn for_loops() {
+        let bytecode = compile("for (x in [1]) { x }").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            instructions(&[
+                // 0000
+                make(Opcode::Constant, &[2]),
+                // 0003
+                make(Opcode::Array, &[1]),
+                // 0006
+                make(Opcode::SetGlobal, &[0]),
+                // 0009
+                make(Opcode::Constant, &[0]),
+                // 0012
+                make(Opcode::SetGlobal, &[1]),
+                // 0015
+                make(Opcode::GetGlobal, &[0]),
+                // 0018
+                make(Opcode::GetGlobal, &[1]),
+                // 0021
+                make(Opcode::IterNext, &[44]),
+                // 0024
+                make(Opcode::SetGlobal, &[2]),
+                // 0027
+                make(Opcode::GetGlobal, &[2]),
+                // 0030
+                make(Opcode::Pop, &[]),
+                // 0031
+                make(Opcode::GetGlobal, &[1]),
+                // 0034
+                make(Opcode::Constant, &[1]),
+                // 0037
+                make(Opcode::Add, &[]),
+                // 0038
+                make(Opcode::SetGlobal, &[1]),
+                // 0041
+                make(Opcode::Jump, &[15]),
+                // 0044
+                make(Opcode::Null, &[]),
+                // 0045
+                make(Opcode::Pop, &[]),
+                // 0046
+            ])
+        );
+
+        // closures capture the variable of a top level loop like a local
+        let captured = compile("for (x in [1]) { fn() { x } }").unwrap();
+        let capture = [
+            make(Opcode::CaptureGlobal, &[2]),
+            make(Opcode::Closure, &[3, 1]),
+        ]
+        .concat();
+        assert!(captured
+            .instructions
+            .windows(capture.len())
+            .any(|window| window == capture.as_slice()));
+    }
+
+    #[test]
+    fn global_let_statements() {
+        let bytecode = compile("let one = 1; let two = one; let one = two;").unwrap();
+
+        assert_eq!(
+            bytecode.instructions,
+            instructions(&[
+                make(Opcode::Constant, &[0]),
+                make(Opcode::SetGlobal, &[0]),
+                make(Opcode::GetGlobal, &[0]),
+                make(Opcode::SetGlobal, &[1]),
+                make(Opcode::GetGlobal, &[1]),
+                make(Opcode::SetGlobal, &[0]),
+            ])
+        );
+    }
+
+    #[test]
+    fn functions_and_closures() {
+        let bytecode = compile("fn(a) { fn(b) { a + b + len([]) } }").unwrap();
+
+        assert_eq!(
+            bytecode.constants,
+            vec![
+                function(
+                    &[
+                        make(Opcode::GetFree, &[0]),
+                        make(Opcode::GetLocal, &[0]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::GetBuiltin, &[0]),
+                        make(Opcode::Array, &[0]),
+                        make(Opcode::Call, &[1]),
+                        make(Opcode::Add, &[]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1
+                ),
+                function(
+                    &[
+                        make(Opcode::CaptureLocal, &[0]),
+                        make(Opcode::Closure, &[0, 1]),
+                        make(Opcode::ReturnValue, &[]),
+                    ],
+                    1,
+                    1
+                ),
+            ]
+        );
+        assert_eq!(
+            bytecode.instructions,
+            instructions(&[make(Opcode::Closure, &[1, 0]), make(Opcode::Pop, &[])])
+        );
+
+        let bytecode = compile("fn() { }").unwrap();
+        assert_eq!(
+            bytecode.constants,
+            vec![function(&[make(Opcode::Return, &[])], 0, 0)]
+        );
+    }
+
+    #[test]
+    fn recursive_functions() {
+        let bytecode = compile(
+            "let wrapper = fn() { let countDown = fn(x) { countDown(x - 1) }; countDown(1) };",
+        )
+        .unwrap();
+
+        assert_eq!(
+            bytecode.constants[1],
+            function(
+                &[
+                    make(Opcode::CurrentClosure, &[]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Sub, &[]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+                1,
+                1
+            )
+        );
+        assert_eq!(
+            bytecode.constants[3],
+            function(
+                &[
+                    make(Opcode::Closure, &[1, 0]),
+                    make(Opcode::SetLocal, &[0]),
+                    make(Opcode::GetLocal, &[0]),
+                    make(Opcode::Constant, &[2]),
+                    make(Opcode::Call, &[1]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+                1,
+                0
+            )
+        );
+    }
+
+    #[test]
+    fn line_table() {
+        let bytecode = compile("let a = 1;\nlet b = fn(x) {\n  x +\n  a\n};\nb(a)").unwrap();
+
+        let line = |offset| code::line_at(&bytecode.lines, offset);
+        assert_eq!(line(0), Some(1));
+        assert_eq!(line(3), Some(1));
+        assert_eq!(line(6), Some(2));
+        assert_eq!(line(bytecode.instructions.len() - 1), Some(6));
+
+        match &bytecode.constants[1] {
+            Object::CompiledFunction(function) => assert_eq!(
+                function.lines,
+                vec![
+                    LineEntry { offset: 0, line: 3 },
+                    LineEntry { offset: 2, line: 4 },
+                    LineEntry { offset: 5, line: 3 },
+                ]
+            ),
+            other => panic!("expected compiled function, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn disassemble_bytecode() {
+        let bytecode = compile("let f = fn(x) { x * 2 }; f(1)").unwrap();
+
+        assert_eq!(
+            bytecode.disassemble(),
+            "== main ==
+0000 OpClosure 1 0
+0004 OpSetGlobal 0
+0007 OpGetGlobal 0
+0010 OpConstant 2
+0013 OpCall 1
+0015 OpPop
+== constant 1: fn/1, 1 locals ==
+0000 OpGetLocal 0
+0002 OpConstant 0
+0005 OpMul
+0006 OpReturnValue
+"
+        );
+    }
+
+    /// returns a program adding `count` string constants, then binding and
+    /// reading `a`
+    fn with_constants(count: usize) -> String {
+        let strings: String = (0..count).map(|i| format!("\"s{}\";", i)).collect();
+        strings + "let a = 123456; a"
+    }
+
+    /// returns a function with `count` locals, called to add the first and the
+    /// last
+    fn with_locals(count: usize) -> String {
+        let lets: String = (0..count).map(|i| format!("let v{} = {};", i, i)).collect();
+        format!("fn() {{ {} v0 + v{} }}()", lets, count - 1)
+    }
+
+    #[test]
+    fn operand_limits() {
+        // the constant of `123456` is the last that fits, then the first that
+        // doesn't
+        assert!(compile(&with_constants(65_535)).is_ok());
+        assert_eq!(
+            compile(&with_constants(65_536)).unwrap_err().kind,
+            CompileErrorKind::TooLarge {
+                opcode: Opcode::Constant,
+                operand: 65_536,
+                max: 65_535,
+            }
+        );
+
+        assert!(compile(&with_locals(256)).is_ok());
+        assert_eq!(
+            compile(&with_locals(257)).unwrap_err().kind,
+            CompileErrorKind::TooLarge {
+                opcode: Opcode::SetLocal,
+                operand: 256,
+                max: 255,
+            }
+        );
+
+        let arguments = vec!["1"; 256].join(", ");
+        let err = compile(&format!("len({})", arguments)).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "1:1: program too large: OpCall operand 256 exceeds 255"
+        );
+    }
+
+    #[test]
+    fn loops_share_constants() {
+        let bytecode = compile("for (x in []) {} for (y in []) {}").unwrap();
+        assert_eq!(
+            bytecode.constants,
+            vec![Object::Integer(0), Object::Integer(1)]
+        );
+    }
+
+    #[test]
+    fn undefined_variables() {
+        assert_eq!(
+            compile("let a = 1;\nfn() { a + b }"),
+            Err(CompileError {
+                kind: CompileErrorKind::UndefinedVariable("b".to_string()),
+                span: Span::new(22, 23, 2, 12),
+            })
+        );
+        assert_eq!(
+            compile("let c = c;").unwrap_err().to_string(),
+            "1:9: identifier not found: c"
+        );
+        assert_eq!(
+            compile("len = 1;").unwrap_err().to_string(),
+            "1:1: identifier not found: len"
+        );
+    }
+
+    #[test]
+    fn captured_assignments() {
+        let bytecode = compile("fn(a) { a = 2; fn() { a += 1; } }").unwrap();
+
+        assert_eq!(
+            bytecode.constants[2],
+            function(
+                &[
+                    make(Opcode::GetFree, &[0]),
+                    make(Opcode::Constant, &[1]),
+                    make(Opcode::Add, &[]),
+                    make(Opcode::SetFree, &[0]),
+                    make(Opcode::Return, &[]),
+                ],
+                0,
+                0
+            )
+        );
+        assert_eq!(
+            bytecode.constants[3],
+            function(
+                &[
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::AssignLocal, &[0]),
+                    make(Opcode::CaptureLocal, &[0]),
+                    make(Opcode::Closure, &[2, 1]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+                1,
+                1
+            )
+        );
+
+        let nested = compile("fn(a) { fn() { fn() { a = 1; } } }").unwrap();
+        assert_eq!(
+            nested.constants[2],
+            function(
+                &[
+                    make(Opcode::CaptureFree, &[0]),
+                    make(Opcode::Closure, &[1, 1]),
+                    make(Opcode::ReturnValue, &[]),
+                ],
+                0,
+                0
+            )
+        );
+
+        assert!(compile("let a = 1; fn() { a = 2; }").is_ok());
+        assert_eq!(
+            compile("let f = fn() { fn() { f = 1; } };"),
+            Err(CompileError {
+                kind: CompileErrorKind::FunctionAssignment("f".to_string()),
+                span: Span::new(22, 28, 1, 23),
+            })
+        );
+    }
+
+    #[test]
+    fn unexpanded_macros() {
+        assert_eq!(
+            compile("fn() { macro(x) { x } }"),
+            Err(CompileError {
+                kind: CompileErrorKind::UnexpandedMacro,
+                span: Span::new(7, 21, 1, 8),
+            })
+        );
+    }
+}
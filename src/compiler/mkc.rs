@@ -0,0 +1,560 @@
+//! the `.mkc` file format for compiled bytecode
+//!
+//! a file is a fixed size header followed by a payload. integers are big
+//! endian.
+//!
+//! * header - the magic bytes `MKC\0`, the format version as a `u16`, the
+//!   payload length as a `u32` and the CRC-32 of the payload as a `u32`
+//!
+//! * payload - the constant pool, then the top level instructions and their
+//!   line table
+//!
+//! constants are tagged with a byte: `0` integer (`i64`), `1` string (length
+//! prefixed UTF-8), `2` compiled function (locals, parameters, instructions
+//! and line table), `3` float (the bits of an `f64`). instructions are length
+//! prefixed, line tables are a count of `(offset, line)` pairs. every count
+//! and length is a `u32`.
+//!
+//! decoded instructions are validated before they reach the vm: every opcode
+//! is known with all its operands, constant indices are within the pool and
+//! jumps land on an instruction.
+
+use crate::code::code::{read_operands, Instructions, LineEntry, Opcode};
+use crate::compiler::compiler::Bytecode;
+use crate::evaluator::object::{CompiledFunction, Object};
+use std::convert::TryFrom;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::rc::Rc;
+
+/// the bytes every `.mkc` file starts with
+pub const MAGIC: [u8; 4] = *b"MKC\0";
+
+/// the version of the format written by `write_to`, files of any other
+/// version are rejected
+pub const FORMAT_VERSION: u16 = 1;
+
+const HEADER_LEN: usize = 14;
+
+const TAG_INTEGER: u8 = 0;
+const TAG_STRING: u8 = 1;
+const TAG_FUNCTION: u8 = 2;
+const TAG_FLOAT: u8 = 3;
+
+/// represents a failure to write or read a `.mkc` file
+#[derive(Debug)]
+pub enum MkcError {
+    Io(io::Error),
+    /// the file doesn't start with `MAGIC`
+    BadMagic,
+    /// the file was written by another version of the format
+    UnsupportedVersion(u16),
+    /// the file ends before the length its header declares
+    Truncated,
+    /// the payload doesn't match the checksum in the header
+    ChecksumMismatch {
+        expected: u32,
+        found: u32,
+    },
+    /// the payload passed the checksum but can't be decoded
+    Malformed(String),
+    /// the constant pool holds an object the format can't store
+    UnsupportedConstant(&'static str),
+}
+
+impl fmt::Display for MkcError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MkcError::Io(err) => write!(f, "{}", err),
+            MkcError::BadMagic => write!(f, "not a compiled monkey file"),
+            MkcError::UnsupportedVersion(version) => write!(
+                f,
+                "unsupported format version {}, expected {}",
+                version, FORMAT_VERSION
+            ),
+            MkcError::Truncated => write!(f, "file is truncated"),
+            MkcError::ChecksumMismatch { expected, found } => write!(
+                f,
+                "checksum mismatch: expected {:08x}, found {:08x}",
+                expected, found
+            ),
+            MkcError::Malformed(reason) => write!(f, "malformed file: {}", reason),
+            MkcError::UnsupportedConstant(type_name) => {
+                write!(f, "can't store {} constants", type_name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for MkcError {}
+
+impl From<io::Error> for MkcError {
+    fn from(err: io::Error) -> MkcError {
+        if err.kind() == io::ErrorKind::UnexpectedEof {
+            MkcError::Truncated
+        } else {
+            MkcError::Io(err)
+        }
+    }
+}
+
+/// returns the CRC-32 (IEEE) checksum of `bytes`
+fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    !crc
+}
+
+impl Bytecode {
+    /// writes the bytecode to `writer` in the `.mkc` format
+    ///
+    /// # Arguments
+    ///
+    /// * `writer` - where the file is written to
+    ///
+    pub fn write_to<W: Write>(&self, mut writer: W) -> Result<(), MkcError> {
+        let mut payload = vec![];
+
+        write_len(&mut payload, self.constants.len())?;
+        for constant in &self.constants {
+            match constant {
+                Object::Integer(value) => {
+                    payload.push(TAG_INTEGER);
+                    payload.extend_from_slice(&value.to_be_bytes());
+                }
+                Object::Float(value) => {
+                    payload.push(TAG_FLOAT);
+                    payload.extend_from_slice(&value.to_bits().to_be_bytes());
+                }
+                Object::String(value) => {
+                    payload.push(TAG_STRING);
+                    write_bytes(&mut payload, value.as_bytes())?;
+                }
+                Object::CompiledFunction(function) => {
+                    payload.push(TAG_FUNCTION);
+                    write_len(&mut payload, function.num_locals)?;
+                    write_len(&mut payload, function.num_parameters)?;
+                    write_bytes(&mut payload, &function.instructions)?;
+                    write_lines(&mut payload, &function.lines)?;
+                }
+                other => return Err(MkcError::UnsupportedConstant(other.type_name())),
+            }
+        }
+        write_bytes(&mut payload, &self.instructions)?;
+        write_lines(&mut payload, &self.lines)?;
+
+        let mut header = Vec::with_capacity(HEADER_LEN);
+        header.extend_from_slice(&MAGIC);
+        header.extend_from_slice(&FORMAT_VERSION.to_be_bytes());
+        write_len(&mut header, payload.len())?;
+        header.extend_from_slice(&crc32(&payload).to_be_bytes());
+
+        writer.write_all(&header)?;
+        writer.write_all(&payload)?;
+        Ok(())
+    }
+
+    /// reads bytecode written by `write_to`, rejecting files of another format
+    /// version and files that are truncated or corrupted
+    ///
+    /// # Arguments
+    ///
+    /// * `reader` - where the file is read from
+    ///
+    pub fn read_from<R: Read>(mut reader: R) -> Result<Bytecode, MkcError> {
+        let mut header = [0; HEADER_LEN];
+        reader.read_exact(&mut header[..MAGIC.len()])?;
+        if header[..MAGIC.len()] != MAGIC {
+            return Err(MkcError::BadMagic);
+        }
+        reader.read_exact(&mut header[MAGIC.len()..])?;
+
+        let version = u16::from_be_bytes([header[4], header[5]]);
+        if version != FORMAT_VERSION {
+            return Err(MkcError::UnsupportedVersion(version));
+        }
+        let len = u32::from_be_bytes([header[6], header[7], header[8], header[9]]);
+        let expected = u32::from_be_bytes([header[10], header[11], header[12], header[13]]);
+
+        let mut payload = vec![];
+        reader.take(len as u64).read_to_end(&mut payload)?;
+        if payload.len() < len as usize {
+            return Err(MkcError::Truncated);
+        }
+        let found = crc32(&payload);
+        if found != expected {
+            return Err(MkcError::ChecksumMismatch { expected, found });
+        }
+
+        let mut decoder = Decoder {
+            bytes: &payload,
+            position: 0,
+        };
+        let bytecode = decoder.bytecode()?;
+        if decoder.position != payload.len() {
+            return Err(MkcError::Malformed(
+                "trailing bytes after the payload".to_string(),
+            ));
+        }
+        validate(&bytecode)?;
+        Ok(bytecode)
+    }
+}
+
+fn write_len(buffer: &mut Vec<u8>, len: usize) -> Result<(), MkcError> {
+    let len = u32::try_from(len)
+        .map_err(|_| MkcError::Malformed(format!("{} doesn't fit in a u32", len)))?;
+    buffer.extend_from_slice(&len.to_be_bytes());
+    Ok(())
+}
+
+fn write_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) -> Result<(), MkcError> {
+    write_len(buffer, bytes.len())?;
+    buffer.extend_from_slice(bytes);
+    Ok(())
+}
+
+fn write_lines(buffer: &mut Vec<u8>, lines: &[LineEntry]) -> Result<(), MkcError> {
+    write_len(buffer, lines.len())?;
+    for entry in lines {
+        write_len(buffer, entry.offset)?;
+        write_len(buffer, entry.line)?;
+    }
+    Ok(())
+}
+
+/// checks the instructions of the top level and of every function in the
+/// constant pool can run without reading past what the file holds
+fn validate(bytecode: &Bytecode) -> Result<(), MkcError> {
+    validate_instructions(&bytecode.instructions, &bytecode.constants)
+        .map_err(|reason| MkcError::Malformed(format!("main: {}", reason)))?;
+
+    for (index, constant) in bytecode.constants.iter().enumerate() {
+        if let Object::CompiledFunction(function) = constant {
+            let check = if function.num_parameters > function.num_locals {
+                Err(format!(
+                    "{} parameters but {} locals",
+                    function.num_parameters, function.num_locals
+                ))
+            } else {
+                validate_instructions(&function.instructions, &bytecode.constants)
+            };
+            check
+                .map_err(|reason| MkcError::Malformed(format!("function {}: {}", index, reason)))?;
+        }
+    }
+    Ok(())
+}
+
+/// checks every opcode of `instructions` is known and complete, that constant
+/// operands index into `constants` and that jumps land on an instruction
+fn validate_instructions(instructions: &[u8], constants: &[Object]) -> Result<(), String> {
+    let mut starts = vec![];
+    let mut jumps = vec![];
+    let mut ip = 0;
+
+    while ip < instructions.len() {
+        starts.push(ip);
+        let op = Opcode::try_from(instructions[ip])
+            .map_err(|byte| format!("unknown opcode {} at {}", byte, ip))?;
+        let definition = op.definition();
+        let len = definition.operand_widths.iter().sum::<usize>();
+        if ip + 1 + len > instructions.len() {
+            return Err(format!("{} at {} is truncated", definition.name, ip));
+        }
+        let (operands, read) = read_operands(&definition, &instructions[ip + 1..]);
+
+        match op {
+            Opcode::Constant | Opcode::Closure if operands[0] >= constants.len() => {
+                return Err(format!(
+                    "{} at {} refers to constant {} of {}",
+                    definition.name,
+                    ip,
+                    operands[0],
+                    constants.len()
+                ));
+            }
+            Opcode::Jump | Opcode::JumpNotTruthy | Opcode::IterNext => {
+                jumps.push((ip, operands[0]));
+            }
+            _ => {}
+        }
+        ip += 1 + read;
+    }
+
+    for (ip, target) in jumps {
+        // jumping right past the last instruction ends the stream
+        if target != instructions.len() && starts.binary_search(&target).is_err() {
+            return Err(format!("jump at {} to {} is out of place", ip, target));
+        }
+    }
+    Ok(())
+}
+
+/// reads the values of a payload in order
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], MkcError> {
+        let end = self
+            .position
+            .checked_add(len)
+            .filter(|end| *end <= self.bytes.len())
+            .ok_or_else(|| MkcError::Malformed("value runs past the payload".to_string()))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, MkcError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn len(&mut self) -> Result<usize, MkcError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+    }
+
+    fn bytes(&mut self) -> Result<&'a [u8], MkcError> {
+        let len = self.len()?;
+        self.take(len)
+    }
+
+    fn instructions(&mut self) -> Result<Instructions, MkcError> {
+        Ok(self.bytes()?.to_vec())
+    }
+
+    fn lines(&mut self) -> Result<Vec<LineEntry>, MkcError> {
+        let count = self.len()?;
+        let mut lines = Vec::with_capacity(count.min(self.bytes.len() / 8));
+        for _ in 0..count {
+            lines.push(LineEntry {
+                offset: self.len()?,
+                line: self.len()?,
+            });
+        }
+        Ok(lines)
+    }
+
+    fn constant(&mut self) -> Result<Object, MkcError> {
+        match self.u8()? {
+            TAG_INTEGER => {
+                let bytes = self.take(8)?;
+                let mut value = [0; 8];
+                value.copy_from_slice(bytes);
+                Ok(Object::Integer(i64::from_be_bytes(value)))
+            }
+            TAG_FLOAT => {
+                let bytes = self.take(8)?;
+                let mut value = [0; 8];
+                value.copy_from_slice(bytes);
+                Ok(Object::Float(f64::from_bits(u64::from_be_bytes(value))))
+            }
+            TAG_STRING => match std::str::from_utf8(self.bytes()?) {
+                Ok(value) => Ok(Object::String(value.to_string())),
+                Err(err) => Err(MkcError::Malformed(format!(
+                    "invalid string constant: {}",
+                    err
+                ))),
+            },
+            TAG_FUNCTION => Ok(Object::CompiledFunction(Rc::new(CompiledFunction {
+                num_locals: self.len()?,
+                num_parameters: self.len()?,
+                instructions: self.instructions()?,
+                lines: self.lines()?,
+            }))),
+            tag => Err(MkcError::Malformed(format!("unknown constant tag {}", tag))),
+        }
+    }
+
+    fn bytecode(&mut self) -> Result<Bytecode, MkcError> {
+        let count = self.len()?;
+        let mut constants = Vec::with_capacity(count.min(self.bytes.len()));
+        for _ in 0..count {
+            constants.push(self.constant()?);
+        }
+
+        Ok(Bytecode {
+            constants,
+            instructions: self.instructions()?,
+            lines: self.lines()?,
+        })
+    }
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::code::make;
+    use crate::compiler::compiler::Compiler;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+    use crate::vm::vm::Vm;
+
+    fn compile(input: &str) -> Bytecode {
+        let mut compiler = Compiler::new();
+        compiler
+            .compile(&Parser::new(Lexer::new(input)).parse_program())
+            .unwrap();
+        compiler.bytecode()
+    }
+
+    fn write(bytecode: &Bytecode) -> Vec<u8> {
+        let mut file = vec![];
+        bytecode.write_to(&mut file).unwrap();
+        file
+    }
+
+    #[test]
+    fn checksum() {
+        assert_eq!(crc32(b""), 0);
+        assert_eq!(crc32(b"123456789"), 0xCBF4_3926);
+    }
+
+    #[test]
+    fn round_trip() {
+        let bytecode = compile(
+            "let greet = fn(name) { \"hello \" + name };\nlet big = -9223372036854775807;\nlet pi = 3.14159;\ngreet(\"monkey\")",
+        );
+        let file = write(&bytecode);
+
+        assert_eq!(&file[..4], b"MKC\0");
+        let read = Bytecode::read_from(file.as_slice()).unwrap();
+        assert_eq!(read, bytecode);
+
+        let mut vm = Vm::new(read);
+        vm.run().unwrap();
+        assert_eq!(
+            vm.last_popped(),
+            &Object::String("hello monkey".to_string())
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_files() {
+        let file = write(&compile("let f = fn(x) { [x, \"x\"] }; f(1)"));
+
+        for len in [0, 3, 10, HEADER_LEN, file.len() - 1].iter() {
+            match Bytecode::read_from(&file[..*len]) {
+                Err(MkcError::Truncated) => {}
+                other => panic!(
+                    "expected truncated error for {} bytes, got {:?}",
+                    len, other
+                ),
+            }
+        }
+
+        let mut version = file.clone();
+        version[5] += 1;
+        match Bytecode::read_from(version.as_slice()) {
+            Err(MkcError::UnsupportedVersion(2)) => {}
+            other => panic!("expected version error, got {:?}", other),
+        }
+
+        let mut corrupted = file.clone();
+        *corrupted.last_mut().unwrap() ^= 1;
+        match Bytecode::read_from(corrupted.as_slice()) {
+            Err(MkcError::ChecksumMismatch { .. }) => {}
+            other => panic!("expected checksum error, got {:?}", other),
+        }
+
+        match Bytecode::read_from(&b"MKB\0rest of the file"[..]) {
+            Err(MkcError::BadMagic) => {}
+            other => panic!("expected magic error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_instructions() {
+        let function = |num_locals, num_parameters, instructions: Vec<Vec<u8>>| {
+            Object::CompiledFunction(Rc::new(CompiledFunction {
+                instructions: instructions.concat(),
+                num_locals,
+                num_parameters,
+                lines: vec![],
+            }))
+        };
+        let tests = vec![
+            (
+                vec![make(Opcode::Constant, &[0])],
+                vec![],
+                "main: OpConstant at 0 refers to constant 0 of 0",
+            ),
+            (
+                vec![make(Opcode::Pop, &[]), vec![255]],
+                vec![],
+                "main: unknown opcode 255 at 1",
+            ),
+            (
+                vec![make(Opcode::Constant, &[0])[..2].to_vec()],
+                vec![Object::Integer(1)],
+                "main: OpConstant at 0 is truncated",
+            ),
+            (
+                vec![make(Opcode::Constant, &[0]), make(Opcode::Jump, &[1])],
+                vec![Object::Integer(1)],
+                "main: jump at 3 to 1 is out of place",
+            ),
+            (
+                vec![make(Opcode::Jump, &[4])],
+                vec![],
+                "main: jump at 0 to 4 is out of place",
+            ),
+            (
+                vec![],
+                vec![function(1, 2, vec![make(Opcode::Return, &[])])],
+                "function 0: 2 parameters but 1 locals",
+            ),
+            (
+                vec![],
+                vec![function(0, 0, vec![make(Opcode::Closure, &[1, 0])])],
+                "function 0: OpClosure at 0 refers to constant 1 of 1",
+            ),
+        ];
+
+        for (instructions, constants, expected) in tests {
+            let file = write(&Bytecode {
+                instructions: instructions.concat(),
+                constants,
+                lines: vec![],
+            });
+
+            match Bytecode::read_from(file.as_slice()) {
+                Err(err @ MkcError::Malformed(_)) => {
+                    assert_eq!(err.to_string(), format!("malformed file: {}", expected))
+                }
+                other => panic!("expected malformed error, got {:?}", other),
+            }
+        }
+
+        let jumps = compile("if (true) { 1 } else { 2 }; for (x in [1]) { x }");
+        assert!(Bytecode::read_from(write(&jumps).as_slice()).is_ok());
+    }
+
+    #[test]
+    fn rejects_unsupported_constants() {
+        let bytecode = Bytecode {
+            instructions: vec![],
+            constants: vec![Object::Boolean(true)],
+            lines: vec![],
+        };
+
+        match bytecode.write_to(vec![]) {
+            Err(err) => assert_eq!(err.to_string(), "can't store BOOLEAN constants"),
+            Ok(()) => panic!("expected an error"),
+        }
+    }
+}
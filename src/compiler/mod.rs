@@ -0,0 +1,3 @@
+pub mod compiler;
+pub mod mkc;
+pub mod symbol_table;
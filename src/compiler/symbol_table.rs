@@ -0,0 +1,297 @@
+use std::collections::HashMap;
+
+/// represents where the value bound to a symbol is stored at runtime
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum SymbolScope {
+    /// a top level binding, stored in the globals of the vm
+    Global,
+    /// a top level binding of a block, ie: the variable of a `for` loop.
+    /// stored in the globals of the vm, but captured by closures like a local
+    /// since every iteration binds it anew
+    BlockGlobal,
+    /// a parameter or binding inside a function, stored on the stack
+    Local,
+    /// a function built into the interpreter
+    Builtin,
+    /// a local of an enclosing function captured by a closure
+    Free,
+    /// the function currently being defined, for recursive local functions
+    Function,
+}
+
+/// represents a name bound by the program
+///
+/// # Parameters
+///
+/// * `name` - the identifier
+///
+/// * `scope` - where the bound value is stored
+///
+/// * `index` - the slot of the value within its scope
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct Symbol {
+    pub name: String,
+    pub scope: SymbolScope,
+    pub index: usize,
+}
+
+/// Represents the names bound in one scope of a program while compiling
+///
+/// # Parameters
+///
+/// * `outer` - the enclosing scope, `None` for the global scope
+///
+/// * `store` - the symbols bound in this scope by name
+///
+/// * `num_definitions` - the number of global or local slots used by this
+///   scope
+///
+/// * `free_symbols` - the symbols of enclosing functions captured by this
+///   scope, in the order the closure receives them
+///
+/// * `blocks` - the blocks open in this scope, innermost last, each with the
+///   names it defines and what they were bound to before the block
+///
+#[derive(Debug, Default)]
+pub struct SymbolTable {
+    outer: Option<Box<SymbolTable>>,
+    store: HashMap<String, Symbol>,
+    pub num_definitions: usize,
+    pub free_symbols: Vec<Symbol>,
+    blocks: Vec<Vec<(String, Option<Symbol>)>>,
+}
+
+impl SymbolTable {
+    /// returns a new, empty global symbol table
+    pub fn new() -> SymbolTable {
+        SymbolTable::default()
+    }
+
+    /// returns a new, empty function scope enclosed by `outer`
+    pub fn new_enclosed(outer: SymbolTable) -> SymbolTable {
+        SymbolTable {
+            outer: Some(Box::new(outer)),
+            ..SymbolTable::default()
+        }
+    }
+
+    /// returns the enclosing symbol table, leaving this one empty
+    pub fn take_outer(&mut self) -> Option<SymbolTable> {
+        self.outer.take().map(|outer| *outer)
+    }
+}
+
+impl SymbolTable {
+    /// binds `name` to a new slot in this scope, or returns its existing slot
+    /// when the scope already binds it. inside a block, a name bound before
+    /// the block is shadowed by a new slot instead.
+    pub fn define(&mut self, name: &str) -> Symbol {
+        let scope = match (&self.outer, self.blocks.is_empty()) {
+            (None, true) => SymbolScope::Global,
+            (None, false) => SymbolScope::BlockGlobal,
+            (Some(_), _) => SymbolScope::Local,
+        };
+
+        let previous = self.store.get(name).cloned();
+        match self.blocks.last_mut() {
+            Some(block) if !block.iter().any(|(defined, _)| defined == name) => {
+                block.push((name.to_string(), previous));
+            }
+            _ => {
+                if let Some(symbol) = previous.filter(|symbol| symbol.scope == scope) {
+                    return symbol;
+                }
+            }
+        }
+
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope,
+            index: self.num_definitions,
+        };
+        self.num_definitions += 1;
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    /// opens a block, the names defined until `close_block` are bound to new
+    /// slots visible only inside it
+    pub fn open_block(&mut self) {
+        self.blocks.push(vec![]);
+    }
+
+    /// closes the innermost block, binding the names it defined back to what
+    /// they were bound to before it
+    pub fn close_block(&mut self) {
+        let block = self.blocks.pop().expect("symbol table has no block");
+        for (name, previous) in block.into_iter().rev() {
+            match previous {
+                Some(symbol) => self.store.insert(name, symbol),
+                None => self.store.remove(&name),
+            };
+        }
+    }
+
+    /// binds `name` to the builtin function at `index`
+    pub fn define_builtin(&mut self, index: usize, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Builtin,
+            index,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    /// binds `name` to the function whose body this scope belongs to
+    pub fn define_function_name(&mut self, name: &str) -> Symbol {
+        let symbol = Symbol {
+            name: name.to_string(),
+            scope: SymbolScope::Function,
+            index: 0,
+        };
+        self.store.insert(name.to_string(), symbol.clone());
+        symbol
+    }
+
+    fn define_free(&mut self, original: Symbol) -> Symbol {
+        let symbol = Symbol {
+            name: original.name.clone(),
+            scope: SymbolScope::Free,
+            index: self.free_symbols.len(),
+        };
+        self.free_symbols.push(original);
+        self.store.insert(symbol.name.clone(), symbol.clone());
+        symbol
+    }
+
+    /// returns the symbol a free symbol of this scope was captured from in
+    /// the scope binding it, any other symbol as is
+    pub fn origin(&self, symbol: &Symbol) -> Symbol {
+        let captured = match symbol.scope {
+            SymbolScope::Free => self.free_symbols.get(symbol.index),
+            _ => None,
+        };
+        match (captured, &self.outer) {
+            (Some(captured), Some(outer)) => outer.origin(captured),
+            _ => symbol.clone(),
+        }
+    }
+
+    /// returns the symbol `name` refers to from this scope. locals of
+    /// enclosing functions are turned into free symbols of every scope
+    /// between them and this one.
+    pub fn resolve(&mut self, name: &str) -> Option<Symbol> {
+        if let Some(symbol) = self.store.get(name) {
+            return Some(symbol.clone());
+        }
+
+        let symbol = self.outer.as_mut()?.resolve(name)?;
+        match symbol.scope {
+            SymbolScope::Global | SymbolScope::Builtin => Some(symbol),
+            _ => Some(self.define_free(symbol)),
+        }
+    }
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn symbol(name: &str, scope: SymbolScope, index: usize) -> Symbol {
+        Symbol {
+            name: name.to_string(),
+            scope,
+            index,
+        }
+    }
+
+    #[test]
+    fn define_and_resolve() {
+        let mut global = SymbolTable::new();
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 1));
+        assert_eq!(global.define("a"), symbol("a", SymbolScope::Global, 0));
+        global.define_builtin(0, "len");
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.define("c"), symbol("c", SymbolScope::Local, 0));
+        assert_eq!(local.define("b"), symbol("b", SymbolScope::Local, 1));
+
+        assert_eq!(
+            local.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(local.resolve("b"), Some(symbol("b", SymbolScope::Local, 1)));
+        assert_eq!(
+            local.resolve("len"),
+            Some(symbol("len", SymbolScope::Builtin, 0))
+        );
+        assert_eq!(local.resolve("d"), None);
+        assert!(local.free_symbols.is_empty());
+    }
+
+    #[test]
+    fn resolve_free() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        let mut first = SymbolTable::new_enclosed(global);
+        first.define("b");
+
+        let mut second = SymbolTable::new_enclosed(first);
+        second.define_function_name("f");
+        second.define("c");
+
+        assert_eq!(
+            second.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(second.resolve("b"), Some(symbol("b", SymbolScope::Free, 0)));
+        assert_eq!(
+            second.resolve("c"),
+            Some(symbol("c", SymbolScope::Local, 0))
+        );
+        assert_eq!(
+            second.resolve("f"),
+            Some(symbol("f", SymbolScope::Function, 0))
+        );
+        assert_eq!(
+            second.free_symbols,
+            vec![symbol("b", SymbolScope::Local, 0)]
+        );
+
+        let first = second.take_outer().unwrap();
+        assert!(first.free_symbols.is_empty());
+    }
+
+    #[test]
+    fn blocks() {
+        let mut global = SymbolTable::new();
+        global.define("a");
+
+        global.open_block();
+        let a = symbol("a", SymbolScope::BlockGlobal, 1);
+        assert_eq!(global.define("a"), a);
+        assert_eq!(global.define("a"), a);
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::BlockGlobal, 2));
+
+        let mut local = SymbolTable::new_enclosed(global);
+        assert_eq!(local.resolve("a"), Some(symbol("a", SymbolScope::Free, 0)));
+        assert_eq!(local.free_symbols, vec![a]);
+        let mut global = local.take_outer().unwrap();
+        global.close_block();
+
+        assert_eq!(
+            global.resolve("a"),
+            Some(symbol("a", SymbolScope::Global, 0))
+        );
+        assert_eq!(global.resolve("b"), None);
+        assert_eq!(global.define("b"), symbol("b", SymbolScope::Global, 3));
+    }
+}
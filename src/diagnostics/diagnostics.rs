@@ -0,0 +1,313 @@
+use crate::compiler::compiler::{CompileError, CompileErrorKind};
+use crate::lexer::lexer::{LexError, LexErrorKind};
+use crate::parser::parser::{ParseError, ParseErrorKind};
+use crate::tokens::tokens::Span;
+use std::fmt::Write;
+
+const RED: &str = "\x1b[1;31m";
+const BLUE: &str = "\x1b[1;34m";
+const BOLD: &str = "\x1b[1m";
+const RESET: &str = "\x1b[0m";
+
+/// represents an error ready to be shown to a user, rustc-style
+///
+/// # Parameters
+///
+/// * `message` - the primary message
+///
+/// * `span` - the source the error is about, `None` when it can't be located
+///
+/// * `help` - notes suggesting how to fix the error
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct Diagnostic {
+    pub message: String,
+    pub span: Option<Span>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    /// returns a new Diagnostic without a location or help notes
+    pub fn error(message: impl Into<String>) -> Diagnostic {
+        Diagnostic {
+            message: message.into(),
+            span: None,
+            help: vec![],
+        }
+    }
+
+    /// returns the diagnostic located at `span`
+    pub fn at(self, span: Span) -> Diagnostic {
+        Diagnostic {
+            span: Some(span),
+            ..self
+        }
+    }
+
+    /// returns the diagnostic with `note` added to its help notes
+    pub fn with_help(mut self, note: impl Into<String>) -> Diagnostic {
+        self.help.push(note.into());
+        self
+    }
+
+    /// renders the diagnostic as
+    ///
+    /// ```text
+    /// error: unterminated string literal
+    ///  --> script.mk:1:9
+    ///   |
+    /// 1 | let a = "abc
+    ///   |         ^^^^
+    ///   = help: add a closing `"`
+    /// ```
+    ///
+    /// the snippet is left out when the diagnostic has no span, or its span
+    /// doesn't come from `source`. a span running over several lines is
+    /// underlined up to the end of its first line.
+    ///
+    /// # Arguments
+    ///
+    /// * `file` - the name `source` was read from
+    ///
+    /// * `source` - the source the diagnostic's span points into
+    ///
+    /// * `color` - whether to colour the output with ANSI escapes, for
+    ///   terminals
+    ///
+    pub fn render(&self, file: &str, source: &str, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{}{}{}", style, text, RESET)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "{}{}",
+            paint(RED, "error"),
+            paint(BOLD, &format!(": {}", self.message))
+        );
+
+        let snippet = self.span.and_then(|span| snippet(source, span));
+        let gutter = match (self.span, &snippet) {
+            (Some(span), Some(_)) => " ".repeat(span.line.to_string().len()),
+            _ => " ".to_string(),
+        };
+
+        match self.span {
+            Some(span) if span.line > 0 => {
+                let _ = writeln!(out, "{}{} {}:{}", gutter, paint(BLUE, "-->"), file, span);
+            }
+            _ => {
+                let _ = writeln!(out, "{}{} {}", gutter, paint(BLUE, "-->"), file);
+            }
+        }
+
+        if let (Some(span), Some((line, indent, width))) = (self.span, &snippet) {
+            let bar = paint(BLUE, "|");
+            let _ = writeln!(out, "{} {}", gutter, bar);
+            let _ = writeln!(
+                out,
+                "{} {} {}",
+                paint(BLUE, &span.line.to_string()),
+                bar,
+                line
+            );
+            let _ = writeln!(
+                out,
+                "{} {} {}{}",
+                gutter,
+                bar,
+                indent,
+                paint(RED, &"^".repeat(*width))
+            );
+        }
+
+        for note in &self.help {
+            let _ = writeln!(
+                out,
+                "{} {} {}: {}",
+                gutter,
+                paint(BLUE, "="),
+                paint(BOLD, "help"),
+                note
+            );
+        }
+
+        out
+    }
+}
+
+/// returns the line `span` starts on, the whitespace lining up a caret with
+/// its start, and how many carets underline it. `None` when `span` doesn't
+/// point into `source`.
+fn snippet(source: &str, span: Span) -> Option<(&str, String, usize)> {
+    if span.line == 0 || span.start > source.len() || !source.is_char_boundary(span.start) {
+        return None;
+    }
+
+    let line_start = source[..span.start].rfind('\n').map_or(0, |at| at + 1);
+    let line_end = source[span.start..]
+        .find('\n')
+        .map_or(source.len(), |at| span.start + at);
+    let line = source[line_start..line_end].trim_end_matches('\r');
+
+    // tabs are kept so the carets line up however wide the terminal shows them
+    let indent = source[line_start..span.start]
+        .chars()
+        .map(|ch| if ch == '\t' { '\t' } else { ' ' })
+        .collect();
+    let end = span.end.min(line_end).max(span.start);
+    let width = source.get(span.start..end).map_or(0, |s| s.chars().count());
+
+    Some((line, indent, width.max(1)))
+}
+
+impl From<&LexError> for Diagnostic {
+    fn from(err: &LexError) -> Diagnostic {
+        let diagnostic = Diagnostic::error(err.kind.to_string()).at(err.span);
+        match &err.kind {
+            LexErrorKind::UnterminatedString => diagnostic.with_help("add a closing `\"`"),
+            LexErrorKind::UnterminatedComment => {
+                diagnostic.with_help("add a closing `*/`, block comments nest")
+            }
+            LexErrorKind::MalformedNumber(_) => diagnostic.with_help(
+                "numbers are decimal, or prefixed by 0x, 0o or 0b, and may use `_` between digits",
+            ),
+            LexErrorKind::InvalidEscape(_) => {
+                diagnostic.with_help("supported escapes are \\n, \\t, \\\", \\\\ and \\u{...}")
+            }
+        }
+    }
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(err: &ParseError) -> Diagnostic {
+        if let ParseErrorKind::Lex(kind) = &err.kind {
+            return Diagnostic::from(&LexError {
+                kind: kind.clone(),
+                span: err.span,
+            });
+        }
+
+        let diagnostic = Diagnostic::error(err.kind.to_string()).at(err.span);
+        match &err.kind {
+            ParseErrorKind::InvalidInteger(_) => {
+                diagnostic.with_help(format!("integers range from {} to {}", i64::MIN, i64::MAX))
+            }
+            _ => diagnostic,
+        }
+    }
+}
+
+impl From<&CompileError> for Diagnostic {
+    fn from(err: &CompileError) -> Diagnostic {
+        let diagnostic = Diagnostic::error(err.kind.to_string()).at(err.span);
+        match &err.kind {
+            CompileErrorKind::UndefinedVariable(name) => {
+                diagnostic.with_help(format!("bind `{}` with `let` before using it", name))
+            }
+            CompileErrorKind::FunctionAssignment(name) => diagnostic.with_help(format!(
+                "bind the new value to another name, `{}` is the function here",
+                name
+            )),
+            _ => diagnostic,
+        }
+    }
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+
+    fn parse_errors(source: &str) -> Vec<Diagnostic> {
+        let mut parser = Parser::new(Lexer::new(source));
+        parser.parse_program();
+        parser.errors().iter().map(Diagnostic::from).collect()
+    }
+
+    #[test]
+    fn render_plain() {
+        let source = "let a = 1;\nlet b = \"abc\nlet c = 3;";
+        let diagnostics = parse_errors(source);
+
+        assert_eq!(
+            diagnostics[0].render("script.mk", source, false),
+            "error: unterminated string literal
+ --> script.mk:2:9
+  |
+2 | let b = \"abc
+  |         ^^^^
+  = help: add a closing `\"`
+"
+        );
+    }
+
+    #[test]
+    fn render_lines_up_carets() {
+        let source = "\tlet a = 1;\n\t\"é\\q\"";
+        let diagnostics = parse_errors(source);
+
+        assert_eq!(
+            diagnostics[0].render("<eval>", source, false),
+            "error: invalid escape sequence \\q in string literal
+ --> <eval>:2:4
+  |
+2 | \t\"é\\q\"
+  | \t  ^^
+  = help: supported escapes are \\n, \\t, \\\", \\\\ and \\u{...}
+"
+        );
+    }
+
+    #[test]
+    fn render_at_end_of_input() {
+        let source = "let x = 10;\n\nlet";
+        let diagnostics = parse_errors(source);
+
+        assert_eq!(
+            diagnostics[0].render("script.mk", source, false),
+            "error: expected next token to be IDENT, got EOF instead
+ --> script.mk:3:4
+  |
+3 | let
+  |    ^
+"
+        );
+    }
+
+    #[test]
+    fn render_without_span() {
+        let diagnostic =
+            Diagnostic::error("identifier not found: x").with_help("check the spelling");
+
+        assert_eq!(
+            diagnostic.render("script.mk", "x", false),
+            "error: identifier not found: x\n --> script.mk\n  = help: check the spelling\n"
+        );
+    }
+
+    #[test]
+    fn render_color() {
+        let diagnostic = Diagnostic::error("oops").at(Span::new(0, 1, 1, 1));
+
+        assert_eq!(
+            diagnostic.render("f", "x", true),
+            "\x1b[1;31merror\x1b[0m\x1b[1m: oops\x1b[0m
+ \x1b[1;34m-->\x1b[0m f:1:1
+  \x1b[1;34m|\x1b[0m
+\x1b[1;34m1\x1b[0m \x1b[1;34m|\x1b[0m x
+  \x1b[1;34m|\x1b[0m \x1b[1;31m^\x1b[0m
+"
+        );
+    }
+}
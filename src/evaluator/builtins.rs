@@ -0,0 +1,120 @@
+use crate::evaluator::object::Object;
+
+/// signature of the functions built into the interpreter
+pub type BuiltinFunction = fn(Vec<Object>) -> Object;
+
+/// every builtin function, by the name it is bound to. the compiler refers to
+/// builtins by their index in this table.
+pub const BUILTINS: &[(&str, BuiltinFunction)] = &[
+    ("len", len),
+    ("first", first),
+    ("last", last),
+    ("rest", rest),
+    ("push", push),
+];
+
+/// returns the builtin function bound to `name`, if there is one. builtins are
+/// looked up after the environment, so bindings can shadow them.
+///
+/// # Arguments
+///
+/// * `name` - the identifier being resolved
+///
+pub fn lookup(name: &str) -> Option<Object> {
+    BUILTINS
+        .iter()
+        .find(|(builtin, _)| *builtin == name)
+        .map(|&(name, function)| Object::Builtin { name, function })
+}
+
+/// returns an error unless exactly `want` arguments were passed
+fn check_arity(args: &[Object], want: usize) -> Result<(), Object> {
+    if args.len() == want {
+        Ok(())
+    } else {
+        Err(Object::error(format!(
+            "wrong number of arguments: want={}, got={}",
+            want,
+            args.len()
+        )))
+    }
+}
+
+/// `len(x)` - the number of characters in a string or elements in an array
+fn len(args: Vec<Object>) -> Object {
+    if let Err(err) = check_arity(&args, 1) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::String(value) => Object::Integer(value.chars().count() as i64),
+        Object::Array(elements) => Object::Integer(elements.len() as i64),
+        other => Object::error(format!(
+            "argument to `len` not supported, got {}",
+            other.type_name()
+        )),
+    }
+}
+
+/// `first(array)` - the first element, or `null` for an empty array
+fn first(args: Vec<Object>) -> Object {
+    if let Err(err) = check_arity(&args, 1) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::Array(elements) => elements.first().cloned().unwrap_or(Object::Null),
+        other => not_an_array("first", other),
+    }
+}
+
+/// `last(array)` - the last element, or `null` for an empty array
+fn last(args: Vec<Object>) -> Object {
+    if let Err(err) = check_arity(&args, 1) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::Array(elements) => elements.last().cloned().unwrap_or(Object::Null),
+        other => not_an_array("last", other),
+    }
+}
+
+/// `rest(array)` - a new array of every element but the first, or `null` for
+/// an empty array
+fn rest(args: Vec<Object>) -> Object {
+    if let Err(err) = check_arity(&args, 1) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::Array(elements) if elements.is_empty() => Object::Null,
+        Object::Array(elements) => Object::Array(elements[1..].to_vec()),
+        other => not_an_array("rest", other),
+    }
+}
+
+/// `push(array, value)` - a new array with `value` appended, the original is
+/// left unchanged
+fn push(args: Vec<Object>) -> Object {
+    if let Err(err) = check_arity(&args, 2) {
+        return err;
+    }
+
+    match &args[0] {
+        Object::Array(elements) => {
+            let mut elements = elements.clone();
+            elements.push(args[1].clone());
+            Object::Array(elements)
+        }
+        other => not_an_array("push", other),
+    }
+}
+
+fn not_an_array(name: &str, other: &Object) -> Object {
+    Object::error(format!(
+        "argument to `{}` must be ARRAY, got {}",
+        name,
+        other.type_name()
+    ))
+}
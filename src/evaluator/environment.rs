@@ -0,0 +1,123 @@
+use crate::evaluator::object::Object;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// a shared, mutable handle to an `Environment`
+pub type Env = Rc<RefCell<Environment>>;
+
+/// Represents the bindings visible to the code being evaluated
+///
+/// # Parameters
+///
+/// * `store` - the bindings created in this scope
+///
+/// * `outer` - the enclosing scope, searched when a name isn't found in
+///   `store`. `None` for the global environment.
+///
+/// # Remarks
+///
+/// * environments are shared through `Rc<RefCell<Environment>>` so function
+///   objects can hold on to the scope they were defined in (closures).
+///
+#[derive(Debug, Default)]
+pub struct Environment {
+    store: HashMap<String, Object>,
+    outer: Option<Env>,
+}
+
+impl Environment {
+    /// returns a new, empty global environment
+    pub fn new() -> Environment {
+        Environment {
+            store: HashMap::new(),
+            outer: None,
+        }
+    }
+
+    /// returns a new, empty environment enclosed by `outer`
+    ///
+    /// # Arguments
+    ///
+    /// * `outer` - the scope to fall back on for names not bound locally
+    ///
+    pub fn new_enclosed(outer: Env) -> Environment {
+        Environment {
+            store: HashMap::new(),
+            outer: Some(outer),
+        }
+    }
+}
+
+impl Environment {
+    /// returns the value bound to `name` in this scope or the closest
+    /// enclosing scope that binds it
+    pub fn get(&self, name: &str) -> Option<Object> {
+        match self.store.get(name) {
+            Some(value) => Some(value.clone()),
+            None => self
+                .outer
+                .as_ref()
+                .and_then(|outer| outer.borrow().get(name)),
+        }
+    }
+
+    /// binds `name` to `value` in this scope, shadowing any outer binding
+    pub fn set(&mut self, name: String, value: Object) {
+        self.store.insert(name, value);
+    }
+
+    /// rebinds `name` to `value` in the closest scope that binds it. returns
+    /// whether any scope binds `name`, nothing is bound when none does.
+    pub fn assign(&mut self, name: &str, value: Object) -> bool {
+        if let Some(slot) = self.store.get_mut(name) {
+            *slot = value;
+            return true;
+        }
+        match &self.outer {
+            Some(outer) => outer.borrow_mut().assign(name, value),
+            None => false,
+        }
+    }
+
+    /// returns the bindings created in this scope, sorted by name. bindings
+    /// of enclosing scopes aren't included.
+    pub fn bindings(&self) -> Vec<(&str, &Object)> {
+        let mut bindings: Vec<_> = self
+            .store
+            .iter()
+            .map(|(name, value)| (name.as_str(), value))
+            .collect();
+        bindings.sort_by_key(|(name, _)| *name);
+        bindings
+    }
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn enclosed_lookup() {
+        let outer = Rc::new(RefCell::new(Environment::new()));
+        outer.borrow_mut().set("a".to_string(), Object::Integer(1));
+        outer.borrow_mut().set("b".to_string(), Object::Integer(2));
+
+        let mut inner = Environment::new_enclosed(Rc::clone(&outer));
+        inner.set("b".to_string(), Object::Boolean(true));
+
+        assert_eq!(inner.get("a"), Some(Object::Integer(1)));
+        assert_eq!(inner.get("b"), Some(Object::Boolean(true)));
+        assert_eq!(outer.borrow().get("b"), Some(Object::Integer(2)));
+        assert_eq!(inner.get("c"), None);
+        assert_eq!(inner.bindings(), vec![("b", &Object::Boolean(true))]);
+        assert_eq!(
+            outer.borrow().bindings(),
+            vec![("a", &Object::Integer(1)), ("b", &Object::Integer(2))]
+        );
+    }
+}
@@ -0,0 +1,1210 @@
+use crate::ast::ast::*;
+use crate::ast::modify::modify_expression;
+use crate::evaluator::builtins;
+use crate::evaluator::environment::{Env, Environment};
+use crate::evaluator::object::{HashPair, Object};
+use crate::tokens::tokens::{Span, TokenType};
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+#[cfg(feature = "bigint")]
+use num_traits::ToPrimitive;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::rc::Rc;
+
+/// evaluates a parsed program in the given environment and returns the value
+/// of its last statement, the value of a top level `return`, or the first
+/// runtime error, located at the expression that raised it.
+///
+/// # Arguments
+///
+/// * `program` - the `Program` to evaluate
+///
+/// * `env` - the environment bindings are read from and written to. keep it
+///   around to evaluate more programs against the same bindings.
+///
+pub fn eval(program: &Program, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for statement in &program.statements {
+        result = eval_statement(statement, env);
+
+        match result {
+            Object::ReturnValue(value) => return *value,
+            Object::Error { .. } => return result,
+            _ => {}
+        }
+    }
+
+    result
+}
+
+/// evaluates the statements of a block. unlike `eval`, a `ReturnValue` is
+/// passed on still wrapped so it can unwind through nested blocks, and so are
+/// `Break` and `Continue` up to their loop.
+fn eval_block_statement(block: &BlockStatement, env: &Env) -> Object {
+    let mut result = Object::Null;
+
+    for statement in &block.statements {
+        result = eval_statement(statement, env);
+
+        if let Object::ReturnValue(_) | Object::Break | Object::Continue | Object::Error { .. } =
+            result
+        {
+            return result;
+        }
+    }
+
+    result
+}
+
+fn eval_statement(statement: &Statement, env: &Env) -> Object {
+    match statement {
+        Statement::Let { name, value, .. } => {
+            let value = eval_expression(value, env);
+            if value.is_error() {
+                return value;
+            }
+            env.borrow_mut().set(name.clone(), value);
+            Object::Null
+        }
+        Statement::Return { value, .. } => {
+            let value = eval_expression(value, env);
+            if value.is_error() {
+                return value;
+            }
+            Object::ReturnValue(Box::new(value))
+        }
+        Statement::Assign {
+            name,
+            operator,
+            value,
+            span,
+        } => eval_assign_statement(name, *operator, value, *span, env),
+        Statement::While {
+            condition, body, ..
+        } => eval_while_statement(condition, body, env),
+        Statement::For {
+            variable,
+            iterable,
+            body,
+            ..
+        } => eval_for_statement(variable, iterable, body, env),
+        Statement::Break { .. } => Object::Break,
+        Statement::Continue { .. } => Object::Continue,
+        Statement::Expression(expression) => eval_expression(expression, env),
+        Statement::Block(block) => eval_block_statement(block, env),
+    }
+}
+
+/// rebinds `name` in the closest scope binding it to `value`, or to the
+/// result of applying `operator` to its current value and `value`
+fn eval_assign_statement(
+    name: &str,
+    operator: Option<TokenType>,
+    value: &Expression,
+    span: Span,
+    env: &Env,
+) -> Object {
+    let current = match env.borrow().get(name) {
+        Some(current) => current,
+        None => {
+            return locate(
+                Object::error(format!("identifier not found: {}", name)),
+                span,
+            )
+        }
+    };
+
+    let mut value = eval_expression(value, env);
+    if value.is_error() {
+        return value;
+    }
+    if let Some(operator) = operator {
+        value = locate(eval_infix_expression(operator, current, value), span);
+        if value.is_error() {
+            return value;
+        }
+    }
+
+    env.borrow_mut().assign(name, value);
+    Object::Null
+}
+
+fn eval_while_statement(condition: &Expression, body: &BlockStatement, env: &Env) -> Object {
+    loop {
+        let condition = eval_expression(condition, env);
+        if condition.is_error() {
+            return condition;
+        }
+        if !condition.is_truthy() {
+            return Object::Null;
+        }
+        if let Some(result) = eval_loop_body(body, env) {
+            return result;
+        }
+    }
+}
+
+/// runs `body` once for each element of the array `iterable` evaluates to,
+/// with the element bound to `variable` in the current scope
+fn eval_for_statement(
+    variable: &str,
+    iterable: &Expression,
+    body: &BlockStatement,
+    env: &Env,
+) -> Object {
+    let elements = match eval_expression(iterable, env) {
+        Object::Array(elements) => elements,
+        err @ Object::Error { .. } => return err,
+        other => {
+            let err = Object::error(format!("not iterable: {}", other.type_name()));
+            return locate(err, iterable.span());
+        }
+    };
+
+    for element in elements {
+        // each iteration binds the variable in its own scope, leaving any
+        // binding of the same name around the loop untouched
+        let scope = Rc::new(RefCell::new(Environment::new_enclosed(Rc::clone(env))));
+        scope.borrow_mut().set(variable.to_string(), element);
+        if let Some(result) = eval_loop_body(body, &scope) {
+            return result;
+        }
+    }
+    Object::Null
+}
+
+/// runs one iteration of a loop, returning what the loop evaluates to when
+/// `body` ends it: `null` for a `break`, or a `return` or an error to pass on
+fn eval_loop_body(body: &BlockStatement, env: &Env) -> Option<Object> {
+    match eval_block_statement(body, env) {
+        Object::Break => Some(Object::Null),
+        result @ (Object::ReturnValue(_) | Object::Error { .. }) => Some(result),
+        _ => None,
+    }
+}
+
+fn eval_expression(expression: &Expression, env: &Env) -> Object {
+    let result = match expression {
+        Expression::Identifier { name, .. } => eval_identifier(name, env),
+        Expression::IntegerLiteral { value, .. } => Object::Integer(*value),
+        Expression::FloatLiteral { value, .. } => Object::Float(*value),
+        Expression::Boolean { value, .. } => Object::Boolean(*value),
+        Expression::StringLiteral { value, .. } => Object::String(value.clone()),
+        Expression::Prefix {
+            operator, right, ..
+        } => {
+            let right = eval_expression(right, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_prefix_expression(*operator, right)
+        }
+        Expression::Infix {
+            left,
+            operator: operator @ (TokenType::AND | TokenType::OR),
+            right,
+            ..
+        } => eval_logical_expression(*operator, left, right, env),
+        Expression::Infix {
+            left,
+            operator,
+            right,
+            ..
+        } => {
+            let left = eval_expression(left, env);
+            if left.is_error() {
+                return left;
+            }
+            let right = eval_expression(right, env);
+            if right.is_error() {
+                return right;
+            }
+            eval_infix_expression(*operator, left, right)
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => {
+            let condition = eval_expression(condition, env);
+            if condition.is_error() {
+                return condition;
+            }
+
+            if condition.is_truthy() {
+                eval_block_statement(consequence, env)
+            } else if let Some(alternative) = alternative {
+                eval_block_statement(alternative, env)
+            } else {
+                Object::Null
+            }
+        }
+        Expression::ArrayLiteral { elements, .. } => match eval_expressions(elements, env) {
+            Ok(elements) => Object::Array(elements),
+            Err(err) => err,
+        },
+        Expression::Index { left, index, .. } => {
+            let left = eval_expression(left, env);
+            if left.is_error() {
+                return left;
+            }
+            let index = eval_expression(index, env);
+            if index.is_error() {
+                return index;
+            }
+            eval_index_expression(left, index)
+        }
+        Expression::HashLiteral { pairs, .. } => eval_hash_literal(pairs, env),
+        Expression::FunctionLiteral {
+            parameters, body, ..
+        } => Object::Function {
+            parameters: parameters.clone(),
+            body: body.clone(),
+            env: Rc::clone(env),
+        },
+        Expression::MacroLiteral { .. } => {
+            Object::error("macros can only be bound by a top level let")
+        }
+        Expression::Call {
+            function,
+            arguments,
+            ..
+        } if is_named(function, "quote") => quote(arguments, env),
+        Expression::Call {
+            function,
+            arguments,
+            ..
+        } => {
+            let function = eval_expression(function, env);
+            if function.is_error() {
+                return function;
+            }
+
+            match eval_expressions(arguments, env) {
+                Ok(args) => apply_function(function, args),
+                Err(err) => err,
+            }
+        }
+    };
+
+    locate(result, expression.span())
+}
+
+/// returns whether `expression` is the identifier `name`
+fn is_named(expression: &Expression, name: &str) -> bool {
+    matches!(expression, Expression::Identifier { name: identifier, .. } if identifier == name)
+}
+
+/// returns the argument of `quote(<argument>)` unevaluated, except for the
+/// `unquote(<expression>)` calls in it, which are replaced by the value of
+/// their expression
+fn quote(arguments: &[Expression], env: &Env) -> Object {
+    if arguments.len() != 1 {
+        return Object::error(format!(
+            "wrong number of arguments: want=1, got={}",
+            arguments.len()
+        ));
+    }
+
+    let quoted = modify_expression(arguments[0].clone(), &mut |expression| match expression {
+        Expression::Call {
+            function,
+            arguments,
+            span,
+        } if is_named(&function, "unquote") && arguments.len() == 1 => {
+            let value = eval_expression(&arguments[0], env);
+            if value.is_error() {
+                return Err(value);
+            }
+            into_expression(value, span)
+        }
+        other => Ok(other),
+    });
+    match quoted {
+        Ok(expression) => Object::Quote(Box::new(expression)),
+        Err(err) => err,
+    }
+}
+
+/// returns the expression evaluating to `value`, placed at `span`. only
+/// numbers, booleans, strings and quotes have one.
+fn into_expression(value: Object, span: Span) -> Result<Expression, Object> {
+    match value {
+        Object::Integer(value) => Ok(Expression::IntegerLiteral { value, span }),
+        Object::Float(value) => Ok(Expression::FloatLiteral { value, span }),
+        Object::Boolean(value) => Ok(Expression::Boolean { value, span }),
+        Object::String(value) => Ok(Expression::StringLiteral { value, span }),
+        Object::Quote(expression) => Ok(*expression),
+        other => Err(locate(
+            Object::error(format!("cannot unquote {}", other.type_name())),
+            span,
+        )),
+    }
+}
+
+/// gives an error raised by an expression the span of the expression. errors
+/// raised by nested expressions keep the span they were given there.
+fn locate(result: Object, span: Span) -> Object {
+    match result {
+        Object::Error {
+            message,
+            span: None,
+        } => Object::Error {
+            message,
+            span: Some(span),
+        },
+        result => result,
+    }
+}
+
+/// evaluates `expressions` left to right, stopping at the first error
+fn eval_expressions(expressions: &[Expression], env: &Env) -> Result<Vec<Object>, Object> {
+    let mut values = Vec::with_capacity(expressions.len());
+    for expression in expressions {
+        let value = eval_expression(expression, env);
+        if value.is_error() {
+            return Err(value);
+        }
+        values.push(value);
+    }
+    Ok(values)
+}
+
+fn eval_identifier(name: &str, env: &Env) -> Object {
+    if let Some(value) = env.borrow().get(name) {
+        return value;
+    }
+    match builtins::lookup(name) {
+        Some(builtin) => builtin,
+        None => Object::error(format!("identifier not found: {}", name)),
+    }
+}
+
+/// evaluates `&&` and `||` to a boolean, `right` is only evaluated when
+/// `left` doesn't decide the result
+fn eval_logical_expression(
+    operator: TokenType,
+    left: &Expression,
+    right: &Expression,
+    env: &Env,
+) -> Object {
+    let left = eval_expression(left, env);
+    if left.is_error() {
+        return left;
+    }
+    if left.is_truthy() == (operator == TokenType::OR) {
+        return Object::Boolean(left.is_truthy());
+    }
+
+    let right = eval_expression(right, env);
+    if right.is_error() {
+        return right;
+    }
+    Object::Boolean(right.is_truthy())
+}
+
+/// applies a prefix operator, shared with the vm so both backends agree
+pub(crate) fn eval_prefix_expression(operator: TokenType, right: Object) -> Object {
+    match operator {
+        TokenType::BANG => Object::Boolean(!right.is_truthy()),
+        TokenType::MINUS => match right {
+            Object::Integer(value) => match value.checked_neg() {
+                Some(value) => Object::Integer(value),
+                #[cfg(feature = "bigint")]
+                None => big_integer(-BigInt::from(value)),
+                #[cfg(not(feature = "bigint"))]
+                None => integer_overflow(format!("-{}", value)),
+            },
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(value) => big_integer(-value),
+            Object::Float(value) => Object::Float(-value),
+            right => Object::error(format!("unknown operator: -{}", right.type_name())),
+        },
+        _ => Object::error(format!(
+            "unknown operator: {}{}",
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+/// applies an infix operator, shared with the vm so both backends agree
+pub(crate) fn eval_infix_expression(operator: TokenType, left: Object, right: Object) -> Object {
+    match (&left, &right) {
+        (Object::Integer(left), Object::Integer(right)) => {
+            eval_integer_infix_expression(operator, *left, *right)
+        }
+        #[cfg(feature = "bigint")]
+        (Object::BigInteger(_), Object::Integer(_) | Object::BigInteger(_))
+        | (Object::Integer(_), Object::BigInteger(_)) => {
+            eval_big_integer_infix_expression(operator, as_big(&left), as_big(&right))
+        }
+        // integers are promoted when mixed with floats
+        (Object::Float(_), _) | (_, Object::Float(_)) => {
+            match (as_float(&left), as_float(&right)) {
+                (Some(left), Some(right)) => eval_float_infix_expression(operator, left, right),
+                _ => type_mismatch(operator, &left, &right),
+            }
+        }
+        (Object::Boolean(left), Object::Boolean(right)) => match operator {
+            TokenType::EQ => Object::Boolean(left == right),
+            TokenType::NotEq => Object::Boolean(left != right),
+            _ => Object::error(format!("unknown operator: BOOLEAN {} BOOLEAN", operator)),
+        },
+        (Object::String(left), Object::String(right)) => match operator {
+            TokenType::PLUS => Object::String(format!("{}{}", left, right)),
+            TokenType::EQ => Object::Boolean(left == right),
+            TokenType::NotEq => Object::Boolean(left != right),
+            _ => Object::error(format!("unknown operator: STRING {} STRING", operator)),
+        },
+        _ if left.type_name() != right.type_name() => type_mismatch(operator, &left, &right),
+        _ => Object::error(format!(
+            "unknown operator: {} {} {}",
+            left.type_name(),
+            operator,
+            right.type_name()
+        )),
+    }
+}
+
+fn type_mismatch(operator: TokenType, left: &Object, right: &Object) -> Object {
+    Object::error(format!(
+        "type mismatch: {} {} {}",
+        left.type_name(),
+        operator,
+        right.type_name()
+    ))
+}
+
+/// applies an operator to two integers, arithmetic is checked: dividing by
+/// zero is an error, and so is overflowing an `i64` unless the `bigint`
+/// feature promotes the result
+fn eval_integer_infix_expression(operator: TokenType, left: i64, right: i64) -> Object {
+    let result = match operator {
+        TokenType::PLUS => left.checked_add(right),
+        TokenType::MINUS => left.checked_sub(right),
+        TokenType::ASTERISK => left.checked_mul(right),
+        TokenType::SLASH | TokenType::PERCENT if right == 0 => return division_by_zero(),
+        TokenType::SLASH => left.checked_div(right),
+        TokenType::PERCENT => left.checked_rem(right),
+        TokenType::POWER if right < 0 => return negative_exponent(left, right),
+        TokenType::POWER => checked_pow(left, right),
+        TokenType::LT => return Object::Boolean(left < right),
+        TokenType::GT => return Object::Boolean(left > right),
+        TokenType::LtEq => return Object::Boolean(left <= right),
+        TokenType::GtEq => return Object::Boolean(left >= right),
+        TokenType::EQ => return Object::Boolean(left == right),
+        TokenType::NotEq => return Object::Boolean(left != right),
+        _ => return Object::error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+    };
+
+    match result {
+        Some(value) => Object::Integer(value),
+        #[cfg(feature = "bigint")]
+        None => eval_big_integer_infix_expression(operator, left.into(), right.into()),
+        #[cfg(not(feature = "bigint"))]
+        None => integer_overflow(format!("{} {} {}", left, operator, right)),
+    }
+}
+
+#[cfg(feature = "bigint")]
+fn eval_big_integer_infix_expression(operator: TokenType, left: BigInt, right: BigInt) -> Object {
+    match operator {
+        TokenType::PLUS => big_integer(left + right),
+        TokenType::MINUS => big_integer(left - right),
+        TokenType::ASTERISK => big_integer(left * right),
+        TokenType::SLASH | TokenType::PERCENT if right == BigInt::from(0) => division_by_zero(),
+        TokenType::SLASH => big_integer(left / right),
+        TokenType::PERCENT => big_integer(left % right),
+        TokenType::POWER if right < BigInt::from(0) => negative_exponent(left, right),
+        TokenType::POWER => match right.to_u32() {
+            Some(exponent) => big_integer(left.pow(exponent)),
+            // only -1, 0 and 1 can be raised to an exponent this big
+            None => match left.to_i64() {
+                Some(-1) => Object::Integer(if right.bit(0) { -1 } else { 1 }),
+                Some(base @ 0..=1) => Object::Integer(base),
+                _ => Object::error(format!("integer overflow: {} ** {}", left, right)),
+            },
+        },
+        TokenType::LT => Object::Boolean(left < right),
+        TokenType::GT => Object::Boolean(left > right),
+        TokenType::LtEq => Object::Boolean(left <= right),
+        TokenType::GtEq => Object::Boolean(left >= right),
+        TokenType::EQ => Object::Boolean(left == right),
+        TokenType::NotEq => Object::Boolean(left != right),
+        _ => Object::error(format!("unknown operator: INTEGER {} INTEGER", operator)),
+    }
+}
+
+/// returns `value` as an `Integer` when it fits one, so each integer has a
+/// single representation
+#[cfg(feature = "bigint")]
+fn big_integer(value: BigInt) -> Object {
+    match value.to_i64() {
+        Some(value) => Object::Integer(value),
+        None => Object::BigInteger(value),
+    }
+}
+
+/// returns the value of an `Integer` or `BigInteger` object as a `BigInt`
+#[cfg(feature = "bigint")]
+fn as_big(integer: &Object) -> BigInt {
+    match integer {
+        Object::Integer(value) => BigInt::from(*value),
+        Object::BigInteger(value) => value.clone(),
+        _ => unreachable!("only integers are promoted to big integers"),
+    }
+}
+
+/// returns `base ** exponent`, `None` when it overflows an `i64`. a base of
+/// -1, 0 or 1 is never too big, whatever the exponent.
+fn checked_pow(base: i64, exponent: i64) -> Option<i64> {
+    match u32::try_from(exponent) {
+        Ok(exponent) => base.checked_pow(exponent),
+        Err(_) if base == -1 => Some(if exponent % 2 == 0 { 1 } else { -1 }),
+        Err(_) if base == 0 || base == 1 => Some(base),
+        Err(_) => None,
+    }
+}
+
+fn negative_exponent(base: impl fmt::Display, exponent: impl fmt::Display) -> Object {
+    Object::error(format!(
+        "negative exponent: {} ** {}, use a float base for fractions",
+        base, exponent
+    ))
+}
+
+fn division_by_zero() -> Object {
+    Object::error("division by zero")
+}
+
+/// returns the error of an integer operation overflowing, `operation` is the
+/// operation written out, ie: `9223372036854775807 + 1`
+#[cfg(not(feature = "bigint"))]
+fn integer_overflow(operation: String) -> Object {
+    Object::error(format!("integer overflow: {}", operation))
+}
+
+fn eval_float_infix_expression(operator: TokenType, left: f64, right: f64) -> Object {
+    match operator {
+        TokenType::PLUS => Object::Float(left + right),
+        TokenType::MINUS => Object::Float(left - right),
+        TokenType::ASTERISK => Object::Float(left * right),
+        TokenType::SLASH => Object::Float(left / right),
+        TokenType::PERCENT => Object::Float(left % right),
+        TokenType::POWER => Object::Float(left.powf(right)),
+        TokenType::LT => Object::Boolean(left < right),
+        TokenType::GT => Object::Boolean(left > right),
+        TokenType::LtEq => Object::Boolean(left <= right),
+        TokenType::GtEq => Object::Boolean(left >= right),
+        TokenType::EQ => Object::Boolean(left == right),
+        TokenType::NotEq => Object::Boolean(left != right),
+        _ => Object::error(format!("unknown operator: FLOAT {} FLOAT", operator)),
+    }
+}
+
+/// returns the value of a number object as an `f64`, `None` for other objects
+fn as_float(number: &Object) -> Option<f64> {
+    match number {
+        Object::Integer(value) => Some(*value as f64),
+        #[cfg(feature = "bigint")]
+        Object::BigInteger(value) => value.to_f64(),
+        Object::Float(value) => Some(*value),
+        _ => None,
+    }
+}
+
+/// evaluates the pairs of a hash literal in source order, a later pair replaces
+/// an earlier one with the same key
+fn eval_hash_literal(pairs: &[(Expression, Expression)], env: &Env) -> Object {
+    let mut hash = BTreeMap::new();
+
+    for (key, value) in pairs {
+        let key = eval_expression(key, env);
+        if key.is_error() {
+            return key;
+        }
+        let hash_key = match key.hash_key() {
+            Some(hash_key) => hash_key,
+            None => return unusable_as_hash_key(&key),
+        };
+        let value = eval_expression(value, env);
+        if value.is_error() {
+            return value;
+        }
+        hash.insert(hash_key, HashPair { key, value });
+    }
+
+    Object::Hash(hash)
+}
+
+pub(crate) fn unusable_as_hash_key(key: &Object) -> Object {
+    Object::error(format!("unusable as hash key: {}", key.type_name()))
+}
+
+/// returns the element of an array at `index` or the value of a hash at the
+/// key `index`, `null` when there is no such element
+pub(crate) fn eval_index_expression(left: Object, index: Object) -> Object {
+    match (&left, &index) {
+        (Object::Hash(pairs), _) => match index.hash_key() {
+            Some(key) => pairs
+                .get(&key)
+                .map_or(Object::Null, |pair| pair.value.clone()),
+            None => unusable_as_hash_key(&index),
+        },
+        (Object::Array(elements), Object::Integer(i)) => {
+            if *i < 0 {
+                return Object::Null;
+            }
+            elements.get(*i as usize).cloned().unwrap_or(Object::Null)
+        }
+        // too big to be the index of any element
+        #[cfg(feature = "bigint")]
+        (Object::Array(_), Object::BigInteger(_)) => Object::Null,
+        _ => Object::error(format!(
+            "index operator not supported: {}[{}]",
+            left.type_name(),
+            index.type_name()
+        )),
+    }
+}
+
+/// calls `function` with `args` bound to its parameters in a new environment
+/// enclosed by the one the function was defined in
+pub(crate) fn apply_function(function: Object, args: Vec<Object>) -> Object {
+    match function {
+        Object::Function {
+            parameters,
+            body,
+            env,
+        } => {
+            if parameters.len() != args.len() {
+                return Object::error(format!(
+                    "wrong number of arguments: want={}, got={}",
+                    parameters.len(),
+                    args.len()
+                ));
+            }
+
+            let mut extended = Environment::new_enclosed(env);
+            for (parameter, arg) in parameters.into_iter().zip(args) {
+                extended.set(parameter, arg);
+            }
+
+            match eval_block_statement(&body, &Rc::new(RefCell::new(extended))) {
+                Object::ReturnValue(value) => *value,
+                result => result,
+            }
+        }
+        Object::Builtin { function, .. } => function(args),
+        other => Object::error(format!("not a function: {}", other.type_name())),
+    }
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::object::HashKey;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+
+    fn test_eval(input: &str) -> Object {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert_eq!(parser.errors(), &[], "input should parse");
+        eval(&program, &Rc::new(RefCell::new(Environment::new())))
+    }
+
+    fn assert_evals(tests: &[(&str, Object)]) {
+        for (input, expected) in tests {
+            assert_eq!(test_eval(input), *expected, "input: {}", input);
+        }
+    }
+
+    #[test]
+    fn integer_expressions() {
+        assert_evals(&[
+            ("5", Object::Integer(5)),
+            ("-10", Object::Integer(-10)),
+            ("5 + 5 + 5 + 5 - 10", Object::Integer(10)),
+            ("2 * 2 * 2 * 2 * 2", Object::Integer(32)),
+            ("50 / 2 * 2 + 10", Object::Integer(60)),
+            ("3 * (3 * 3) + 10", Object::Integer(37)),
+            ("(5 + 10 * 2 + 15 / 3) * 2 + -10", Object::Integer(50)),
+        ]);
+    }
+
+    #[test]
+    fn boolean_expressions() {
+        assert_evals(&[
+            ("true", Object::Boolean(true)),
+            ("1 < 2", Object::Boolean(true)),
+            ("1 > 2", Object::Boolean(false)),
+            ("1 == 1", Object::Boolean(true)),
+            ("1 != 1", Object::Boolean(false)),
+            ("true != false", Object::Boolean(true)),
+            ("(1 < 2) == true", Object::Boolean(true)),
+            ("!true", Object::Boolean(false)),
+            ("!!5", Object::Boolean(true)),
+        ]);
+    }
+
+    #[test]
+    fn float_expressions() {
+        assert_evals(&[
+            ("3.5", Object::Float(3.5)),
+            ("-2.5", Object::Float(-2.5)),
+            ("0.5 + 0.25", Object::Float(0.75)),
+            ("1 + 0.5", Object::Float(1.5)),
+            ("1.5 * 2", Object::Float(3.0)),
+            ("7 / 2.0", Object::Float(3.5)),
+            ("7 / 2", Object::Integer(3)),
+            ("1e3 - 1", Object::Float(999.0)),
+            ("1.0 / 0", Object::Float(f64::INFINITY)),
+            ("0.1 < 1", Object::Boolean(true)),
+            ("2 == 2.0", Object::Boolean(true)),
+            ("2.5 != 2.5", Object::Boolean(false)),
+            ("0xff + 0b1 + 0o10 + 1_000", Object::Integer(1264)),
+            (
+                "1.5 + true",
+                Object::error("type mismatch: FLOAT + BOOLEAN"),
+            ),
+            ("{1.5: 1}", Object::error("unusable as hash key: FLOAT")),
+        ]);
+    }
+
+    #[test]
+    fn logical_expressions() {
+        assert_evals(&[
+            ("true && 1", Object::Boolean(true)),
+            ("0 && false", Object::Boolean(false)),
+            ("false || \"\"", Object::Boolean(true)),
+            ("!true || !true", Object::Boolean(false)),
+            ("false && missing", Object::Boolean(false)),
+            ("true || missing", Object::Boolean(true)),
+            (
+                "true && missing",
+                Object::error("identifier not found: missing"),
+            ),
+            ("1 <= 2 && 2.5 >= 2", Object::Boolean(true)),
+            ("10 % 4 ** 2", Object::Integer(10)),
+        ]);
+    }
+
+    #[test]
+    fn assignments_and_loops() {
+        assert_evals(&[
+            ("let x = 1; x = 2; x", Object::Integer(2)),
+            ("let x = 10; x -= 3; x *= 2; x /= 7; x %= 3; x", Object::Integer(2)),
+            ("let s = \"a\"; s += \"b\"; s", Object::String("ab".to_string())),
+            ("x = 1", Object::error("identifier not found: x")),
+            ("let x = true; x += 1", Object::error("type mismatch: BOOLEAN + INTEGER")),
+            (
+                "let count = 0; let inc = fn() { count += 1 }; inc(); inc(); count",
+                Object::Integer(2),
+            ),
+            (
+                "let i = 0; let sum = 0; while (i < 5) { i += 1; sum += i; } sum",
+                Object::Integer(15),
+            ),
+            (
+                "let sum = 0; for (x in [1, 2, 3, 4]) { if (x == 2) { continue; } if (x == 4) { break; } sum += x; } sum",
+                Object::Integer(4),
+            ),
+            (
+                "let f = fn() { while (true) { return 3; } }; f()",
+                Object::Integer(3),
+            ),
+            ("let x = 5; for (x in [1, 2]) {} x", Object::Integer(5)),
+            (
+                "let fs = []; for (x in [1, 2]) { fs = push(fs, fn() { x }); } fs[0]() + fs[1]()",
+                Object::Integer(3),
+            ),
+            (
+                "let n = 0; for (row in [[1, 2], [3]]) { for (x in row) { if (x == 2) { break; } n += x; } } n",
+                Object::Integer(4),
+            ),
+            ("for (x in 5) { x }", Object::error("not iterable: INTEGER")),
+            ("while (1 / 0) { 1 }", Object::error("division by zero")),
+        ]);
+    }
+
+    #[test]
+    fn quote_unquote() {
+        let tests = [
+            ("quote(5)", "5"),
+            ("quote(5 + 8)", "(5 + 8)"),
+            ("quote(foobar + barfoo)", "(foobar + barfoo)"),
+            ("quote(unquote(4))", "4"),
+            ("quote(8 + unquote(4 + 4))", "(8 + 8)"),
+            ("quote(unquote(4 + 4) + 8)", "(8 + 8)"),
+            ("let foobar = 8; quote(unquote(foobar))", "8"),
+            ("quote(unquote(true == false))", "false"),
+            ("quote(unquote(1.5 * 2) + unquote(\"a\"))", "(3.0 + \"a\")"),
+            ("quote(unquote(quote(4 + 4)))", "(4 + 4)"),
+            (
+                "let quoted = quote(4 + 4); quote(unquote(4 + 4) + unquote(quoted))",
+                "(8 + (4 + 4))",
+            ),
+        ];
+
+        for (input, expected) in tests.iter() {
+            match test_eval(input) {
+                Object::Quote(expression) => assert_eq!(expression.to_string(), *expected),
+                other => panic!("expected quote, got {:?}, input: {}", other, input),
+            }
+        }
+
+        assert_evals(&[
+            (
+                "quote(1, 2)",
+                Object::error("wrong number of arguments: want=1, got=2"),
+            ),
+            ("quote(unquote([1]))", Object::error("cannot unquote ARRAY")),
+            (
+                "quote(unquote(missing))",
+                Object::error("identifier not found: missing"),
+            ),
+            ("unquote(1)", Object::error("identifier not found: unquote")),
+            (
+                "let m = fn() { macro() { 1 } }; m()",
+                Object::error("macros can only be bound by a top level let"),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn string_expressions() {
+        assert_evals(&[
+            ("\"hello world\"", Object::String("hello world".to_string())),
+            (
+                "\"hello\" + \" \" + \"world\"",
+                Object::String("hello world".to_string()),
+            ),
+            ("\"tab\\there\"", Object::String("tab\there".to_string())),
+            ("\"a\" == \"a\"", Object::Boolean(true)),
+            ("\"a\" != \"a\"", Object::Boolean(false)),
+            ("\"a\" == \"b\"", Object::Boolean(false)),
+            (
+                "let greet = fn(name) { \"hi \" + name }; greet(\"bob\")",
+                Object::String("hi bob".to_string()),
+            ),
+            (
+                "\"a\" - \"b\"",
+                Object::error("unknown operator: STRING - STRING"),
+            ),
+            (
+                "\"a\" + 1",
+                Object::error("type mismatch: STRING + INTEGER"),
+            ),
+        ]);
+    }
+
+    fn array(values: &[i64]) -> Object {
+        Object::Array(values.iter().map(|v| Object::Integer(*v)).collect())
+    }
+
+    #[test]
+    fn array_expressions() {
+        assert_evals(&[
+            ("[1, 2 * 2, 3 + 3]", array(&[1, 4, 6])),
+            ("[1, 2, 3][0]", Object::Integer(1)),
+            ("let i = 0; [1][i]", Object::Integer(1)),
+            ("[1, 2, 3][1 + 1]", Object::Integer(3)),
+            (
+                "let myArray = [1, 2, 3]; myArray[0] + myArray[1] + myArray[2]",
+                Object::Integer(6),
+            ),
+            ("[1, 2, 3][3]", Object::Null),
+            ("[1, 2, 3][-1]", Object::Null),
+            ("[[1, 2]][0][1]", Object::Integer(2)),
+            (
+                "1[0]",
+                Object::error("index operator not supported: INTEGER[INTEGER]"),
+            ),
+            ("[1, foo]", Object::error("identifier not found: foo")),
+        ]);
+        assert_eq!(test_eval("[1, \"two\", [3]]").to_string(), "[1, two, [3]]");
+    }
+
+    #[test]
+    fn hash_expressions() {
+        let hash = test_eval(
+            "let two = \"two\"; {\"one\": 10 - 9, two: 1 + 1, \"thr\" + \"ee\": 6 / 2, 4: 4, true: 5, false: 6}",
+        );
+        match &hash {
+            Object::Hash(pairs) => {
+                let expected = [
+                    (HashKey::String("one".to_string()), 1),
+                    (HashKey::String("two".to_string()), 2),
+                    (HashKey::String("three".to_string()), 3),
+                    (HashKey::Integer(4), 4),
+                    (HashKey::Boolean(true), 5),
+                    (HashKey::Boolean(false), 6),
+                ];
+                assert_eq!(pairs.len(), expected.len());
+                for (key, value) in expected.iter() {
+                    assert_eq!(pairs[key].value, Object::Integer(*value));
+                }
+            }
+            other => panic!("expected hash, got {:?}", other),
+        }
+        assert_eq!(
+            hash.to_string(),
+            "{4: 4, false: 6, true: 5, one: 1, three: 3, two: 2}"
+        );
+
+        assert_evals(&[
+            ("{\"foo\": 5}[\"foo\"]", Object::Integer(5)),
+            ("{\"foo\": 5}[\"bar\"]", Object::Null),
+            ("let key = \"foo\"; {\"foo\": 5}[key]", Object::Integer(5)),
+            ("let h = {}; h[\"foo\"]", Object::Null),
+            ("{5: 5}[5]", Object::Integer(5)),
+            ("{true: 5}[true]", Object::Integer(5)),
+            ("{1: 1, 1: 2}[1]", Object::Integer(2)),
+            (
+                "{\"double\": fn(x) { x * 2 }}[\"double\"](3)",
+                Object::Integer(6),
+            ),
+            (
+                "{\"a\": 1} == {\"a\": 1}",
+                Object::error("unknown operator: HASH == HASH"),
+            ),
+            (
+                "{fn(x) { x }: 1}",
+                Object::error("unusable as hash key: FUNCTION"),
+            ),
+            (
+                "{\"a\": 1}[[1]]",
+                Object::error("unusable as hash key: ARRAY"),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn builtin_functions() {
+        assert_evals(&[
+            ("len(\"\")", Object::Integer(0)),
+            ("len(\"four\")", Object::Integer(4)),
+            ("len(\"h\\u{e9}\")", Object::Integer(2)),
+            ("len([1, 2, 3])", Object::Integer(3)),
+            (
+                "len(1)",
+                Object::error("argument to `len` not supported, got INTEGER"),
+            ),
+            (
+                "len(\"one\", \"two\")",
+                Object::error("wrong number of arguments: want=1, got=2"),
+            ),
+            ("first([1, 2, 3])", Object::Integer(1)),
+            ("first([])", Object::Null),
+            (
+                "first(1)",
+                Object::error("argument to `first` must be ARRAY, got INTEGER"),
+            ),
+            ("last([1, 2, 3])", Object::Integer(3)),
+            ("last([])", Object::Null),
+            ("rest([1, 2, 3])", array(&[2, 3])),
+            ("rest([])", Object::Null),
+            ("push([], 1)", array(&[1])),
+            ("let a = [1]; push(a, 2); a", array(&[1])),
+            (
+                "push(1, 1)",
+                Object::error("argument to `push` must be ARRAY, got INTEGER"),
+            ),
+            ("let len = fn(x) { 42 }; len([])", Object::Integer(42)),
+            (
+                "let map = fn(arr, f) { let iter = fn(arr, acc) { if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) } }; iter(arr, []) }; map([1, 2, 3], fn(x) { x * 2 })",
+                array(&[2, 4, 6]),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn if_else_expressions() {
+        assert_evals(&[
+            ("if (true) { 10 }", Object::Integer(10)),
+            ("if (false) { 10 }", Object::Null),
+            ("if (1) { 10 }", Object::Integer(10)),
+            ("if (1 > 2) { 10 } else { 20 }", Object::Integer(20)),
+        ]);
+    }
+
+    #[test]
+    fn return_statements() {
+        assert_evals(&[
+            ("return 10; 9;", Object::Integer(10)),
+            ("9; return 2 * 5; 9;", Object::Integer(10)),
+            (
+                "if (10 > 1) { if (10 > 1) { return 10; } return 1; }",
+                Object::Integer(10),
+            ),
+            ("{ return 3; } 4", Object::Integer(3)),
+        ]);
+    }
+
+    #[test]
+    fn error_handling() {
+        assert_evals(&[
+            (
+                "5 + true;",
+                Object::error("type mismatch: INTEGER + BOOLEAN"),
+            ),
+            ("-true", Object::error("unknown operator: -BOOLEAN")),
+            (
+                "if (10 > 1) { true + false; 5 }",
+                Object::error("unknown operator: BOOLEAN + BOOLEAN"),
+            ),
+            ("foobar", Object::error("identifier not found: foobar")),
+            (
+                "let f = fn(x) { x }; f(1, 2)",
+                Object::error("wrong number of arguments: want=1, got=2"),
+            ),
+            ("5(1)", Object::error("not a function: INTEGER")),
+        ]);
+    }
+
+    #[test]
+    fn error_spans() {
+        let tests = [
+            ("1 +\n  (true + 2)", Span::new(7, 15, 2, 4)),
+            ("let f = fn(x) { x / 0 };\nf(1)", Span::new(16, 21, 1, 17)),
+            ("[1, foo]", Span::new(4, 7, 1, 5)),
+            ("len(1)", Span::new(0, 6, 1, 1)),
+        ];
+
+        for (input, span) in tests.iter() {
+            match test_eval(input) {
+                Object::Error { span: found, .. } => {
+                    assert_eq!(found, Some(*span), "input: {}", input)
+                }
+                other => panic!("expected an error for {}, got {:?}", input, other),
+            }
+        }
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        assert_evals(&[
+            ("1 / 0", Object::error("division by zero")),
+            ("let zero = 0; 10 / zero", Object::error("division by zero")),
+            ("9223372036854775807 - 1 + 1", Object::Integer(i64::MAX)),
+            ("-9223372036854775807 - 1", Object::Integer(i64::MIN)),
+        ]);
+
+        #[cfg(not(feature = "bigint"))]
+        assert_evals(&[
+            (
+                "9223372036854775807 + 1",
+                Object::error("integer overflow: 9223372036854775807 + 1"),
+            ),
+            (
+                "let min = -9223372036854775807 - 1; min / -1",
+                Object::error("integer overflow: -9223372036854775808 / -1"),
+            ),
+            (
+                "let min = -9223372036854775807 - 1; -min",
+                Object::error("integer overflow: --9223372036854775808"),
+            ),
+            (
+                "4294967296 * 4294967296",
+                Object::error("integer overflow: 4294967296 * 4294967296"),
+            ),
+        ]);
+    }
+
+    #[cfg(feature = "bigint")]
+    #[test]
+    fn big_integers() {
+        let big = |digits: &str| Object::BigInteger(digits.parse().unwrap());
+
+        assert_evals(&[
+            ("9223372036854775807 + 1", big("9223372036854775808")),
+            ("4294967296 * 4294967296", big("18446744073709551616")),
+            (
+                "let min = -9223372036854775807 - 1; -min",
+                big("9223372036854775808"),
+            ),
+            ("9223372036854775807 + 1 - 1", Object::Integer(i64::MAX)),
+            (
+                "(9223372036854775807 + 1) / 2",
+                Object::Integer(4611686018427387904),
+            ),
+            (
+                "9223372036854775807 + 1 > 9223372036854775807",
+                Object::Boolean(true),
+            ),
+            (
+                "(9223372036854775807 + 1) / 0",
+                Object::error("division by zero"),
+            ),
+            (
+                "(9223372036854775807 + 1) * 0.5",
+                Object::Float(4611686018427387904.0),
+            ),
+            (
+                "{9223372036854775807 + 1: 1}[9223372036854775807 * 2 - 9223372036854775806]",
+                Object::Integer(1),
+            ),
+            ("[1][9223372036854775807 + 1]", Object::Null),
+            (
+                "9223372036854775807 + 1 + true",
+                Object::error("type mismatch: INTEGER + BOOLEAN"),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn let_statements() {
+        assert_evals(&[
+            ("let a = 5; a;", Object::Integer(5)),
+            ("let a = 5 * 5; a;", Object::Integer(25)),
+            (
+                "let a = 5; let b = a; let c = a + b + 5; c;",
+                Object::Integer(15),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn functions_and_closures() {
+        assert_evals(&[
+            (
+                "let add = fn(a, b) { a + b }; add(1, 2)",
+                Object::Integer(3),
+            ),
+            (
+                "let identity = fn(x) { return x; 0 }; identity(5);",
+                Object::Integer(5),
+            ),
+            ("fn(x) { x * 2 }(5)", Object::Integer(10)),
+            (
+                "let newAdder = fn(x) { fn(y) { x + y } }; let addTwo = newAdder(2); addTwo(3);",
+                Object::Integer(5),
+            ),
+            (
+                "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+                Object::Integer(610),
+            ),
+        ]);
+    }
+
+    #[test]
+    fn persistent_environment() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut parser = Parser::new(Lexer::new("let x = 20;"));
+        eval(&parser.parse_program(), &env);
+
+        let mut parser = Parser::new(Lexer::new("x + 1"));
+        assert_eq!(eval(&parser.parse_program(), &env), Object::Integer(21));
+    }
+}
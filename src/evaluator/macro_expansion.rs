@@ -0,0 +1,230 @@
+use crate::ast::ast::{Expression, Program, Statement};
+use crate::ast::modify::modify_program;
+use crate::evaluator::environment::Env;
+use crate::evaluator::evaluator::apply_function;
+use crate::evaluator::object::Object;
+
+/// moves the macros bound by the top level `let` statements of `program` into
+/// `env`, removing their statements from the program
+///
+/// # Arguments
+///
+/// * `program` - the parsed program, before `expand_macros`
+///
+/// * `env` - the environment of macro definitions, keep it around to define
+///   and expand macros of more programs against it
+///
+pub fn define_macros(program: &mut Program, env: &Env) {
+    program.statements.retain(|statement| match statement {
+        Statement::Let {
+            name,
+            value: Expression::MacroLiteral {
+                parameters, body, ..
+            },
+            ..
+        } => {
+            env.borrow_mut().set(
+                name.clone(),
+                Object::Macro {
+                    parameters: parameters.clone(),
+                    body: body.clone(),
+                    env: Env::clone(env),
+                },
+            );
+            false
+        }
+        _ => true,
+    });
+}
+
+/// replaces the calls to the macros of `env` in `program` by the quote each
+/// macro returns. the arguments of a call are passed to the macro quoted,
+/// unevaluated.
+///
+/// returns the first error a macro raises, or the error of a macro returning
+/// anything but a quote, located at the call
+///
+/// # Arguments
+///
+/// * `program` - the program `define_macros` removed the macro definitions of
+///
+/// * `env` - the environment of macro definitions
+///
+pub fn expand_macros(program: Program, env: &Env) -> Result<Program, Object> {
+    modify_program(program, &mut |expression| {
+        let (function, arguments, span) = match expression {
+            Expression::Call {
+                function,
+                arguments,
+                span,
+            } => (function, arguments, span),
+            other => return Ok(other),
+        };
+        let definition = match &*function {
+            Expression::Identifier { name, .. } => env.borrow().get(name),
+            _ => None,
+        };
+        let (parameters, body, macro_env) = match definition {
+            Some(Object::Macro {
+                parameters,
+                body,
+                env,
+            }) => (parameters, body, env),
+            _ => {
+                return Ok(Expression::Call {
+                    function,
+                    arguments,
+                    span,
+                })
+            }
+        };
+
+        let name = function.to_string();
+        let function = Object::Function {
+            parameters,
+            body,
+            env: macro_env,
+        };
+        let args = arguments
+            .into_iter()
+            .map(|argument| Object::Quote(Box::new(argument)))
+            .collect();
+        let err = match apply_function(function, args) {
+            Object::Quote(expression) => return Ok(*expression),
+            err @ Object::Error { span: Some(_), .. } => return Err(err),
+            Object::Error { message, .. } => message,
+            other => format!(
+                "macro {} returned {}, expected a quote",
+                name,
+                other.type_name()
+            ),
+        };
+        Err(Object::Error {
+            message: err,
+            span: Some(span),
+        })
+    })
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluator::environment::Environment;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+    use crate::tokens::tokens::Span;
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    fn parse(input: &str) -> Program {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert_eq!(parser.errors(), &[], "input should parse");
+        program
+    }
+
+    fn expand(input: &str) -> Result<Program, Object> {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut program = parse(input);
+        define_macros(&mut program, &env);
+        expand_macros(program, &env)
+    }
+
+    #[test]
+    fn define_macros_removes_definitions() {
+        let env = Rc::new(RefCell::new(Environment::new()));
+        let mut program = parse(
+            "let number = 1;
+let function = fn(x, y) { x + y };
+let mymacro = macro(x, y) { x + y; };",
+        );
+        define_macros(&mut program, &env);
+
+        assert_eq!(program.statements.len(), 2);
+        assert_eq!(env.borrow().get("number"), None);
+        assert_eq!(env.borrow().get("function"), None);
+        let definition = env.borrow().get("mymacro");
+        match definition {
+            Some(Object::Macro {
+                parameters, body, ..
+            }) => {
+                assert_eq!(parameters, ["x", "y"]);
+                assert_eq!(body.to_string(), "{ (x + y) }");
+            }
+            other => panic!("expected macro, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn expand_macro_calls() {
+        let tests = [
+            (
+                "let infix = macro() { quote(1 + 2); }; infix();",
+                "(1 + 2)",
+            ),
+            (
+                "let reverse = macro(a, b) { quote(unquote(b) - unquote(a)); }; reverse(2 + 2, 10 - 5);",
+                "((10 - 5) - (2 + 2))",
+            ),
+            (
+                "let unless = macro(condition, consequence, alternative) {
+    quote(if (!(unquote(condition))) {
+        unquote(consequence);
+    } else {
+        unquote(alternative);
+    });
+};
+unless(10 > 5, puts(\"not greater\"), puts(\"greater\"));",
+                "if (!(10 > 5)) { puts(\"not greater\") } else { puts(\"greater\") }",
+            ),
+            (
+                "let twice = macro(x) { quote(unquote(x) * 2) }; let f = fn(y) { twice(y + 1) }; f",
+                "let f = fn(y) { ((y + 1) * 2) };f",
+            ),
+        ];
+
+        for (input, expected) in tests.iter() {
+            assert_eq!(expand(input).unwrap().to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn expand_macro_errors() {
+        let tests = [
+            (
+                "let m = macro(x) { 1 };\nm(2)",
+                "macro m returned INTEGER, expected a quote",
+                Span::new(24, 28, 2, 1),
+            ),
+            (
+                "let m = macro(x) { quote(x) }; m()",
+                "wrong number of arguments: want=1, got=0",
+                Span::new(31, 34, 1, 32),
+            ),
+            (
+                "let m = macro() { 1 / 0 }; m()",
+                "division by zero",
+                Span::new(18, 23, 1, 19),
+            ),
+            (
+                "let m = macro() { quote(unquote(fn() {})) }; m()",
+                "cannot unquote FUNCTION",
+                Span::new(24, 40, 1, 25),
+            ),
+        ];
+
+        for (input, expected_message, expected_span) in tests.iter() {
+            match expand(input) {
+                Err(Object::Error { message, span }) => {
+                    assert_eq!(message, *expected_message, "input: {}", input);
+                    assert_eq!(span, Some(*expected_span), "input: {}", input);
+                }
+                other => panic!("expected error, got {:?}", other),
+            }
+        }
+    }
+}
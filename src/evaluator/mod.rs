@@ -0,0 +1,5 @@
+pub mod builtins;
+pub mod environment;
+pub mod evaluator;
+pub mod macro_expansion;
+pub mod object;
@@ -0,0 +1,310 @@
+use crate::ast::ast::{BlockStatement, Expression};
+use crate::code::code::{Instructions, LineEntry};
+use crate::evaluator::builtins::BuiltinFunction;
+use crate::evaluator::environment::Env;
+use crate::tokens::tokens::Span;
+#[cfg(feature = "bigint")]
+use num_bigint::BigInt;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::rc::Rc;
+
+/// represents the value of an object used as a hash key. only integers,
+/// booleans and strings can be used as keys.
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Hash)]
+pub enum HashKey {
+    Integer(i64),
+    #[cfg(feature = "bigint")]
+    BigInteger(BigInt),
+    Boolean(bool),
+    String(String),
+}
+
+/// represents an entry of a hash, the key is kept as an object to display it
+#[derive(Debug, PartialEq, Clone)]
+pub struct HashPair {
+    pub key: Object,
+    pub value: Object,
+}
+
+/// represents a function literal compiled to bytecode
+///
+/// # Parameters
+///
+/// * `instructions` - the body of the function
+///
+/// * `num_locals` - the number of stack slots the function uses for its
+///   parameters and local bindings
+///
+/// * `num_parameters` - the number of arguments the function takes
+///
+/// * `lines` - the source line of each run of instructions
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct CompiledFunction {
+    pub instructions: Instructions,
+    pub num_locals: usize,
+    pub num_parameters: usize,
+    pub lines: Vec<LineEntry>,
+}
+
+/// represents a compiled function together with the free variables it
+/// captured when it was created, each a `Cell` shared with the function it
+/// was captured from
+#[derive(Debug, PartialEq, Clone)]
+pub struct Closure {
+    pub function: Rc<CompiledFunction>,
+    pub free: Vec<Object>,
+}
+
+/// represents the values monkey lang code evaluates to
+#[derive(Clone)]
+pub enum Object {
+    Integer(i64),
+    /// an integer outside the range of `i64`, integers are only this big when
+    /// they don't fit an `Integer`
+    #[cfg(feature = "bigint")]
+    BigInteger(BigInt),
+    Float(f64),
+    Boolean(bool),
+    String(String),
+    Array(Vec<Object>),
+    /// a hash of key/value pairs, iterated in key order
+    Hash(BTreeMap<HashKey, HashPair>),
+    Null,
+    /// wraps the value of a `return` statement while it unwinds to the
+    /// enclosing function call or program
+    ReturnValue(Box<Object>),
+    /// unwinds a loop body to the loop a `break` leaves
+    Break,
+    /// unwinds a loop body to the loop a `continue` starts the next
+    /// iteration of
+    Continue,
+    /// a runtime error, stops evaluation of the program. `span` is the
+    /// expression that raised it, `None` until the evaluator locates it
+    Error {
+        message: String,
+        span: Option<Span>,
+    },
+    /// a function literal together with the environment it was defined in
+    Function {
+        parameters: Vec<String>,
+        body: BlockStatement,
+        env: Env,
+    },
+    /// the unevaluated expression passed to `quote`
+    Quote(Box<Expression>),
+    /// a macro literal together with the environment of macro definitions,
+    /// only seen during macro expansion
+    Macro {
+        parameters: Vec<String>,
+        body: BlockStatement,
+        env: Env,
+    },
+    /// a function built into the interpreter, ie: `len`
+    Builtin {
+        name: &'static str,
+        function: BuiltinFunction,
+    },
+    /// a function constant produced by the compiler, only seen by the vm
+    CompiledFunction(Rc<CompiledFunction>),
+    /// a function value of the vm
+    Closure(Rc<Closure>),
+    /// a local of the vm captured by a closure, shared by the function it
+    /// belongs to and the closures capturing it. only seen by the vm.
+    Cell(Rc<RefCell<Object>>),
+}
+
+impl Object {
+    /// returns a new runtime error not located in the source yet
+    pub fn error(message: impl Into<String>) -> Object {
+        Object::Error {
+            message: message.into(),
+            span: None,
+        }
+    }
+
+    /// returns the name of the object's type, as used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Object::Integer(_) => "INTEGER",
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(_) => "INTEGER",
+            Object::Float(_) => "FLOAT",
+            Object::Boolean(_) => "BOOLEAN",
+            Object::String(_) => "STRING",
+            Object::Array(_) => "ARRAY",
+            Object::Hash(_) => "HASH",
+            Object::Null => "NULL",
+            Object::ReturnValue(_) => "RETURN_VALUE",
+            Object::Break => "BREAK",
+            Object::Continue => "CONTINUE",
+            Object::Error { .. } => "ERROR",
+            Object::Function { .. } => "FUNCTION",
+            Object::Quote(_) => "QUOTE",
+            Object::Macro { .. } => "MACRO",
+            Object::Builtin { .. } => "BUILTIN",
+            Object::CompiledFunction(_) => "COMPILED_FUNCTION",
+            // closures are the vm's functions, named alike for matching errors
+            Object::Closure(_) => "FUNCTION",
+            Object::Cell(_) => "CELL",
+        }
+    }
+
+    /// returns whether the object counts as true in a condition. `false` and
+    /// `null` are the only falsy values.
+    pub fn is_truthy(&self) -> bool {
+        !matches!(self, Object::Boolean(false) | Object::Null)
+    }
+
+    /// returns the key the object is stored under in a hash, `None` if the
+    /// object can't be used as a hash key
+    pub fn hash_key(&self) -> Option<HashKey> {
+        match self {
+            Object::Integer(value) => Some(HashKey::Integer(*value)),
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(value) => Some(HashKey::BigInteger(value.clone())),
+            Object::Boolean(value) => Some(HashKey::Boolean(*value)),
+            Object::String(value) => Some(HashKey::String(value.clone())),
+            _ => None,
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        matches!(self, Object::Error { .. })
+    }
+}
+
+impl PartialEq for Object {
+    /// compares values structurally, functions are only equal when they share
+    /// the same defining environment, builtins are equal by name and errors by
+    /// message, wherever they were raised
+    fn eq(&self, other: &Object) -> bool {
+        match (self, other) {
+            (Object::Integer(a), Object::Integer(b)) => a == b,
+            #[cfg(feature = "bigint")]
+            (Object::BigInteger(a), Object::BigInteger(b)) => a == b,
+            (Object::Float(a), Object::Float(b)) => a == b,
+            (Object::Boolean(a), Object::Boolean(b)) => a == b,
+            (Object::String(a), Object::String(b)) => a == b,
+            (Object::Array(a), Object::Array(b)) => a == b,
+            (Object::Hash(a), Object::Hash(b)) => a == b,
+            (Object::Null, Object::Null) => true,
+            (Object::ReturnValue(a), Object::ReturnValue(b)) => a == b,
+            (Object::Break, Object::Break) | (Object::Continue, Object::Continue) => true,
+            (Object::Error { message: a, .. }, Object::Error { message: b, .. }) => a == b,
+            (
+                Object::Function {
+                    parameters: a_parameters,
+                    body: a_body,
+                    env: a_env,
+                },
+                Object::Function {
+                    parameters: b_parameters,
+                    body: b_body,
+                    env: b_env,
+                },
+            ) => a_parameters == b_parameters && a_body == b_body && Rc::ptr_eq(a_env, b_env),
+            (Object::Quote(a), Object::Quote(b)) => a == b,
+            (
+                Object::Macro {
+                    parameters: a_parameters,
+                    body: a_body,
+                    env: a_env,
+                },
+                Object::Macro {
+                    parameters: b_parameters,
+                    body: b_body,
+                    env: b_env,
+                },
+            ) => a_parameters == b_parameters && a_body == b_body && Rc::ptr_eq(a_env, b_env),
+            (Object::Builtin { name: a, .. }, Object::Builtin { name: b, .. }) => a == b,
+            (Object::CompiledFunction(a), Object::CompiledFunction(b)) => a == b,
+            (Object::Closure(a), Object::Closure(b)) => a == b,
+            // a cell may hold a closure capturing the cell itself
+            (Object::Cell(a), Object::Cell(b)) => Rc::ptr_eq(a, b),
+            _ => false,
+        }
+    }
+}
+
+impl fmt::Debug for Object {
+    // functions are printed without their environment, which may contain the
+    // function itself
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "Integer({})", value),
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(value) => write!(f, "BigInteger({})", value),
+            Object::Float(value) => write!(f, "Float({:?})", value),
+            Object::Boolean(value) => write!(f, "Boolean({})", value),
+            Object::String(value) => write!(f, "String({:?})", value),
+            Object::Array(elements) => write!(f, "Array({:?})", elements),
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .values()
+                    .map(|pair| format!("{:?}: {:?}", pair.key, pair.value))
+                    .collect();
+                write!(f, "Hash({{{}}})", pairs.join(", "))
+            }
+            Object::Null => write!(f, "Null"),
+            Object::ReturnValue(value) => write!(f, "ReturnValue({:?})", value),
+            Object::Break => write!(f, "Break"),
+            Object::Continue => write!(f, "Continue"),
+            Object::Error { message, .. } => write!(f, "Error({:?})", message),
+            Object::Function { .. } => write!(f, "Function({})", self),
+            Object::Quote(expression) => write!(f, "Quote({})", expression),
+            Object::Macro { .. } => write!(f, "Macro({})", self),
+            Object::Builtin { name, .. } => write!(f, "Builtin({})", name),
+            Object::CompiledFunction(function) => write!(f, "{:?}", function),
+            Object::Closure(closure) => write!(f, "{:?}", closure),
+            Object::Cell(_) => write!(f, "Cell"),
+        }
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Object::Integer(value) => write!(f, "{}", value),
+            #[cfg(feature = "bigint")]
+            Object::BigInteger(value) => write!(f, "{}", value),
+            // the debug form keeps the fraction of whole floats, ie: `2.0`
+            Object::Float(value) => write!(f, "{:?}", value),
+            Object::Boolean(value) => write!(f, "{}", value),
+            Object::String(value) => write!(f, "{}", value),
+            Object::Array(elements) => {
+                let elements: Vec<String> = elements.iter().map(|e| e.to_string()).collect();
+                write!(f, "[{}]", elements.join(", "))
+            }
+            Object::Hash(pairs) => {
+                let pairs: Vec<String> = pairs
+                    .values()
+                    .map(|pair| format!("{}: {}", pair.key, pair.value))
+                    .collect();
+                write!(f, "{{{}}}", pairs.join(", "))
+            }
+            Object::Null => write!(f, "null"),
+            Object::ReturnValue(value) => write!(f, "{}", value),
+            Object::Break => write!(f, "break"),
+            Object::Continue => write!(f, "continue"),
+            Object::Error { message, .. } => write!(f, "ERROR: {}", message),
+            Object::Function {
+                parameters, body, ..
+            } => write!(f, "fn({}) {}", parameters.join(", "), body),
+            Object::Quote(expression) => write!(f, "QUOTE({})", expression),
+            Object::Macro {
+                parameters, body, ..
+            } => write!(f, "macro({}) {}", parameters.join(", "), body),
+            Object::Builtin { name, .. } => write!(f, "builtin {}", name),
+            Object::CompiledFunction(function) => {
+                write!(f, "compiled fn/{}", function.num_parameters)
+            }
+            Object::Closure(closure) => {
+                write!(f, "compiled fn/{}", closure.function.num_parameters)
+            }
+            Object::Cell(_) => write!(f, "cell"),
+        }
+    }
+}
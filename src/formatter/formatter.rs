@@ -0,0 +1,608 @@
+use crate::ast::ast::{BlockStatement, Expression, Statement};
+use crate::lexer::lexer::Lexer;
+use crate::parser::parser::{ParseError, Parser, Precedence};
+use crate::tokens::tokens::{Token, TokenType};
+
+/// the width, in characters, lines are kept to by wrapping call arguments
+pub const MAX_WIDTH: usize = 80;
+
+const INDENT: &str = "    ";
+
+/// returns `source` formatted canonically: one statement per line, blocks
+/// indented by four spaces, a space around every infix operator, and the
+/// arguments of calls that don't fit in `MAX_WIDTH` wrapped one per line.
+/// runs of blank lines between statements are kept as a single blank line.
+///
+/// comments are kept on their own line, or at the end of the line of the
+/// statement they follow. comments inside an expression but outside its
+/// blocks are moved to the end of the statement.
+///
+/// the formatted source parses to the same AST as `source`, spans aside.
+///
+/// # Arguments
+///
+/// * `source` - the monkey lang source to format
+///
+pub fn format(source: &str) -> Result<String, Vec<ParseError>> {
+    let mut parser = Parser::new(Lexer::new(source));
+    let program = parser.parse_program();
+
+    if !parser.errors().is_empty() {
+        return Err(parser.errors().to_vec());
+    }
+
+    let comments = Lexer::new(source)
+        .with_comments()
+        .filter(|token| token.token_type == TokenType::COMMENT)
+        .collect();
+    let mut formatter = Formatter {
+        source,
+        comments,
+        next_comment: 0,
+        out: String::new(),
+        depth: 0,
+        fresh: true,
+        last_end: 0,
+    };
+    formatter.statements(&program.statements, source.len());
+    if !formatter.out.is_empty() {
+        formatter.out.push('\n');
+    }
+
+    Ok(formatter.out)
+}
+
+/// writes the formatted source of an AST
+///
+/// # Parameters
+///
+/// * `source` - the source the AST was parsed from, used to find blank lines
+///
+/// * `comments` - the comments of `source`, in source order
+///
+/// * `next_comment` - the index of the first comment not written yet
+///
+/// * `out` - the formatted source written so far
+///
+/// * `depth` - how many blocks deep the statements being written are
+///
+/// * `fresh` - whether nothing has been written in the current block yet
+///
+/// * `last_end` - the offset in `source` of the end of the last statement or
+///   comment written
+///
+struct Formatter<'a> {
+    source: &'a str,
+    comments: Vec<Token<'a>>,
+    next_comment: usize,
+    out: String,
+    depth: usize,
+    fresh: bool,
+    last_end: usize,
+}
+
+impl<'a> Formatter<'a> {
+    /// writes `statements` and the comments among them, up to the offset
+    /// `end` in `source`
+    fn statements(&mut self, statements: &[Statement], end: usize) {
+        for (i, statement) in statements.iter().enumerate() {
+            let span = statement.span();
+            self.comments_before(span.start);
+            self.start_line(span.start);
+            self.statement(statement, i + 1 == statements.len());
+            self.last_end = span.end;
+            self.trailing_comments(span.end);
+        }
+        self.comments_before(end);
+    }
+
+    /// starts the line of a statement or comment starting at the offset
+    /// `start` in `source`, after a blank line if the source had one
+    fn start_line(&mut self, start: usize) {
+        if self.fresh {
+            self.fresh = false;
+            if self.depth > 0 {
+                self.newline();
+            }
+            return;
+        }
+
+        let blank_line = self
+            .source
+            .get(self.last_end..start)
+            .is_some_and(|gap| gap.matches('\n').count() > 1);
+        if blank_line {
+            self.out.push('\n');
+        }
+        self.newline();
+    }
+
+    /// writes the comments starting before the offset `end`, each on its own
+    /// line
+    fn comments_before(&mut self, end: usize) {
+        while let Some(comment) = self.comments.get(self.next_comment).copied() {
+            if comment.span.start >= end {
+                return;
+            }
+
+            self.next_comment += 1;
+            self.start_line(comment.span.start);
+            self.out.push_str(comment.literal);
+            self.last_end = comment.span.end;
+        }
+    }
+
+    /// writes the comments left inside the statement ending at the offset
+    /// `end`, and those following it on its line, at the end of its line
+    fn trailing_comments(&mut self, end: usize) {
+        let mut after_line_comment = false;
+
+        while let Some(comment) = self.comments.get(self.next_comment).copied() {
+            let trailing =
+                comment.span.start < end || !self.source[end..comment.span.start].contains('\n');
+            if !trailing {
+                return;
+            }
+
+            self.next_comment += 1;
+            // a line comment runs to the end of the line, the comments
+            // after it need their own line
+            if after_line_comment {
+                self.newline();
+            } else {
+                self.out.push(' ');
+            }
+            self.out.push_str(comment.literal);
+            after_line_comment = comment.literal.starts_with("//");
+            self.last_end = self.last_end.max(comment.span.end);
+        }
+    }
+
+    /// returns whether comments start before the offset `end`
+    fn comments_until(&self, end: usize) -> bool {
+        self.comments
+            .get(self.next_comment)
+            .is_some_and(|comment| comment.span.start < end)
+    }
+
+    /// writes `statement`, `last` tells whether it's the last statement of its
+    /// block, the only one an expression statement may leave its `;` off
+    fn statement(&mut self, statement: &Statement, last: bool) {
+        match statement {
+            Statement::Let { name, value, .. } => {
+                self.out.push_str("let ");
+                self.out.push_str(name);
+                self.out.push_str(" = ");
+                self.expression(value);
+                self.out.push(';');
+            }
+            Statement::Return { value, .. } => {
+                self.out.push_str("return ");
+                self.expression(value);
+                self.out.push(';');
+            }
+            Statement::Assign {
+                name,
+                operator,
+                value,
+                ..
+            } => {
+                self.out.push_str(name);
+                self.out.push(' ');
+                if let Some(operator) = operator {
+                    self.out.push_str(&operator.to_string());
+                }
+                self.out.push_str("= ");
+                self.expression(value);
+                self.out.push(';');
+            }
+            Statement::While {
+                condition, body, ..
+            } => {
+                self.out.push_str("while (");
+                self.expression(condition);
+                self.out.push_str(") ");
+                self.block(body);
+            }
+            Statement::For {
+                variable,
+                iterable,
+                body,
+                ..
+            } => {
+                self.out.push_str("for (");
+                self.out.push_str(variable);
+                self.out.push_str(" in ");
+                self.expression(iterable);
+                self.out.push_str(") ");
+                self.block(body);
+            }
+            Statement::Break { .. } => self.out.push_str("break;"),
+            Statement::Continue { .. } => self.out.push_str("continue;"),
+            // without its `;`, the next statement could carry on the
+            // expression, ie: `x` followed by `-1`
+            Statement::Expression(expression) => {
+                // a statement starting with `{}` parses as an empty block
+                let grouped = matches!(
+                    leftmost(expression),
+                    Expression::HashLiteral { pairs, .. } if pairs.is_empty()
+                );
+                self.grouped(expression, grouped);
+                if !last {
+                    self.out.push(';');
+                }
+            }
+            Statement::Block(block) => self.block(block),
+        }
+    }
+
+    fn block(&mut self, block: &BlockStatement) {
+        if block.statements.is_empty() && !self.comments_until(block.span.end) {
+            self.out.push_str("{}");
+            return;
+        }
+
+        self.out.push('{');
+        self.depth += 1;
+        self.fresh = true;
+        self.statements(&block.statements, block.span.end);
+        self.depth -= 1;
+        self.newline();
+        self.out.push('}');
+    }
+
+    fn expression(&mut self, expression: &Expression) {
+        match expression {
+            Expression::Identifier { name, .. } => self.out.push_str(name),
+            // numbers are written as they were, keeping their radix and `_`
+            // separators
+            Expression::IntegerLiteral { span, .. } | Expression::FloatLiteral { span, .. } => {
+                match self.source.get(span.start..span.end) {
+                    Some(literal) => self.out.push_str(literal),
+                    None => self.out.push_str(&expression.to_string()),
+                }
+            }
+            Expression::Boolean { value, .. } => self.out.push_str(&value.to_string()),
+            Expression::StringLiteral { .. } => self.out.push_str(&expression.to_string()),
+            Expression::Prefix {
+                operator, right, ..
+            } => {
+                self.out.push_str(&operator.to_string());
+                self.grouped(right, precedence(right) < Precedence::Prefix);
+            }
+            // an operand binding as loosely as the operator needs parentheses
+            // on the right of a left associative operator, and on the left of
+            // `**`. a prefix operand never does on the right, it already
+            // extends as far as it can.
+            Expression::Infix {
+                left,
+                operator,
+                right,
+                ..
+            } => {
+                let binding = Precedence::of(*operator);
+                let right_binding = Precedence::right_of(*operator);
+                self.grouped(
+                    left,
+                    precedence(left) < binding
+                        || precedence(left) == binding && right_binding < binding,
+                );
+                self.out.push_str(&format!(" {} ", operator));
+                self.grouped(
+                    right,
+                    precedence(right) <= right_binding
+                        && !matches!(**right, Expression::Prefix { .. }),
+                );
+            }
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                self.out.push_str("if (");
+                self.expression(condition);
+                self.out.push_str(") ");
+                self.block(consequence);
+                if let Some(alternative) = alternative {
+                    self.out.push_str(" else ");
+                    self.block(alternative);
+                }
+            }
+            Expression::ArrayLiteral { elements, .. } => {
+                self.out.push('[');
+                self.list(elements);
+                self.out.push(']');
+            }
+            Expression::Index { left, index, .. } => {
+                self.grouped(left, precedence(left) < Precedence::Call);
+                self.out.push('[');
+                self.expression(index);
+                self.out.push(']');
+            }
+            Expression::HashLiteral { pairs, .. } => {
+                self.out.push('{');
+                for (i, (key, value)) in pairs.iter().enumerate() {
+                    if i > 0 {
+                        self.out.push_str(", ");
+                    }
+                    self.expression(key);
+                    self.out.push_str(": ");
+                    self.expression(value);
+                }
+                self.out.push('}');
+            }
+            Expression::FunctionLiteral {
+                parameters, body, ..
+            } => {
+                self.out
+                    .push_str(&format!("fn({}) ", parameters.join(", ")));
+                self.block(body);
+            }
+            Expression::MacroLiteral {
+                parameters, body, ..
+            } => {
+                self.out
+                    .push_str(&format!("macro({}) ", parameters.join(", ")));
+                self.block(body);
+            }
+            Expression::Call {
+                function,
+                arguments,
+                ..
+            } => {
+                self.grouped(function, precedence(function) < Precedence::Call);
+                self.call_arguments(arguments);
+            }
+        }
+    }
+
+    /// writes `arguments` on the line of the call when its first line fits in
+    /// `MAX_WIDTH`, one per line otherwise
+    fn call_arguments(&mut self, arguments: &[Expression]) {
+        let line_start = self.out.rfind('\n').map_or(0, |at| at + 1);
+        let start = self.out.len();
+
+        self.out.push('(');
+        self.list(arguments);
+        self.out.push(')');
+
+        let line_end = self.out[start..]
+            .find('\n')
+            .map_or(self.out.len(), |at| start + at);
+        if arguments.is_empty() || self.out[line_start..line_end].chars().count() <= MAX_WIDTH {
+            return;
+        }
+
+        self.out.truncate(start);
+        self.out.push('(');
+        self.depth += 1;
+        for (i, argument) in arguments.iter().enumerate() {
+            self.newline();
+            self.expression(argument);
+            if i + 1 < arguments.len() {
+                self.out.push(',');
+            }
+        }
+        self.depth -= 1;
+        self.newline();
+        self.out.push(')');
+    }
+
+    fn list(&mut self, expressions: &[Expression]) {
+        for (i, expression) in expressions.iter().enumerate() {
+            if i > 0 {
+                self.out.push_str(", ");
+            }
+            self.expression(expression);
+        }
+    }
+
+    /// writes `expression`, in parentheses when `grouped`
+    fn grouped(&mut self, expression: &Expression, grouped: bool) {
+        if grouped {
+            self.out.push('(');
+        }
+        self.expression(expression);
+        if grouped {
+            self.out.push(')');
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.push('\n');
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+}
+
+/// returns how tightly `expression` binds to the operators around it.
+/// expressions that don't end with an operand never need parentheses.
+fn precedence(expression: &Expression) -> Precedence {
+    match expression {
+        Expression::Prefix { .. } => Precedence::Prefix,
+        Expression::Infix { operator, .. } => Precedence::of(*operator),
+        Expression::Call { .. } => Precedence::Call,
+        _ => Precedence::Index,
+    }
+}
+
+/// returns the expression `expression`'s source starts with
+fn leftmost(expression: &Expression) -> &Expression {
+    match expression {
+        Expression::Infix { left, .. }
+        | Expression::Index { left, .. }
+        | Expression::Call { function: left, .. } => leftmost(left),
+        _ => expression,
+    }
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// returns the debug form of the AST `source` parses to, without spans
+    fn shape(source: &str) -> String {
+        let mut parser = Parser::new(Lexer::new(source));
+        let mut debug = format!("{:?}", parser.parse_program());
+        assert_eq!(parser.errors(), &[], "source should parse: {}", source);
+
+        while let Some(start) = debug.find("span: Span {") {
+            let end = start + debug[start..].find('}').unwrap() + 1;
+            debug.replace_range(start..end, "");
+        }
+        debug
+    }
+
+    fn assert_formats(tests: &[(&str, &str)]) {
+        for (source, expected) in tests.iter() {
+            let formatted = format(source).unwrap();
+            assert_eq!(formatted, *expected, "source: {}", source);
+            assert_eq!(shape(&formatted), shape(source), "source: {}", source);
+            assert_eq!(format(&formatted).unwrap(), formatted, "not idempotent");
+        }
+    }
+
+    #[test]
+    fn statements_and_blocks() {
+        assert_formats(&[
+            ("", ""),
+            ("let   x=5 ;return x", "let x = 5;\nreturn x;\n"),
+            ("x\n-1", "x - 1\n"),
+            ("x;-1", "x;\n-1\n"),
+            (
+                "let add=fn(a,b){let c=a+b;c};",
+                "let add = fn(a, b) {\n    let c = a + b;\n    c\n};\n",
+            ),
+            (
+                "if(a<b){a}else{if(b){{}}}",
+                "if (a < b) {\n    a\n} else {\n    if (b) {\n        {}\n    }\n}\n",
+            ),
+            ("fn(){}", "fn() {}\n"),
+            (
+                "let m=macro(a){quote(unquote(a)*2)};",
+                "let m = macro(a) {\n    quote(unquote(a) * 2)\n};\n",
+            ),
+            (
+                "let a = 1;\n\n\n\nlet b = 2;\nb",
+                "let a = 1;\n\nlet b = 2;\nb\n",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn assignments_and_loops() {
+        assert_formats(&[
+            ("x=1;x+=2*y", "x = 1;\nx += 2 * y;\n"),
+            (
+                "while(i<10){i%=3;if(i){continue}}",
+                "while (i < 10) {\n    i %= 3;\n    if (i) {\n        continue;\n    }\n}\n",
+            ),
+            (
+                "for(x in [1,2]){break;}x",
+                "for (x in [1, 2]) {\n    break;\n}\nx\n",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn operators_and_grouping() {
+        assert_formats(&[
+            ("1+2*3", "1 + 2 * 3\n"),
+            ("(1+2)*3", "(1 + 2) * 3\n"),
+            ("1-(2-3)", "1 - (2 - 3)\n"),
+            ("(1-2)-3", "1 - 2 - 3\n"),
+            ("a==b!=c<d>e/f", "a == b != c < d > e / f\n"),
+            ("-(a+b)", "-(a + b)\n"),
+            ("--a", "--a\n"),
+            ("!-f(x)[0]", "!-f(x)[0]\n"),
+            ("(-a)[0]", "(-a)[0]\n"),
+            ("(a+b)(c)", "(a + b)(c)\n"),
+            ("((f))(1)", "f(1)\n"),
+            ("fn(x){x}(1)", "fn(x) {\n    x\n}(1)\n"),
+            ("a||b&&c", "a || b && c\n"),
+            ("(a||b)&&c", "(a || b) && c\n"),
+            ("a<=b==(c>=d)%e", "a <= b == (c >= d) % e\n"),
+            ("a**(b**c)", "a ** b ** c\n"),
+            ("(a**b)**c", "(a ** b) ** c\n"),
+            ("-(a**b)", "-a ** b\n"),
+            ("(-a)**b", "(-a) ** b\n"),
+            ("a**(-b)*c", "a ** -b * c\n"),
+            ("a**(-b**c)", "a ** -b ** c\n"),
+        ]);
+    }
+
+    #[test]
+    fn literals() {
+        assert_formats(&[
+            ("\"a\\\"b\\\\c\\td\\n\"", "\"a\\\"b\\\\c\\td\\n\"\n"),
+            ("[1,[2,3],[]]", "[1, [2, 3], []]\n"),
+            ("0xFF+1_000*2.5e-3", "0xFF + 1_000 * 2.5e-3\n"),
+            (
+                "{\"a\":1,true:[2]}[\"a\"]",
+                "{\"a\": 1, true: [2]}[\"a\"]\n",
+            ),
+            ("let h={};h", "let h = {};\nh\n"),
+            ("({})", "({})\n"),
+            ("({}[1] + 1)", "({}[1] + 1)\n"),
+        ]);
+    }
+
+    #[test]
+    fn wraps_long_calls() {
+        assert_formats(&[
+            (
+                "let result = compute(firstLongArgument, secondLongArgument, thirdLongArgument, 4);",
+                "let result = compute(\n    firstLongArgument,\n    secondLongArgument,\n    thirdLongArgument,\n    4\n);\n",
+            ),
+            (
+                "let a = fn() { outer(inner(firstLongArgument, secondLongArgument), thirdArgumentIsLongerStill) }",
+                "let a = fn() {\n    outer(\n        inner(firstLongArgument, secondLongArgument),\n        thirdArgumentIsLongerStill\n    )\n};\n",
+            ),
+            (
+                "map(values, fn(value) { value * 2 })",
+                "map(values, fn(value) {\n    value * 2\n})\n",
+            ),
+        ]);
+    }
+
+    #[test]
+    fn comments() {
+        assert_formats(&[
+            ("// only a comment", "// only a comment\n"),
+            (
+                "// leading\nlet a=1; // trailing\n\n\n/* block */ a",
+                "// leading\nlet a = 1; // trailing\n\n/* block */\na\n",
+            ),
+            (
+                "let f = fn() {\n// todo\n};",
+                "let f = fn() {\n    // todo\n};\n",
+            ),
+            (
+                "if (x) {\n  a // a\n\n  // b\n  b\n  // end\n} else { /* c */ }",
+                "if (x) {\n    a; // a\n\n    // b\n    b\n    // end\n} else {\n    /* c */\n}\n",
+            ),
+            (
+                "let a = f(1, // one\n  2 /* two */);\na",
+                "let a = f(1, 2); // one\n/* two */\na\n",
+            ),
+            ("1 /* a */ /* b */", "1 /* a */ /* b */\n"),
+        ]);
+    }
+
+    #[test]
+    fn reports_parse_errors() {
+        let errors = format("let = 1").unwrap_err();
+
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "1:5: expected next token to be IDENT, got ASSIGN instead"
+        );
+    }
+}
@@ -1,5 +1,59 @@
 use crate::tokens::tokens::*;
-use ascii::*;
+use std::fmt;
+use std::iter::{FusedIterator, Peekable};
+use std::str::CharIndices;
+
+/// represents the kinds of malformed input the lexer can report
+#[derive(Debug, PartialEq, Clone)]
+pub enum LexErrorKind {
+    /// a string literal is missing its closing `"`
+    UnterminatedString,
+    /// a `\` in a string literal isn't followed by a supported escape, holds
+    /// the offending sequence
+    InvalidEscape(String),
+    /// a `/*` comment is missing its closing `*/`
+    UnterminatedComment,
+    /// a number literal has no digits, or runs into characters it can't hold,
+    /// holds the literal
+    MalformedNumber(String),
+}
+
+impl fmt::Display for LexErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LexErrorKind::UnterminatedString => write!(f, "unterminated string literal"),
+            LexErrorKind::InvalidEscape(sequence) => {
+                write!(f, "invalid escape sequence {} in string literal", sequence)
+            }
+            LexErrorKind::UnterminatedComment => write!(f, "unterminated block comment"),
+            LexErrorKind::MalformedNumber(literal) => {
+                write!(f, "malformed number literal {}", literal)
+            }
+        }
+    }
+}
+
+/// represents malformed input found by the lexer
+///
+/// # Parameters
+///
+/// * `kind` - what went wrong
+///
+/// * `span` - the location of the malformed input
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct LexError {
+    pub kind: LexErrorKind,
+    pub span: Span,
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+impl std::error::Error for LexError {}
 
 /// Represents a Lexer for monkey lang
 ///
@@ -7,180 +61,618 @@ use ascii::*;
 ///
 /// * `input` - value to lex
 ///
-/// * `position` - value that represents the current position of the
-/// lexer
+/// * `chars` - the characters of `input` not read yet, with their byte
+///   offsets
 ///
-/// * `read_position` - value that represents the position the lexer is
-/// currently reading (typically one ahead of the current position value)
+/// * `position` - byte offset of the character the lexer is currently
+///   matching
 ///
-/// * `ch` - vector that represents the characters the lexer is
-/// currently matching / working with
+/// * `ch` - the character the lexer is currently matching / working with.
+///   `'\0'` once the end of the input is reached.
+///
+/// * `line` - the 1-based line `ch` is on
+///
+/// * `column` - the 1-based column of `ch` on its line, counted in characters
+///
+/// * `errors` - every `LexError` found so far
+///
+/// * `comments` - whether comments are returned as `COMMENT` tokens rather
+///   than skipped
 ///
 /// # Remarks
 ///
 /// * instanciate this with a mutable variable. the lexer needs to be mutable to
-/// adjust the positions and ch values.
+///   adjust the positions and ch values.
+///
+/// * input is read as UTF-8 in a single pass. characters that can't start a
+///   token are returned as `ILLEGAL` tokens rather than stopping the lexer.
 ///
-pub struct Lexer {
-    input: String,
+/// * tokens borrow their literals from `input`, so the input has to outlive
+///   the tokens read from it.
+///
+#[derive(Clone)]
+pub struct Lexer<'a> {
+    input: &'a str,
+    chars: Peekable<CharIndices<'a>>,
     position: usize,
-    read_position: usize,
-    ch: Vec<AsciiChar>,
+    ch: char,
+    line: usize,
+    column: usize,
+    errors: Vec<LexError>,
+    comments: bool,
 }
 
-impl Lexer {
+impl<'a> Lexer<'a> {
     /// returns a new Lexer construct
     ///
     /// # Arguments
     ///
-    /// * `input` - a `String` value to lex
+    /// * `input` - a `&str` value to lex
     ///
-    pub fn new(input: String) -> Lexer {
+    pub fn new(input: &'a str) -> Lexer<'a> {
         let mut l = Lexer {
-            input: input,
+            input,
+            chars: input.char_indices().peekable(),
             position: 0,
-            read_position: 0,
-            ch: vec![AsciiChar::Null],
+            ch: '\0',
+            line: 1,
+            column: 0,
+            errors: vec![],
+            comments: false,
         };
 
         l.read_char();
         l
     }
+
+    /// returns the lexer set to return `//` and `/* */` comments as `COMMENT`
+    /// tokens, for tools that keep them such as formatters
+    pub fn with_comments(self) -> Lexer<'a> {
+        Lexer {
+            comments: true,
+            ..self
+        }
+    }
 }
 
-impl Lexer {
-    /// returns next position character in Lexer input as an AsciiChar, without
-    /// modifying the Lexer or advancing the position/read_position parameters
+/// returns whether `ch` can start an identifier. with the
+/// `unicode-identifiers` feature this follows `XID_Start`, otherwise only
+/// ASCII letters are accepted.
+fn is_identifier_start(ch: char) -> bool {
+    #[cfg(feature = "unicode-identifiers")]
+    {
+        unicode_xid::UnicodeXID::is_xid_start(ch)
+    }
+    #[cfg(not(feature = "unicode-identifiers"))]
+    {
+        ch.is_ascii_alphabetic()
+    }
+}
+
+/// returns whether `ch` can continue an identifier. with the
+/// `unicode-identifiers` feature this follows `XID_Continue`, otherwise only
+/// ASCII letters and digits are accepted.
+fn is_identifier_continue(ch: char) -> bool {
+    #[cfg(feature = "unicode-identifiers")]
+    {
+        unicode_xid::UnicodeXID::is_xid_continue(ch)
+    }
+    #[cfg(not(feature = "unicode-identifiers"))]
+    {
+        ch.is_ascii_alphanumeric()
+    }
+}
+
+/// decodes the escape sequence at the start of `rest`, the text following a
+/// `\` in a string literal. returns the escaped character and the length of
+/// the sequence in bytes, or the length of the invalid sequence.
+///
+/// supported escapes are `\n`, `\t`, `\"`, `\\` and `\u{...}` with 1 to 6 hex
+/// digits naming a unicode scalar value.
+fn decode_escape(rest: &str) -> Result<(char, usize), usize> {
+    match rest.chars().next() {
+        Some('n') => Ok(('\n', 1)),
+        Some('t') => Ok(('\t', 1)),
+        Some('"') => Ok(('"', 1)),
+        Some('\\') => Ok(('\\', 1)),
+        Some('u') => {
+            if !rest[1..].starts_with('{') {
+                return Err(1);
+            }
+            let digits = rest[2..]
+                .chars()
+                .take_while(|ch| ch.is_ascii_hexdigit())
+                .count();
+            if !rest[2 + digits..].starts_with('}') || digits == 0 || digits > 6 {
+                return Err(2 + digits);
+            }
+            u32::from_str_radix(&rest[2..2 + digits], 16)
+                .ok()
+                .and_then(std::char::from_u32)
+                .map(|ch| (ch, 3 + digits))
+                .ok_or(3 + digits)
+        }
+        Some(ch) => Err(ch.len_utf8()),
+        None => Err(0),
+    }
+}
+
+/// returns the value of a `STRING` token's literal, with the quotes removed
+/// and escape sequences decoded. invalid escapes are kept as written, the
+/// lexer reports them when reading the token.
+///
+/// # Arguments
+///
+/// * `literal` - the literal of a `STRING` token, opening quote included
+///
+pub fn unescape(literal: &str) -> String {
+    let body = literal.strip_prefix('"').unwrap_or(literal);
+    let mut value = String::with_capacity(body.len());
+    let mut chars = body.char_indices();
+
+    while let Some((i, ch)) = chars.next() {
+        match ch {
+            '"' => break,
+            '\\' => {
+                let len = match decode_escape(&body[i + 1..]) {
+                    Ok((escaped, len)) => {
+                        value.push(escaped);
+                        len
+                    }
+                    Err(len) => {
+                        value.push_str(&body[i..i + 1 + len]);
+                        len
+                    }
+                };
+                // skip the rest of the sequence, which is ascii apart from an
+                // invalid single character escape
+                let end = i + 1 + len;
+                while chars.as_str().len() > body.len() - end {
+                    chars.next();
+                }
+            }
+            ch => value.push(ch),
+        }
+    }
+
+    value
+}
+
+impl<'a> Lexer<'a> {
+    /// returns the errors found in the input read so far, in source order
+    pub fn errors(&self) -> &[LexError] {
+        &self.errors
+    }
+
+    /// returns the errors found in the input read so far, leaving none behind
+    pub fn take_errors(&mut self) -> Vec<LexError> {
+        std::mem::take(&mut self.errors)
+    }
+
+    /// returns next position character in Lexer input, without modifying the
+    /// Lexer or advancing the position parameter
     ///
     /// # Arguments
     ///
-    /// * `&self` - a reference to the Lexer construct being used
+    /// * `&mut self` - a mutable reference to the Lexer construct being used
     ///
-    fn peek_char(&self) -> AsciiChar {
-        if self.read_position >= self.input.len() {
-            AsciiChar::Null
-        } else {
-            let ch = self.input.chars().nth(self.read_position).unwrap();
-            AsciiChar::new(ch)
-        }
+    fn peek_char(&mut self) -> char {
+        self.chars.peek().map_or('\0', |&(_, ch)| ch)
     }
-    /// advances `position` and `next_position` on the lexer
+
+    /// advances `position` on the lexer by one character, moving `line` and
+    /// `column` along with it
     ///
     /// # Arguments
     ///
     /// * `&mut self` - a mutable reference to the Lexer construct being used
     fn read_char(&mut self) {
-        if self.read_position >= self.input.len() {
-            self.ch = vec![AsciiChar::Null];
+        if self.ch == '\n' {
+            self.line += 1;
+            self.column = 1;
         } else {
-            let ch = self.input.chars().nth(self.read_position).unwrap();
-            self.ch = vec![AsciiChar::new(ch)];
+            self.column += 1;
+        }
+
+        match self.chars.next() {
+            Some((position, ch)) => {
+                self.position = position;
+                self.ch = ch;
+            }
+            None => {
+                self.position = self.input.len();
+                self.ch = '\0';
+            }
         }
+    }
 
-        self.position = self.read_position;
-        self.read_position += 1;
+    /// returns whether the lexer has read past the last character of input
+    fn at_end(&self) -> bool {
+        self.position >= self.input.len()
     }
 
-    /// returns a vector of AsciiChar items that represent the entire literal
-    /// the lexer is attempting to match/parse against. used to read IDENT and
-    /// keyword vals from the input String.
+    /// returns the entire literal the lexer is attempting to match/parse
+    /// against, borrowed from the input. used to read IDENT and keyword vals.
     ///
     /// # Arguments
     ///
     /// * `&mut self` - a mutable reference to the Lexer construct being used
     ///
-    fn read_identifier(&mut self) -> Vec<AsciiChar> {
-        let mut literal: Vec<AsciiChar> = vec![];
-        let mut c: AsciiChar = self.ch[0];
+    fn read_identifier(&mut self) -> &'a str {
+        let start = self.position;
 
-        while c.is_alphabetic() || c.is_ascii_digit() {
-            literal.push(self.ch[0]);
+        while is_identifier_continue(self.ch) {
             self.read_char();
-            c = self.ch[0];
         }
 
-        literal
+        &self.input[start..self.position]
     }
 
-    /// returns `(TokenType Vec<AsciiChar>)` that references a match from the
-    /// lexer.
+    /// reads a string literal up to and including its closing quote, expects
+    /// `ch` to be the opening quote. escape sequences are validated but not
+    /// decoded, see `unescape()`.
     ///
     /// # Arguments
     ///
     /// * `&mut self` - a mutable reference to the Lexer construct being used
     ///
-    fn match_token_type(&mut self) -> (TokenType, Vec<AsciiChar>) {
-        let mut default: bool = false;
-
-        // matches the current `literal` vector as a slice with conditions
-        // nested in for ==, != and identifiers/keywords
-        let result = match self.ch.as_slice() {
-            [AsciiChar::Equal] => {
-                if [self.peek_char()] == [AsciiChar::Equal] {
+    fn read_string(&mut self) {
+        let (start, line, column) = (self.position, self.line, self.column);
+        self.read_char();
+
+        loop {
+            match self.ch {
+                '"' => {
                     self.read_char();
-                    (TokenType::EQ, vec![AsciiChar::Equal, AsciiChar::Equal])
+                    return;
+                }
+                '\\' => {
+                    let escape = (self.position, self.line, self.column);
+                    let result = decode_escape(&self.input[escape.0 + 1..]);
+                    let end = escape.0 + 1 + result.map_or_else(|len| len, |(_, len)| len);
+
+                    while self.position < end {
+                        self.read_char();
+                    }
+                    if result.is_err() {
+                        self.errors.push(LexError {
+                            kind: LexErrorKind::InvalidEscape(
+                                self.input[escape.0..end].to_string(),
+                            ),
+                            span: Span::new(escape.0, end, escape.1, escape.2),
+                        });
+                    }
+                }
+                '\0' if self.at_end() => {
+                    self.errors.push(LexError {
+                        kind: LexErrorKind::UnterminatedString,
+                        span: Span::new(start, self.position, line, column),
+                    });
+                    return;
+                }
+                _ => self.read_char(),
+            }
+        }
+    }
+
+    /// reads a number literal, expects `ch` to be its first digit. returns
+    /// `FLOAT` for literals with a fraction or an exponent, `INT` otherwise.
+    ///
+    /// * `0x`, `0o` and `0b` prefix hexadecimal, octal and binary integers
+    ///
+    /// * `_` can separate digits, ie: `1_000_000`
+    ///
+    /// * decimal literals can have a fraction and an exponent, ie: `3.14` or
+    ///   `1e-9`
+    ///
+    /// literals without digits, or running into letters or digits they can't
+    /// hold such as `12abc` or `0b102`, are read whole and reported as
+    /// malformed.
+    ///
+    /// # Arguments
+    ///
+    /// * `&mut self` - a mutable reference to the Lexer construct being used
+    ///
+    fn read_number(&mut self) -> TokenType {
+        let (start, line, column) = (self.position, self.line, self.column);
+        let mut token_type = TokenType::INT;
+
+        let radix = match (self.ch, self.peek_char()) {
+            ('0', 'x') | ('0', 'X') => 16,
+            ('0', 'o') | ('0', 'O') => 8,
+            ('0', 'b') | ('0', 'B') => 2,
+            _ => 10,
+        };
+        if radix != 10 {
+            self.read_char();
+            self.read_char();
+        }
+        let mut valid = self.read_digits(radix);
+
+        if radix == 10 {
+            if self.ch == '.' && self.peek_char().is_ascii_digit() {
+                token_type = TokenType::FLOAT;
+                self.read_char();
+                self.read_digits(10);
+            }
+            if self.ch == 'e' || self.ch == 'E' {
+                token_type = TokenType::FLOAT;
+                self.read_char();
+                if self.ch == '+' || self.ch == '-' {
+                    self.read_char();
+                }
+                valid &= self.read_digits(10);
+            }
+        }
+
+        if is_identifier_continue(self.ch) || self.ch.is_ascii_digit() {
+            valid = false;
+            while is_identifier_continue(self.ch) || self.ch.is_ascii_digit() {
+                self.read_char();
+            }
+        }
+        if !valid {
+            self.errors.push(LexError {
+                kind: LexErrorKind::MalformedNumber(self.input[start..self.position].to_string()),
+                span: Span::new(start, self.position, line, column),
+            });
+        }
+
+        token_type
+    }
+
+    /// reads digits of the given radix and `_` separators, returns whether at
+    /// least one digit was read
+    fn read_digits(&mut self, radix: u32) -> bool {
+        let mut digits = false;
+        while self.ch.is_digit(radix) || self.ch == '_' {
+            digits |= self.ch != '_';
+            self.read_char();
+        }
+        digits
+    }
+
+    /// reads a `//` comment up to the end of its line, expects `ch` to be the
+    /// first `/`
+    fn read_line_comment(&mut self) {
+        while self.ch != '\n' && !self.at_end() {
+            self.read_char();
+        }
+    }
+
+    /// reads a `/* */` comment up to and including its closing `*/`, expects
+    /// `ch` to be the opening `/`. comments nest, so every `/*` inside the
+    /// comment needs its own `*/`.
+    ///
+    /// # Arguments
+    ///
+    /// * `&mut self` - a mutable reference to the Lexer construct being used
+    ///
+    fn read_block_comment(&mut self) {
+        let (start, line, column) = (self.position, self.line, self.column);
+        let mut depth = 0;
+
+        loop {
+            match (self.ch, self.peek_char()) {
+                ('/', '*') => depth += 1,
+                ('*', '/') => depth -= 1,
+                ('\0', _) if self.at_end() => {
+                    self.errors.push(LexError {
+                        kind: LexErrorKind::UnterminatedComment,
+                        span: Span::new(start, self.position, line, column),
+                    });
+                    return;
+                }
+                _ => {
+                    self.read_char();
+                    continue;
+                }
+            }
+
+            self.read_char();
+            self.read_char();
+            if depth == 0 {
+                return;
+            }
+        }
+    }
+
+    /// returns the `TokenType` of the token starting at `ch`, leaving the lexer
+    /// on the character following the token.
+    ///
+    /// # Arguments
+    ///
+    /// * `&mut self` - a mutable reference to the Lexer construct being used
+    ///
+    fn match_token_type(&mut self) -> TokenType {
+        // matches the current character with conditions nested in for the two
+        // character operators and identifiers/keywords
+        let token_type = match self.ch {
+            '=' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    TokenType::EQ
                 } else {
-                    (TokenType::ASSIGN, vec![AsciiChar::Equal])
+                    TokenType::ASSIGN
                 }
             }
-            [AsciiChar::Plus] => (TokenType::PLUS, vec![AsciiChar::Plus]),
-            [AsciiChar::Minus] => (TokenType::MINUS, vec![AsciiChar::Minus]),
-            [AsciiChar::Exclamation] => {
-                if [self.peek_char()] == [AsciiChar::Equal] {
+            '+' => {
+                if self.peek_char() == '=' {
                     self.read_char();
-                    (
-                        TokenType::NotEq,
-                        vec![AsciiChar::Exclamation, AsciiChar::Equal],
-                    )
+                    TokenType::PlusAssign
                 } else {
-                    (TokenType::BANG, vec![AsciiChar::Exclamation])
+                    TokenType::PLUS
                 }
             }
-            [AsciiChar::Slash] => (TokenType::SLASH, vec![AsciiChar::Slash]),
-            [AsciiChar::Asterisk] => (TokenType::ASTERISK, vec![AsciiChar::Asterisk]),
-            [AsciiChar::LessThan] => (TokenType::LT, vec![AsciiChar::LessThan]),
-            [AsciiChar::GreaterThan] => (TokenType::GT, vec![AsciiChar::GreaterThan]),
-            [AsciiChar::ParenOpen] => (TokenType::LPAREN, vec![AsciiChar::ParenOpen]),
-            [AsciiChar::ParenClose] => (TokenType::RPAREN, vec![AsciiChar::ParenClose]),
-            [AsciiChar::CurlyBraceOpen] => (TokenType::LBRACE, vec![AsciiChar::CurlyBraceOpen]),
-            [AsciiChar::CurlyBraceClose] => (TokenType::RBRACE, vec![AsciiChar::CurlyBraceClose]),
-            [AsciiChar::Comma] => (TokenType::COMMA, vec![AsciiChar::Comma]),
-            [AsciiChar::Semicolon] => (TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
-            [AsciiChar::Null] => (TokenType::EOF, vec![AsciiChar::Null]),
-            _ => {
-                default = true;
-                if self.ch[0].is_ascii_alphanumeric() {
-                    let literal = self.read_identifier();
-                    (Token::look_up_ident(literal.clone()), literal)
+            '-' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    TokenType::MinusAssign
                 } else {
+                    TokenType::MINUS
+                }
+            }
+            '!' => {
+                if self.peek_char() == '=' {
                     self.read_char();
-                    (TokenType::ILLEGAL, vec![AsciiChar::Null])
+                    TokenType::NotEq
+                } else {
+                    TokenType::BANG
                 }
             }
+            '/' => match self.peek_char() {
+                '/' => {
+                    self.read_line_comment();
+                    return TokenType::COMMENT;
+                }
+                '*' => {
+                    self.read_block_comment();
+                    return TokenType::COMMENT;
+                }
+                '=' => {
+                    self.read_char();
+                    TokenType::SlashAssign
+                }
+                _ => TokenType::SLASH,
+            },
+            '*' => match self.peek_char() {
+                '*' => {
+                    self.read_char();
+                    TokenType::POWER
+                }
+                '=' => {
+                    self.read_char();
+                    TokenType::AsteriskAssign
+                }
+                _ => TokenType::ASTERISK,
+            },
+            '%' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    TokenType::PercentAssign
+                } else {
+                    TokenType::PERCENT
+                }
+            }
+            '<' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    TokenType::LtEq
+                } else {
+                    TokenType::LT
+                }
+            }
+            '>' => {
+                if self.peek_char() == '=' {
+                    self.read_char();
+                    TokenType::GtEq
+                } else {
+                    TokenType::GT
+                }
+            }
+            // a single `&` or `|` isn't an operator
+            '&' => {
+                if self.peek_char() == '&' {
+                    self.read_char();
+                    TokenType::AND
+                } else {
+                    TokenType::ILLEGAL
+                }
+            }
+            '|' => {
+                if self.peek_char() == '|' {
+                    self.read_char();
+                    TokenType::OR
+                } else {
+                    TokenType::ILLEGAL
+                }
+            }
+            '(' => TokenType::LPAREN,
+            ')' => TokenType::RPAREN,
+            '{' => TokenType::LBRACE,
+            '}' => TokenType::RBRACE,
+            '[' => TokenType::LBRACKET,
+            ']' => TokenType::RBRACKET,
+            ',' => TokenType::COMMA,
+            ';' => TokenType::SEMICOLON,
+            ':' => TokenType::COLON,
+            '"' => {
+                self.read_string();
+                return TokenType::STRING;
+            }
+            '\0' if self.at_end() => return TokenType::EOF,
+            // numbers and identifiers/keywords already end on the character
+            // following them, so they return without advancing again
+            ch if ch.is_ascii_digit() => return self.read_number(),
+            ch if is_identifier_start(ch) => {
+                return Token::look_up_ident(self.read_identifier());
+            }
+            _ => TokenType::ILLEGAL,
         };
 
-        if !default {
-            self.read_char();
-        }
-
-        result
+        self.read_char();
+        token_type
     }
 
-    /// Advances past whitespace and returns the next `Token`, after receiving
-    /// a match from `match_token_type()`.
+    /// Advances past whitespace, and comments unless the lexer was built
+    /// `with_comments()`, and returns the next `Token`, after receiving a match
+    /// from `match_token_type()`. the token's literal is the slice of input it
+    /// was read from.
     ///
     /// # Arguments
     ///
     /// * `&mut self` - a mutable reference to the Lexer construct being used
-    pub fn next_token(&mut self) -> Token {
-        while self.ch[0].is_ascii_whitespace() {
-            self.read_char();
+    pub fn next_token(&mut self) -> Token<'a> {
+        loop {
+            while self.ch.is_whitespace() {
+                self.read_char();
+            }
+            let (start, line, column) = (self.position, self.line, self.column);
+            let token_type = self.match_token_type();
+            if token_type == TokenType::COMMENT && !self.comments {
+                continue;
+            }
+
+            let literal = &self.input[start..self.position];
+            return Token::new(token_type, literal).at(Span::new(
+                start,
+                self.position,
+                line,
+                column,
+            ));
+        }
+    }
+}
+
+impl<'a> Iterator for Lexer<'a> {
+    type Item = Token<'a>;
+
+    /// returns the next token, or `None` once the end of the input is reached.
+    /// unlike `next_token()`, the `EOF` token is never returned.
+    fn next(&mut self) -> Option<Token<'a>> {
+        let token = self.next_token();
+        if token.token_type == TokenType::EOF {
+            None
+        } else {
+            Some(token)
         }
-        let (token_type, literal) = self.match_token_type();
-        Token::new(token_type, literal)
     }
 }
 
+// `next_token()` keeps returning `EOF` once the input is exhausted, so the
+// iterator keeps returning `None`
+impl<'a> FusedIterator for Lexer<'a> {}
+
+/// returns every token of `input` in order, without the trailing `EOF`
+///
+/// # Arguments
+///
+/// * `input` - a `&str` value to lex
+///
+pub fn tokenize(input: &str) -> Vec<Token<'_>> {
+    Lexer::new(input).collect()
+}
+
 //
 // Tests
 //
@@ -191,55 +683,97 @@ mod tests {
 
     #[test]
     fn read_char() {
-        let input: String = String::from(";t; string");
+        let input = ";t; string";
         let mut l: Lexer = Lexer::new(input);
 
         assert_eq!(l.position, 0);
-        assert_eq!(l.read_position, 1);
-        assert_eq!(l.ch, vec![AsciiChar::Semicolon]);
+        assert_eq!(l.ch, ';');
         l.read_char();
         assert_eq!(l.position, 1);
-        assert_eq!(l.read_position, 2);
-        assert_eq!(l.ch, vec![AsciiChar::t]);
+        assert_eq!(l.ch, 't');
         l.read_char();
         assert_eq!(l.position, 2);
-        assert_eq!(l.read_position, 3);
-        assert_eq!(l.ch, vec![AsciiChar::Semicolon]);
+        assert_eq!(l.ch, ';');
     }
 
     #[test]
     fn read_identifier() {
-        let mut l: Lexer = Lexer::new("let five cat".to_string());
+        let mut l: Lexer = Lexer::new("let five cat");
         let literal = l.read_identifier();
-        let ascii_chars = vec![AsciiChar::l, AsciiChar::e, AsciiChar::t];
-        assert_eq!(ascii_chars.len(), literal.len());
-        assert_eq!(ascii_chars.as_slice(), literal.as_slice());
+        assert_eq!(literal, "let");
     }
 
     #[test]
     fn peek_char() {
-        let l: Lexer = Lexer::new("==let five cat".to_string());
-        assert_eq!(l.peek_char(), AsciiChar::Equal)
+        let mut l: Lexer = Lexer::new("==let five cat");
+        assert_eq!(l.peek_char(), '=')
+    }
+
+    #[test]
+    fn read_char_multibyte() {
+        let mut l: Lexer = Lexer::new("é;");
+
+        assert_eq!((l.position, l.ch), (0, 'é'));
+        l.read_char();
+        assert_eq!((l.position, l.ch), (2, ';'));
+        l.read_char();
+        assert_eq!((l.position, l.ch), (3, '\0'));
+    }
+
+    #[test]
+    fn illegal_characters() {
+        let mut l: Lexer = Lexer::new("let caf\u{e9} = 1; 😀 $");
+        let expected = [
+            (TokenType::LET, "let", Span::new(0, 3, 1, 1)),
+            #[cfg(not(feature = "unicode-identifiers"))]
+            (TokenType::IDENT, "caf", Span::new(4, 7, 1, 5)),
+            #[cfg(not(feature = "unicode-identifiers"))]
+            (TokenType::ILLEGAL, "\u{e9}", Span::new(7, 9, 1, 8)),
+            #[cfg(feature = "unicode-identifiers")]
+            (TokenType::IDENT, "caf\u{e9}", Span::new(4, 9, 1, 5)),
+            (TokenType::ASSIGN, "=", Span::new(10, 11, 1, 10)),
+            (TokenType::INT, "1", Span::new(12, 13, 1, 12)),
+            (TokenType::SEMICOLON, ";", Span::new(13, 14, 1, 13)),
+            (TokenType::ILLEGAL, "😀", Span::new(15, 19, 1, 15)),
+            (TokenType::ILLEGAL, "$", Span::new(20, 21, 1, 17)),
+            (TokenType::EOF, "", Span::new(21, 21, 1, 18)),
+        ];
+
+        for (token_type, literal, span) in expected.iter() {
+            let tok = l.next_token();
+            assert_eq!(tok, Token::new(*token_type, literal).at(*span));
+        }
+    }
+
+    #[cfg(feature = "unicode-identifiers")]
+    #[test]
+    fn unicode_identifiers() {
+        let mut l: Lexer = Lexer::new("let 変数 = größe_2;");
+
+        assert_eq!(l.next_token().token_type, TokenType::LET);
+        assert_eq!(l.next_token().literal, "変数");
+        assert_eq!(l.next_token().token_type, TokenType::ASSIGN);
+        assert_eq!(l.next_token().literal, "größe_2");
     }
 
     #[test]
     fn next_token() {
-        let input = String::from("=+(){},;\nlet");
-
-        let tests: [Token; 10] = [
-            Token::new(TokenType::ASSIGN, vec![AsciiChar::Equal]),
-            Token::new(TokenType::PLUS, vec![AsciiChar::Plus]),
-            Token::new(TokenType::LPAREN, vec![AsciiChar::ParenOpen]),
-            Token::new(TokenType::RPAREN, vec![AsciiChar::ParenClose]),
-            Token::new(TokenType::LBRACE, vec![AsciiChar::CurlyBraceOpen]),
-            Token::new(TokenType::RBRACE, vec![AsciiChar::CurlyBraceClose]),
-            Token::new(TokenType::COMMA, vec![AsciiChar::Comma]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
-            Token::new(
-                TokenType::LET,
-                vec![AsciiChar::l, AsciiChar::e, AsciiChar::t],
-            ),
-            Token::new(TokenType::EOF, vec![AsciiChar::Null]),
+        let input = "=+(){}[],;:\nlet";
+
+        let tests: [Token; 13] = [
+            Token::new(TokenType::ASSIGN, "="),
+            Token::new(TokenType::PLUS, "+"),
+            Token::new(TokenType::LPAREN, "("),
+            Token::new(TokenType::RPAREN, ")"),
+            Token::new(TokenType::LBRACE, "{"),
+            Token::new(TokenType::RBRACE, "}"),
+            Token::new(TokenType::LBRACKET, "["),
+            Token::new(TokenType::RBRACKET, "]"),
+            Token::new(TokenType::COMMA, ","),
+            Token::new(TokenType::SEMICOLON, ";"),
+            Token::new(TokenType::COLON, ":"),
+            Token::new(TokenType::LET, "let"),
+            Token::new(TokenType::EOF, ""),
         ];
 
         let mut l: Lexer = Lexer::new(input);
@@ -251,16 +785,296 @@ mod tests {
         }
     }
 
+    #[test]
+    fn operators() {
+        let input = "a <= b >= c && d || e % f ** g * h & i | j";
+        let expected = [
+            TokenType::IDENT,
+            TokenType::LtEq,
+            TokenType::IDENT,
+            TokenType::GtEq,
+            TokenType::IDENT,
+            TokenType::AND,
+            TokenType::IDENT,
+            TokenType::OR,
+            TokenType::IDENT,
+            TokenType::PERCENT,
+            TokenType::IDENT,
+            TokenType::POWER,
+            TokenType::IDENT,
+            TokenType::ASTERISK,
+            TokenType::IDENT,
+            TokenType::ILLEGAL,
+            TokenType::IDENT,
+            TokenType::ILLEGAL,
+            TokenType::IDENT,
+            TokenType::EOF,
+        ];
+
+        let mut l: Lexer = Lexer::new(input);
+        for token_type in expected.iter() {
+            assert_eq!(l.next_token().token_type, *token_type);
+        }
+
+        let literals: Vec<&str> = Lexer::new("<=>=&&||%**").map(|t| t.literal).collect();
+        assert_eq!(literals, ["<=", ">=", "&&", "||", "%", "**"]);
+
+        let types: Vec<TokenType> = Lexer::new("+= -= *= /= %= ==")
+            .map(|t| t.token_type)
+            .collect();
+        assert_eq!(
+            types,
+            [
+                TokenType::PlusAssign,
+                TokenType::MinusAssign,
+                TokenType::AsteriskAssign,
+                TokenType::SlashAssign,
+                TokenType::PercentAssign,
+                TokenType::EQ,
+            ]
+        );
+    }
+
+    #[test]
+    fn token_positions() {
+        let mut l: Lexer = Lexer::new("let x = 5;\n  x == 10\n");
+        let expected = [
+            Span::new(0, 3, 1, 1),
+            Span::new(4, 5, 1, 5),
+            Span::new(6, 7, 1, 7),
+            Span::new(8, 9, 1, 9),
+            Span::new(9, 10, 1, 10),
+            Span::new(13, 14, 2, 3),
+            Span::new(15, 17, 2, 5),
+            Span::new(18, 20, 2, 8),
+            Span::new(21, 21, 3, 1),
+        ];
+
+        for span in expected.iter() {
+            let tok = l.next_token();
+            assert_eq!(tok.span, *span, "{:?}", tok);
+        }
+    }
+
+    #[test]
+    fn string_literals() {
+        let mut l: Lexer = Lexer::new("\"foo bar\" \"\" \"a\\\"b\\n\"");
+
+        assert_eq!(
+            l.next_token(),
+            Token::new(TokenType::STRING, "\"foo bar\"").at(Span::new(0, 9, 1, 1))
+        );
+        assert_eq!(l.next_token().literal, "\"\"");
+        assert_eq!(l.next_token().literal, "\"a\\\"b\\n\"");
+        assert_eq!(l.next_token().token_type, TokenType::EOF);
+        assert!(l.errors().is_empty());
+    }
+
+    #[test]
+    fn unescape_literals() {
+        let tests = [
+            ("\"plain\"", "plain"),
+            ("\"tab\\tnew\\nline\"", "tab\tnew\nline"),
+            ("\"\\\"quoted\\\" \\\\\"", "\"quoted\" \\"),
+            ("\"\\u{48}\\u{1F600}!\"", "H😀!"),
+            ("\"bad \\q \\u{110000}\"", "bad \\q \\u{110000}"),
+            ("\"unterminated", "unterminated"),
+        ];
+
+        for (literal, expected) in tests.iter() {
+            assert_eq!(unescape(literal), *expected);
+        }
+    }
+
+    #[test]
+    fn string_errors() {
+        let mut l: Lexer = Lexer::new("\"a\\qb \\u{zz}\"\nlet s = \"open");
+        let types: Vec<TokenType> = l.by_ref().map(|tok| tok.token_type).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::STRING,
+                TokenType::LET,
+                TokenType::IDENT,
+                TokenType::ASSIGN,
+                TokenType::STRING
+            ]
+        );
+        assert_eq!(
+            l.take_errors(),
+            vec![
+                LexError {
+                    kind: LexErrorKind::InvalidEscape("\\q".to_string()),
+                    span: Span::new(2, 4, 1, 3)
+                },
+                LexError {
+                    kind: LexErrorKind::InvalidEscape("\\u{".to_string()),
+                    span: Span::new(6, 9, 1, 7)
+                },
+                LexError {
+                    kind: LexErrorKind::UnterminatedString,
+                    span: Span::new(22, 27, 2, 9)
+                },
+            ]
+        );
+        assert!(l.errors().is_empty());
+    }
+
+    #[test]
+    fn numbers() {
+        let tests = [
+            ("0", TokenType::INT),
+            ("1_000_000", TokenType::INT),
+            ("0xFF", TokenType::INT),
+            ("0Xdead_beef", TokenType::INT),
+            ("0o17", TokenType::INT),
+            ("0b1010", TokenType::INT),
+            ("3.14", TokenType::FLOAT),
+            ("1e-9", TokenType::FLOAT),
+            ("2.5E+3", TokenType::FLOAT),
+            ("1_0.0_1e1_0", TokenType::FLOAT),
+        ];
+
+        for (input, token_type) in tests.iter() {
+            let mut l: Lexer = Lexer::new(input);
+            assert_eq!(
+                l.next_token(),
+                Token::new(*token_type, input).at(Span::new(0, input.len(), 1, 1))
+            );
+            assert_eq!(l.next_token().token_type, TokenType::EOF);
+            assert_eq!(l.errors(), &[], "input: {}", input);
+        }
+
+        let literals: Vec<&str> = tokenize("1.5.2 [1][0] 7.e")
+            .iter()
+            .map(|tok| tok.literal)
+            .collect();
+        assert_eq!(
+            literals,
+            vec!["1.5", ".", "2", "[", "1", "]", "[", "0", "]", "7", ".", "e"]
+        );
+    }
+
+    #[test]
+    fn number_errors() {
+        let tests = [
+            "12abc", "0x", "0xFG", "0b102", "0o8", "1e", "1e+", "2.5x", "0b_",
+        ];
+
+        for input in tests.iter() {
+            let mut l: Lexer = Lexer::new(input);
+            assert_eq!(l.next_token().literal, *input);
+            assert_eq!(l.next_token().token_type, TokenType::EOF);
+            assert_eq!(
+                l.take_errors(),
+                vec![LexError {
+                    kind: LexErrorKind::MalformedNumber(input.to_string()),
+                    span: Span::new(0, input.len(), 1, 1)
+                }]
+            );
+        }
+    }
+
+    #[test]
+    fn comments() {
+        let input = "let a = 1; // one\n/* two /* nested */ still two */ a /\n// end";
+        let types: Vec<TokenType> = tokenize(input).iter().map(|tok| tok.token_type).collect();
+
+        assert_eq!(
+            types,
+            vec![
+                TokenType::LET,
+                TokenType::IDENT,
+                TokenType::ASSIGN,
+                TokenType::INT,
+                TokenType::SEMICOLON,
+                TokenType::IDENT,
+                TokenType::SLASH,
+            ]
+        );
+
+        let comments: Vec<Token> = Lexer::new(input)
+            .with_comments()
+            .filter(|tok| tok.token_type == TokenType::COMMENT)
+            .collect();
+        assert_eq!(
+            comments,
+            vec![
+                Token::new(TokenType::COMMENT, "// one").at(Span::new(11, 17, 1, 12)),
+                Token::new(TokenType::COMMENT, "/* two /* nested */ still two */")
+                    .at(Span::new(18, 50, 2, 1)),
+                Token::new(TokenType::COMMENT, "// end").at(Span::new(55, 61, 3, 1)),
+            ]
+        );
+    }
+
+    #[test]
+    fn comment_errors() {
+        let mut l: Lexer = Lexer::new("a /* open /* nested */\nb").with_comments();
+
+        assert_eq!(l.next_token().token_type, TokenType::IDENT);
+        assert_eq!(
+            l.next_token(),
+            Token::new(TokenType::COMMENT, "/* open /* nested */\nb").at(Span::new(2, 24, 1, 3))
+        );
+        assert_eq!(l.next_token().token_type, TokenType::EOF);
+        assert_eq!(
+            l.take_errors(),
+            vec![LexError {
+                kind: LexErrorKind::UnterminatedComment,
+                span: Span::new(2, 24, 1, 3)
+            }]
+        );
+    }
+
+    #[test]
+    fn iterator() {
+        let mut l: Lexer = Lexer::new("let x = 5;");
+
+        let types: Vec<TokenType> = l.by_ref().map(|tok| tok.token_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                TokenType::LET,
+                TokenType::IDENT,
+                TokenType::ASSIGN,
+                TokenType::INT,
+                TokenType::SEMICOLON
+            ]
+        );
+        assert_eq!(l.next(), None);
+        assert_eq!(l.next(), None);
+
+        let mut peekable = Lexer::new("a == b").peekable();
+        assert_eq!(peekable.peek().map(|tok| tok.literal), Some("a"));
+        assert_eq!(
+            peekable
+                .filter(|tok| tok.token_type == TokenType::IDENT)
+                .count(),
+            2
+        );
+    }
+
+    #[test]
+    fn tokenize_input() {
+        let tokens = tokenize("add(1, 2)");
+
+        let literals: Vec<&str> = tokens.iter().map(|tok| tok.literal).collect();
+        assert_eq!(literals, vec!["add", "(", "1", ",", "2", ")"]);
+        assert_eq!(tokens[5].span, Span::new(8, 9, 1, 9));
+        assert!(tokenize("  \n ").is_empty());
+    }
+
     #[test]
     fn testing_advanced_input() {
-        let input: String = String::from(
-            "let five = 5;
+        let input = "let five = 5;
 let ten = 10;
 let add = fn(x, y) {
     x + y;
 }; 
 let result = add(five, ten);
-!-/*5;
+!-/ *5;
 5 < 10 > 5;
 
 if (5 < 10) {
@@ -271,178 +1085,99 @@ if (5 < 10) {
 
 10 == 10;
 10 != 9;
-",
-        );
+";
 
         let tests: Vec<Token> = vec![
             //line 1
-            Token::new(
-                TokenType::LET,
-                vec![AsciiChar::l, AsciiChar::e, AsciiChar::t],
-            ),
-            Token::new(
-                TokenType::IDENT,
-                vec![AsciiChar::f, AsciiChar::i, AsciiChar::v, AsciiChar::e],
-            ),
-            Token::new(TokenType::ASSIGN, vec![AsciiChar::Equal]),
-            Token::new(TokenType::INT, vec![AsciiChar::_5]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::LET, "let"),
+            Token::new(TokenType::IDENT, "five"),
+            Token::new(TokenType::ASSIGN, "="),
+            Token::new(TokenType::INT, "5"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 2
-            Token::new(
-                TokenType::LET,
-                vec![AsciiChar::l, AsciiChar::e, AsciiChar::t],
-            ),
-            Token::new(
-                TokenType::IDENT,
-                vec![AsciiChar::t, AsciiChar::e, AsciiChar::n],
-            ),
-            Token::new(TokenType::ASSIGN, vec![AsciiChar::Equal]),
-            Token::new(TokenType::INT, vec![AsciiChar::_1, AsciiChar::_0]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::LET, "let"),
+            Token::new(TokenType::IDENT, "ten"),
+            Token::new(TokenType::ASSIGN, "="),
+            Token::new(TokenType::INT, "10"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 3
-            Token::new(
-                TokenType::LET,
-                vec![AsciiChar::l, AsciiChar::e, AsciiChar::t],
-            ),
-            Token::new(
-                TokenType::IDENT,
-                vec![AsciiChar::a, AsciiChar::d, AsciiChar::d],
-            ),
-            Token::new(TokenType::ASSIGN, vec![AsciiChar::Equal]),
-            Token::new(TokenType::FUNCTION, vec![AsciiChar::f, AsciiChar::n]),
-            Token::new(TokenType::LPAREN, vec![AsciiChar::ParenOpen]),
-            Token::new(TokenType::IDENT, vec![AsciiChar::x]),
-            Token::new(TokenType::COMMA, vec![AsciiChar::Comma]),
-            Token::new(TokenType::IDENT, vec![AsciiChar::y]),
-            Token::new(TokenType::RPAREN, vec![AsciiChar::ParenClose]),
-            Token::new(TokenType::LBRACE, vec![AsciiChar::CurlyBraceOpen]),
+            Token::new(TokenType::LET, "let"),
+            Token::new(TokenType::IDENT, "add"),
+            Token::new(TokenType::ASSIGN, "="),
+            Token::new(TokenType::FUNCTION, "fn"),
+            Token::new(TokenType::LPAREN, "("),
+            Token::new(TokenType::IDENT, "x"),
+            Token::new(TokenType::COMMA, ","),
+            Token::new(TokenType::IDENT, "y"),
+            Token::new(TokenType::RPAREN, ")"),
+            Token::new(TokenType::LBRACE, "{"),
             // line 4
-            Token::new(TokenType::IDENT, vec![AsciiChar::x]),
-            Token::new(TokenType::PLUS, vec![AsciiChar::Plus]),
-            Token::new(TokenType::IDENT, vec![AsciiChar::y]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::IDENT, "x"),
+            Token::new(TokenType::PLUS, "+"),
+            Token::new(TokenType::IDENT, "y"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 5  };
-            Token::new(TokenType::RBRACE, vec![AsciiChar::CurlyBraceClose]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::RBRACE, "}"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 6  let result = add(five, ten);
-            Token::new(
-                TokenType::LET,
-                vec![AsciiChar::l, AsciiChar::e, AsciiChar::t],
-            ),
-            Token::new(
-                TokenType::IDENT,
-                vec![
-                    AsciiChar::r,
-                    AsciiChar::e,
-                    AsciiChar::s,
-                    AsciiChar::u,
-                    AsciiChar::l,
-                    AsciiChar::t,
-                ],
-            ),
-            Token::new(TokenType::ASSIGN, vec![AsciiChar::Equal]),
-            Token::new(
-                TokenType::IDENT,
-                vec![AsciiChar::a, AsciiChar::d, AsciiChar::d],
-            ),
-            Token::new(TokenType::LPAREN, vec![AsciiChar::ParenOpen]),
-            Token::new(
-                TokenType::IDENT,
-                vec![AsciiChar::f, AsciiChar::i, AsciiChar::v, AsciiChar::e],
-            ),
-            Token::new(TokenType::COMMA, vec![AsciiChar::Comma]),
-            Token::new(
-                TokenType::IDENT,
-                vec![AsciiChar::t, AsciiChar::e, AsciiChar::n],
-            ),
-            Token::new(TokenType::RPAREN, vec![AsciiChar::ParenClose]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
-            // line 7:  !-/*5;
-            Token::new(TokenType::BANG, vec![AsciiChar::Exclamation]),
-            Token::new(TokenType::MINUS, vec![AsciiChar::Minus]),
-            Token::new(TokenType::SLASH, vec![AsciiChar::Slash]),
-            Token::new(TokenType::ASTERISK, vec![AsciiChar::Asterisk]),
-            Token::new(TokenType::INT, vec![AsciiChar::_5]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::LET, "let"),
+            Token::new(TokenType::IDENT, "result"),
+            Token::new(TokenType::ASSIGN, "="),
+            Token::new(TokenType::IDENT, "add"),
+            Token::new(TokenType::LPAREN, "("),
+            Token::new(TokenType::IDENT, "five"),
+            Token::new(TokenType::COMMA, ","),
+            Token::new(TokenType::IDENT, "ten"),
+            Token::new(TokenType::RPAREN, ")"),
+            Token::new(TokenType::SEMICOLON, ";"),
+            // line 7:  !-/ *5;
+            Token::new(TokenType::BANG, "!"),
+            Token::new(TokenType::MINUS, "-"),
+            Token::new(TokenType::SLASH, "/"),
+            Token::new(TokenType::ASTERISK, "*"),
+            Token::new(TokenType::INT, "5"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 8:  5 < 10 > 5;
-            Token::new(TokenType::INT, vec![AsciiChar::_5]),
-            Token::new(TokenType::LT, vec![AsciiChar::LessThan]),
-            Token::new(TokenType::INT, vec![AsciiChar::_1, AsciiChar::_0]),
-            Token::new(TokenType::GT, vec![AsciiChar::GreaterThan]),
-            Token::new(TokenType::INT, vec![AsciiChar::_5]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::INT, "5"),
+            Token::new(TokenType::LT, "<"),
+            Token::new(TokenType::INT, "10"),
+            Token::new(TokenType::GT, ">"),
+            Token::new(TokenType::INT, "5"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 9
             // line 10:  if (5 < 10) {
-            Token::new(TokenType::IF, vec![AsciiChar::i, AsciiChar::f]),
-            Token::new(TokenType::LPAREN, vec![AsciiChar::ParenOpen]),
-            Token::new(TokenType::INT, vec![AsciiChar::_5]),
-            Token::new(TokenType::LT, vec![AsciiChar::LessThan]),
-            Token::new(TokenType::INT, vec![AsciiChar::_1, AsciiChar::_0]),
-            Token::new(TokenType::RPAREN, vec![AsciiChar::ParenClose]),
-            Token::new(TokenType::LBRACE, vec![AsciiChar::CurlyBraceOpen]),
+            Token::new(TokenType::IF, "if"),
+            Token::new(TokenType::LPAREN, "("),
+            Token::new(TokenType::INT, "5"),
+            Token::new(TokenType::LT, "<"),
+            Token::new(TokenType::INT, "10"),
+            Token::new(TokenType::RPAREN, ")"),
+            Token::new(TokenType::LBRACE, "{"),
             // line 11:  return true;
-            Token::new(
-                TokenType::RETURN,
-                vec![
-                    AsciiChar::r,
-                    AsciiChar::e,
-                    AsciiChar::t,
-                    AsciiChar::u,
-                    AsciiChar::r,
-                    AsciiChar::n,
-                ],
-            ),
-            Token::new(
-                TokenType::TRUE,
-                vec![AsciiChar::t, AsciiChar::r, AsciiChar::u, AsciiChar::e],
-            ),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::RETURN, "return"),
+            Token::new(TokenType::TRUE, "true"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 12:  } else {
-            Token::new(TokenType::RBRACE, vec![AsciiChar::CurlyBraceClose]),
-            Token::new(
-                TokenType::ELSE,
-                vec![AsciiChar::e, AsciiChar::l, AsciiChar::s, AsciiChar::e],
-            ),
-            Token::new(TokenType::LBRACE, vec![AsciiChar::CurlyBraceOpen]),
+            Token::new(TokenType::RBRACE, "}"),
+            Token::new(TokenType::ELSE, "else"),
+            Token::new(TokenType::LBRACE, "{"),
             // line 13:  return false;
-            Token::new(
-                TokenType::RETURN,
-                vec![
-                    AsciiChar::r,
-                    AsciiChar::e,
-                    AsciiChar::t,
-                    AsciiChar::u,
-                    AsciiChar::r,
-                    AsciiChar::n,
-                ],
-            ),
-            Token::new(
-                TokenType::FALSE,
-                vec![
-                    AsciiChar::f,
-                    AsciiChar::a,
-                    AsciiChar::l,
-                    AsciiChar::s,
-                    AsciiChar::e,
-                ],
-            ),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::RETURN, "return"),
+            Token::new(TokenType::FALSE, "false"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 14:  }
-            Token::new(TokenType::RBRACE, vec![AsciiChar::CurlyBraceClose]),
+            Token::new(TokenType::RBRACE, "}"),
             // line 15:
             // line 16: 10 == 10;
-            Token::new(TokenType::INT, vec![AsciiChar::_1, AsciiChar::_0]),
-            Token::new(TokenType::EQ, vec![AsciiChar::Equal, AsciiChar::Equal]),
-            Token::new(TokenType::INT, vec![AsciiChar::_1, AsciiChar::_0]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::INT, "10"),
+            Token::new(TokenType::EQ, "=="),
+            Token::new(TokenType::INT, "10"),
+            Token::new(TokenType::SEMICOLON, ";"),
             // line 17:  10 != 9;
-            Token::new(TokenType::INT, vec![AsciiChar::_1, AsciiChar::_0]),
-            Token::new(
-                TokenType::NotEq,
-                vec![AsciiChar::Exclamation, AsciiChar::Equal],
-            ),
-            Token::new(TokenType::INT, vec![AsciiChar::_9]),
-            Token::new(TokenType::SEMICOLON, vec![AsciiChar::Semicolon]),
+            Token::new(TokenType::INT, "10"),
+            Token::new(TokenType::NotEq, "!="),
+            Token::new(TokenType::INT, "9"),
+            Token::new(TokenType::SEMICOLON, ";"),
         ];
 
         let mut l: Lexer = Lexer::new(input);
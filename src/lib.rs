@@ -1,3 +1,16 @@
+#![allow(clippy::module_inception)]
+
 pub mod tokens;
 pub mod lexer;
-pub mod repl;
\ No newline at end of file
+pub mod ast;
+pub mod parser;
+pub mod evaluator;
+pub mod code;
+pub mod compiler;
+pub mod vm;
+pub mod diagnostics;
+pub mod formatter;
+pub mod repl;
+
+pub use crate::ast::ast::{BlockStatement, Expression, Program, Statement};
+pub use crate::tokens::tokens::Span;
@@ -1,65 +1,1487 @@
-use crate::tokens::tokens::Token;
-use crate::lexer::lexer::Lexer;
 use crate::ast::ast::*;
+use crate::lexer::lexer::{self, LexErrorKind, Lexer};
+use crate::tokens::tokens::{Span, Token, TokenType};
+use std::fmt;
+use std::mem;
 
-pub struct Parser {
-    lexer: Lexer;
-    current_token: Token,
-    peek_token: Token,
+/// binding power of the operators in monkey lang, from loosest to tightest.
+/// the derived `PartialOrd` follows declaration order.
+#[derive(Debug, PartialEq, PartialOrd, Clone, Copy)]
+pub enum Precedence {
+    Lowest,
+    LogicalOr,   // ||
+    LogicalAnd,  // &&
+    Equals,      // ==
+    LessGreater, // > or <
+    Sum,         // +
+    Product,     // *
+    Prefix,      // -x or !x
+    Power,       // x ** y
+    Call,        // my_function(x)
+    Index,       // array[index]
 }
 
-impl Parser {
-    // Create new Parser
-    new(lexer: &mut Lexer) -> Self {
-        let current_token = lexer.next_token();
-        let peek_token = lexer.next_token();
+impl Precedence {
+    /// returns the precedence a token has when used as an infix operator
+    pub(crate) fn of(token_type: TokenType) -> Precedence {
+        match token_type {
+            TokenType::OR => Precedence::LogicalOr,
+            TokenType::AND => Precedence::LogicalAnd,
+            TokenType::EQ | TokenType::NotEq => Precedence::Equals,
+            TokenType::LT | TokenType::GT | TokenType::LtEq | TokenType::GtEq => {
+                Precedence::LessGreater
+            }
+            TokenType::PLUS | TokenType::MINUS => Precedence::Sum,
+            TokenType::SLASH | TokenType::ASTERISK | TokenType::PERCENT => Precedence::Product,
+            TokenType::POWER => Precedence::Power,
+            TokenType::LPAREN => Precedence::Call,
+            TokenType::LBRACKET => Precedence::Index,
+            _ => Precedence::Lowest,
+        }
+    }
 
-        Parser {
-            lexer,
-            current_token,
-            peek_token,
+    /// returns the precedence the right operand of an infix operator is parsed
+    /// with. `**` is right associative, so its right operand can be another
+    /// `**`, every other operator is left associative.
+    pub(crate) fn right_of(token_type: TokenType) -> Precedence {
+        match token_type {
+            TokenType::POWER => Precedence::Prefix,
+            other => Precedence::of(other),
         }
     }
 }
 
-impl Parser {
-    next_token(&mut Self) {
-        Self.current_token = Self.peek_token;
-        Self.peek_token = Self.lexer.next_token();
+/// represents the kinds of failure the parser can report
+#[derive(Debug, PartialEq, Clone)]
+pub enum ParseErrorKind {
+    /// the parser required `expected` as the next token but read `found`
+    UnexpectedToken {
+        expected: TokenType,
+        found: TokenType,
+    },
+    /// no expression can start with the given token
+    NoPrefixParseFn(TokenType),
+    /// an `INT` token's literal could not be parsed as an `i64`
+    InvalidInteger(String),
+    /// a `FLOAT` token's literal could not be parsed as an `f64`
+    InvalidFloat(String),
+    /// a `break` or `continue` outside of the body of a loop
+    OutsideLoop(TokenType),
+    /// a `break` or `continue` in a block whose value is used, which would
+    /// leave the loop with the expression around the block half evaluated
+    InExpression(TokenType),
+    /// the lexer found malformed input
+    Lex(LexErrorKind),
+}
+
+impl fmt::Display for ParseErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ParseErrorKind::UnexpectedToken { expected, found } => write!(
+                f,
+                "expected next token to be {:?}, got {:?} instead",
+                expected, found
+            ),
+            ParseErrorKind::NoPrefixParseFn(token_type) => {
+                write!(f, "no prefix parse function for {:?} found", token_type)
+            }
+            ParseErrorKind::InvalidInteger(literal) => {
+                write!(f, "could not parse {} as integer", literal)
+            }
+            ParseErrorKind::InvalidFloat(literal) => {
+                write!(f, "could not parse {} as float", literal)
+            }
+            ParseErrorKind::OutsideLoop(token_type) => {
+                write!(f, "{} outside of a loop", token_type)
+            }
+            ParseErrorKind::InExpression(token_type) => {
+                write!(f, "{} inside an expression", token_type)
+            }
+            ParseErrorKind::Lex(kind) => write!(f, "{}", kind),
+        }
     }
-    parse_program(&mut Self) -> Result<Program> {
-        let mut program: Program = Program { statements: vec![] };
-        while Self.lexer.current_token.token_type != TokenType::EOF {
-            let statement: Option = Self.parse_statement();
-            match statement {
-                Ok(stmt) => 
-                Err(err) => 
+}
+
+/// represents a failure to parse monkey lang source, located at the token
+/// that caused it
+///
+/// # Parameters
+///
+/// * `kind` - what went wrong
+///
+/// * `span` - the location of the offending token
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct ParseError {
+    pub kind: ParseErrorKind,
+    pub span: Span,
+}
+
+impl ParseError {
+    /// returns a new ParseError located at `token`
+    fn at(token: &Token, kind: ParseErrorKind) -> ParseError {
+        ParseError {
+            kind,
+            span: token.span,
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.span, self.kind)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Represents a top-down operator precedence (Pratt) parser for monkey lang
+///
+/// # Parameters
+///
+/// * `lexer` - the `Lexer` tokens are read from
+///
+/// * `current_token` - the token currently being parsed
+///
+/// * `peek_token` - the token after `current_token`, used to decide how to
+///   continue parsing
+///
+/// * `errors` - every `ParseError` reported so far
+///
+/// * `loop_depth` - how many loop bodies the current token is nested in,
+///   within the innermost function
+///
+pub struct Parser<'a> {
+    lexer: Lexer<'a>,
+    current_token: Token<'a>,
+    peek_token: Token<'a>,
+    errors: Vec<ParseError>,
+    loop_depth: usize,
+}
+
+impl<'a> Parser<'a> {
+    /// returns a new Parser with `current_token` and `peek_token` read from
+    /// the given lexer
+    ///
+    /// # Arguments
+    ///
+    /// * `lexer` - the `Lexer` to read tokens from
+    ///
+    pub fn new(lexer: Lexer<'a>) -> Parser<'a> {
+        let mut parser = Parser {
+            lexer,
+            current_token: Token::new(TokenType::EOF, ""),
+            peek_token: Token::new(TokenType::EOF, ""),
+            errors: vec![],
+            loop_depth: 0,
+        };
+        parser.next_token();
+        parser.next_token();
+
+        parser
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// advances `current_token` and `peek_token` by one token
+    fn next_token(&mut self) {
+        self.current_token = self.peek_token;
+        self.peek_token = self.lexer.next_token();
+
+        for err in self.lexer.take_errors() {
+            self.errors.push(ParseError {
+                kind: ParseErrorKind::Lex(err.kind),
+                span: err.span,
+            });
+        }
+    }
+
+    fn current_token_is(&self, token_type: TokenType) -> bool {
+        self.current_token.token_type == token_type
+    }
+
+    fn peek_token_is(&self, token_type: TokenType) -> bool {
+        self.peek_token.token_type == token_type
+    }
+
+    /// advances the parser if `peek_token` is of the expected type, returns an
+    /// `UnexpectedToken` error otherwise
+    fn expect_peek(&mut self, token_type: TokenType) -> Result<(), ParseError> {
+        if self.peek_token_is(token_type) {
+            self.next_token();
+            Ok(())
+        } else {
+            Err(ParseError::at(
+                &self.peek_token,
+                ParseErrorKind::UnexpectedToken {
+                    expected: token_type,
+                    found: self.peek_token.token_type,
+                },
+            ))
+        }
+    }
+
+    fn peek_precedence(&self) -> Precedence {
+        Precedence::of(self.peek_token.token_type)
+    }
+
+    /// parses the lexer input until `EOF` and returns the resulting `Program`.
+    /// statements that fail to parse are left out of the program, their errors
+    /// are collected in `errors()` and parsing resumes at the next statement.
+    ///
+    /// # Arguments
+    ///
+    /// * `&mut self` - a mutable reference to the Parser construct being used
+    ///
+    pub fn parse_program(&mut self) -> Program {
+        let mut program = Program { statements: vec![] };
+
+        while !self.current_token_is(TokenType::EOF) {
+            match self.parse_statement() {
+                Ok(statement) => {
+                    program.statements.push(statement);
+                    self.next_token();
+                }
+                Err(err) => {
+                    self.report(err);
+                    self.synchronize();
+                    // a stray closing brace has no block to end at the top
+                    // level, skip it to keep making progress
+                    if self.current_token_is(TokenType::RBRACE) {
+                        self.next_token();
+                    }
+                }
+            }
+        }
+
+        // lex errors are reported when a token is read, ahead of the parse
+        // errors for the tokens before it
+        self.errors.sort_by_key(|err| err.span.start);
+
+        program
+    }
+
+    /// records a parse error, unless the lexer already reported an error for
+    /// the same source, ie: a malformed number that can't be parsed either
+    fn report(&mut self, err: ParseError) {
+        if !self.errors.iter().any(|reported| reported.span == err.span) {
+            self.errors.push(err);
+        }
+    }
+
+    /// returns the errors reported while parsing, in source order
+    pub fn errors(&self) -> &[ParseError] {
+        &self.errors
+    }
+
+    /// skips tokens until the start of the next statement after a parse error.
+    /// stops after a `SEMICOLON`, or on the `RBRACE` closing the current block
+    /// so the block can still be closed.
+    fn synchronize(&mut self) {
+        while !self.current_token_is(TokenType::EOF) {
+            match self.current_token.token_type {
+                TokenType::SEMICOLON => {
+                    self.next_token();
+                    return;
+                }
+                TokenType::RBRACE => return,
+                _ => self.next_token(),
+            }
+        }
+    }
+
+    fn parse_statement(&mut self) -> Result<Statement, ParseError> {
+        match self.current_token.token_type {
+            TokenType::LET => self.parse_let_statement(),
+            TokenType::RETURN => self.parse_return_statement(),
+            TokenType::WHILE => self.parse_while_statement(),
+            TokenType::FOR => self.parse_for_statement(),
+            TokenType::BREAK | TokenType::CONTINUE => self.parse_loop_control_statement(),
+            TokenType::IDENT
+                if self.peek_token_is(TokenType::ASSIGN)
+                    || compound_operator(self.peek_token.token_type).is_some() =>
+            {
+                self.parse_assign_statement()
+            }
+            TokenType::LBRACE if !self.brace_opens_hash() => {
+                Ok(Statement::Block(self.parse_block_statement()?))
+            }
+            _ => self.parse_expression_statement(),
+        }
+    }
+
+    fn parse_let_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_token.span;
+        self.expect_peek(TokenType::IDENT)?;
+        let name = self.current_token.literal.to_string();
+
+        self.expect_peek(TokenType::ASSIGN)?;
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.next_token();
+        }
+        check_loop_controls(&value)?;
+
+        Ok(Statement::Let {
+            name,
+            value,
+            span: start.to(self.current_token.span),
+        })
+    }
+
+    fn parse_return_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_token.span;
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.next_token();
+        }
+        check_loop_controls(&value)?;
+
+        Ok(Statement::Return {
+            value,
+            span: start.to(self.current_token.span),
+        })
+    }
+
+    fn parse_assign_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_token.span;
+        let name = self.current_token.literal.to_string();
+        self.next_token();
+
+        let operator = compound_operator(self.current_token.token_type);
+        self.next_token();
+
+        let value = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.next_token();
+        }
+        check_loop_controls(&value)?;
+
+        Ok(Statement::Assign {
+            name,
+            operator,
+            value,
+            span: start.to(self.current_token.span),
+        })
+    }
+
+    fn parse_while_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_token.span;
+        self.expect_peek(TokenType::LPAREN)?;
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        check_loop_controls(&condition)?;
+        self.expect_peek(TokenType::RPAREN)?;
+
+        self.expect_peek(TokenType::LBRACE)?;
+        let body = self.parse_loop_body()?;
+
+        Ok(Statement::While {
+            condition,
+            span: start.to(body.span),
+            body,
+        })
+    }
+
+    fn parse_for_statement(&mut self) -> Result<Statement, ParseError> {
+        let start = self.current_token.span;
+        self.expect_peek(TokenType::LPAREN)?;
+        self.expect_peek(TokenType::IDENT)?;
+        let variable = self.current_token.literal.to_string();
+
+        self.expect_peek(TokenType::IN)?;
+        self.next_token();
+        let iterable = self.parse_expression(Precedence::Lowest)?;
+        check_loop_controls(&iterable)?;
+        self.expect_peek(TokenType::RPAREN)?;
+
+        self.expect_peek(TokenType::LBRACE)?;
+        let body = self.parse_loop_body()?;
+
+        Ok(Statement::For {
+            variable,
+            iterable,
+            span: start.to(body.span),
+            body,
+        })
+    }
+
+    /// parses the block of a loop, where `break` and `continue` are allowed
+    fn parse_loop_body(&mut self) -> Result<BlockStatement, ParseError> {
+        self.loop_depth += 1;
+        let body = self.parse_block_statement();
+        self.loop_depth -= 1;
+        body
+    }
+
+    fn parse_loop_control_statement(&mut self) -> Result<Statement, ParseError> {
+        let token = self.current_token;
+        if self.loop_depth == 0 {
+            return Err(ParseError::at(
+                &token,
+                ParseErrorKind::OutsideLoop(token.token_type),
+            ));
+        }
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.next_token();
+        }
+
+        let span = token.span.to(self.current_token.span);
+        match token.token_type {
+            TokenType::BREAK => Ok(Statement::Break { span }),
+            _ => Ok(Statement::Continue { span }),
+        }
+    }
+
+    fn parse_expression_statement(&mut self) -> Result<Statement, ParseError> {
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        if self.peek_token_is(TokenType::SEMICOLON) {
+            self.next_token();
+        }
+        // the value of an `if` statement is dropped, its blocks may leave a
+        // loop. its condition, or an `if` used as an operand, may not.
+        match &expression {
+            Expression::If { condition, .. } => check_loop_controls(condition)?,
+            expression => check_loop_controls(expression)?,
+        }
+
+        Ok(Statement::Expression(expression))
+    }
+
+    /// parses an expression starting at `current_token`, consuming infix
+    /// operators for as long as they bind tighter than `precedence`
+    fn parse_expression(&mut self, precedence: Precedence) -> Result<Expression, ParseError> {
+        let mut left = self.parse_prefix()?;
+
+        while !self.peek_token_is(TokenType::SEMICOLON) && precedence < self.peek_precedence() {
+            self.next_token();
+            left = self.parse_infix(left)?;
+        }
+
+        Ok(left)
+    }
+
+    fn parse_prefix(&mut self) -> Result<Expression, ParseError> {
+        match self.current_token.token_type {
+            TokenType::IDENT => Ok(Expression::Identifier {
+                name: self.current_token.literal.to_string(),
+                span: self.current_token.span,
+            }),
+            TokenType::INT => self.parse_integer_literal(),
+            TokenType::FLOAT => self.parse_float_literal(),
+            TokenType::STRING => Ok(Expression::StringLiteral {
+                value: lexer::unescape(self.current_token.literal),
+                span: self.current_token.span,
+            }),
+            TokenType::TRUE | TokenType::FALSE => Ok(Expression::Boolean {
+                value: self.current_token_is(TokenType::TRUE),
+                span: self.current_token.span,
+            }),
+            TokenType::BANG | TokenType::MINUS => self.parse_prefix_expression(),
+            TokenType::LPAREN => self.parse_grouped_expression(),
+            TokenType::IF => self.parse_if_expression(),
+            TokenType::FUNCTION | TokenType::MACRO => self.parse_function_literal(),
+            TokenType::LBRACKET => self.parse_array_literal(),
+            TokenType::LBRACE => self.parse_hash_literal(),
+            token_type => Err(ParseError::at(
+                &self.current_token,
+                ParseErrorKind::NoPrefixParseFn(token_type),
+            )),
+        }
+    }
+
+    fn parse_infix(&mut self, left: Expression) -> Result<Expression, ParseError> {
+        match self.current_token.token_type {
+            TokenType::LPAREN => self.parse_call_expression(left),
+            TokenType::LBRACKET => self.parse_index_expression(left),
+            _ => self.parse_infix_expression(left),
+        }
+    }
+
+    fn parse_integer_literal(&mut self) -> Result<Expression, ParseError> {
+        let literal = self.current_token.literal;
+        let digits = literal.replace('_', "");
+        let (digits, radix) = match digits.get(..2) {
+            Some("0x") | Some("0X") => (&digits[2..], 16),
+            Some("0o") | Some("0O") => (&digits[2..], 8),
+            Some("0b") | Some("0B") => (&digits[2..], 2),
+            _ => (&digits[..], 10),
+        };
+
+        match i64::from_str_radix(digits, radix) {
+            Ok(value) => Ok(Expression::IntegerLiteral {
+                value,
+                span: self.current_token.span,
+            }),
+            Err(_) => Err(ParseError::at(
+                &self.current_token,
+                ParseErrorKind::InvalidInteger(literal.to_string()),
+            )),
+        }
+    }
+
+    fn parse_float_literal(&mut self) -> Result<Expression, ParseError> {
+        let literal = self.current_token.literal;
+        match literal.replace('_', "").parse::<f64>() {
+            Ok(value) => Ok(Expression::FloatLiteral {
+                value,
+                span: self.current_token.span,
+            }),
+            Err(_) => Err(ParseError::at(
+                &self.current_token,
+                ParseErrorKind::InvalidFloat(literal.to_string()),
+            )),
+        }
+    }
+
+    fn parse_prefix_expression(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_token.span;
+        let operator = self.current_token.token_type;
+        self.next_token();
+
+        let right = self.parse_expression(Precedence::Prefix)?;
+        Ok(Expression::Prefix {
+            operator,
+            span: start.to(right.span()),
+            right: Box::new(right),
+        })
+    }
+
+    fn parse_infix_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
+        let operator = self.current_token.token_type;
+        self.next_token();
+
+        let right = self.parse_expression(Precedence::right_of(operator))?;
+        Ok(Expression::Infix {
+            span: left.span().to(right.span()),
+            left: Box::new(left),
+            operator,
+            right: Box::new(right),
+        })
+    }
+
+    fn parse_grouped_expression(&mut self) -> Result<Expression, ParseError> {
+        self.next_token();
+
+        let expression = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(TokenType::RPAREN)?;
+
+        Ok(expression)
+    }
+
+    fn parse_if_expression(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_token.span;
+        self.expect_peek(TokenType::LPAREN)?;
+        self.next_token();
+        let condition = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(TokenType::RPAREN)?;
+
+        self.expect_peek(TokenType::LBRACE)?;
+        let consequence = self.parse_block_statement()?;
+
+        let alternative = if self.peek_token_is(TokenType::ELSE) {
+            self.next_token();
+            self.expect_peek(TokenType::LBRACE)?;
+            Some(self.parse_block_statement()?)
+        } else {
+            None
+        };
+
+        Ok(Expression::If {
+            condition: Box::new(condition),
+            consequence,
+            alternative,
+            span: start.to(self.current_token.span),
+        })
+    }
+
+    /// parses statements until the closing `RBRACE`, expects `current_token`
+    /// to be the opening `LBRACE`. errors inside the block are collected and
+    /// recovered from like they are at the top level.
+    fn parse_block_statement(&mut self) -> Result<BlockStatement, ParseError> {
+        let start = self.current_token.span;
+        let mut statements = vec![];
+        self.next_token();
+
+        while !self.current_token_is(TokenType::RBRACE) {
+            if self.current_token_is(TokenType::EOF) {
+                return Err(ParseError::at(
+                    &self.current_token,
+                    ParseErrorKind::UnexpectedToken {
+                        expected: TokenType::RBRACE,
+                        found: TokenType::EOF,
+                    },
+                ));
+            }
+
+            match self.parse_statement() {
+                Ok(statement) => {
+                    statements.push(statement);
+                    self.next_token();
+                }
+                Err(err) => {
+                    self.report(err);
+                    self.synchronize();
+                }
+            }
+        }
+
+        Ok(BlockStatement {
+            statements,
+            span: start.to(self.current_token.span),
+        })
+    }
+
+    /// parses a `fn` literal, or a `macro` literal which is written alike
+    fn parse_function_literal(&mut self) -> Result<Expression, ParseError> {
+        let keyword = self.current_token.token_type;
+        let start = self.current_token.span;
+        self.expect_peek(TokenType::LPAREN)?;
+        let parameters = self.parse_function_parameters()?;
+
+        // a function body starts outside of any loop, a `break` can't leave
+        // the function
+        self.expect_peek(TokenType::LBRACE)?;
+        let loop_depth = mem::replace(&mut self.loop_depth, 0);
+        let body = self.parse_block_statement();
+        self.loop_depth = loop_depth;
+        let body = body?;
+
+        let span = start.to(body.span);
+        match keyword {
+            TokenType::MACRO => Ok(Expression::MacroLiteral {
+                parameters,
+                body,
+                span,
+            }),
+            _ => Ok(Expression::FunctionLiteral {
+                parameters,
+                body,
+                span,
+            }),
+        }
+    }
+
+    fn parse_function_parameters(&mut self) -> Result<Vec<String>, ParseError> {
+        let mut parameters = vec![];
+
+        if self.peek_token_is(TokenType::RPAREN) {
+            self.next_token();
+            return Ok(parameters);
+        }
+
+        self.expect_peek(TokenType::IDENT)?;
+        parameters.push(self.current_token.literal.to_string());
+
+        while self.peek_token_is(TokenType::COMMA) {
+            self.next_token();
+            self.expect_peek(TokenType::IDENT)?;
+            parameters.push(self.current_token.literal.to_string());
+        }
+
+        self.expect_peek(TokenType::RPAREN)?;
+        Ok(parameters)
+    }
+
+    fn parse_call_expression(&mut self, function: Expression) -> Result<Expression, ParseError> {
+        let arguments = self.parse_expression_list(TokenType::RPAREN)?;
+        Ok(Expression::Call {
+            span: function.span().to(self.current_token.span),
+            function: Box::new(function),
+            arguments,
+        })
+    }
+
+    fn parse_array_literal(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_token.span;
+        let elements = self.parse_expression_list(TokenType::RBRACKET)?;
+        Ok(Expression::ArrayLiteral {
+            elements,
+            span: start.to(self.current_token.span),
+        })
+    }
+
+    /// returns whether the `LBRACE` at `current_token` opens a hash literal
+    /// rather than a block. a `COLON` can only appear directly inside the
+    /// braces of a hash literal, so the tokens up to the matching `RBRACE` are
+    /// scanned for one.
+    fn brace_opens_hash(&self) -> bool {
+        let mut lexer = self.lexer.clone();
+        let mut token = self.peek_token;
+        let mut depth = 0usize;
+
+        loop {
+            match token.token_type {
+                TokenType::COLON if depth == 0 => return true,
+                TokenType::SEMICOLON if depth == 0 => return false,
+                TokenType::RBRACE if depth == 0 => return false,
+                TokenType::EOF => return false,
+                TokenType::LPAREN | TokenType::LBRACKET | TokenType::LBRACE => depth += 1,
+                TokenType::RPAREN | TokenType::RBRACKET | TokenType::RBRACE => {
+                    depth = depth.saturating_sub(1)
+                }
+                _ => {}
             }
+            token = lexer.next_token();
         }
     }
-    parse_statement(){}
+
+    fn parse_hash_literal(&mut self) -> Result<Expression, ParseError> {
+        let start = self.current_token.span;
+        let mut pairs = vec![];
+
+        while !self.peek_token_is(TokenType::RBRACE) {
+            self.next_token();
+            let key = self.parse_expression(Precedence::Lowest)?;
+            self.expect_peek(TokenType::COLON)?;
+            self.next_token();
+            let value = self.parse_expression(Precedence::Lowest)?;
+            pairs.push((key, value));
+
+            if !self.peek_token_is(TokenType::RBRACE) {
+                self.expect_peek(TokenType::COMMA)?;
+            }
+        }
+        self.expect_peek(TokenType::RBRACE)?;
+
+        Ok(Expression::HashLiteral {
+            pairs,
+            span: start.to(self.current_token.span),
+        })
+    }
+
+    fn parse_index_expression(&mut self, left: Expression) -> Result<Expression, ParseError> {
+        self.next_token();
+        let index = self.parse_expression(Precedence::Lowest)?;
+        self.expect_peek(TokenType::RBRACKET)?;
+
+        Ok(Expression::Index {
+            span: left.span().to(self.current_token.span),
+            left: Box::new(left),
+            index: Box::new(index),
+        })
+    }
+
+    /// parses a comma separated list of expressions up to and including the
+    /// `end` token, expects `current_token` to be the opening delimiter
+    fn parse_expression_list(&mut self, end: TokenType) -> Result<Vec<Expression>, ParseError> {
+        let mut list = vec![];
+
+        if self.peek_token_is(end) {
+            self.next_token();
+            return Ok(list);
+        }
+
+        self.next_token();
+        list.push(self.parse_expression(Precedence::Lowest)?);
+
+        while self.peek_token_is(TokenType::COMMA) {
+            self.next_token();
+            self.next_token();
+            list.push(self.parse_expression(Precedence::Lowest)?);
+        }
+
+        self.expect_peek(end)?;
+        Ok(list)
+    }
 }
 
+/// returns an error located at the first `break` or `continue` of the blocks
+/// of `expression` that leaves a loop enclosing the expression
+fn check_loop_controls(expression: &Expression) -> Result<(), ParseError> {
+    match find_loop_control(expression) {
+        Some((token_type, span)) => Err(ParseError {
+            kind: ParseErrorKind::InExpression(token_type),
+            span,
+        }),
+        None => Ok(()),
+    }
+}
+
+/// returns the first `break` or `continue` in `expression`, skipping the
+/// loops and functions nested in it, which `break` and `continue` can't
+/// leave
+fn find_loop_control(expression: &Expression) -> Option<(TokenType, Span)> {
+    match expression {
+        Expression::Prefix { right, .. } => find_loop_control(right),
+        Expression::Infix { left, right, .. } => {
+            find_loop_control(left).or_else(|| find_loop_control(right))
+        }
+        Expression::If {
+            condition,
+            consequence,
+            alternative,
+            ..
+        } => find_loop_control(condition)
+            .or_else(|| find_block_loop_control(consequence))
+            .or_else(|| alternative.as_ref().and_then(find_block_loop_control)),
+        Expression::ArrayLiteral { elements, .. } => elements.iter().find_map(find_loop_control),
+        Expression::Index { left, index, .. } => {
+            find_loop_control(left).or_else(|| find_loop_control(index))
+        }
+        Expression::HashLiteral { pairs, .. } => pairs
+            .iter()
+            .find_map(|(key, value)| find_loop_control(key).or_else(|| find_loop_control(value))),
+        Expression::Call {
+            function,
+            arguments,
+            ..
+        } => find_loop_control(function).or_else(|| arguments.iter().find_map(find_loop_control)),
+        _ => None,
+    }
+}
+
+fn find_block_loop_control(block: &BlockStatement) -> Option<(TokenType, Span)> {
+    block
+        .statements
+        .iter()
+        .find_map(|statement| match statement {
+            Statement::Let { value, .. }
+            | Statement::Return { value, .. }
+            | Statement::Assign { value, .. } => find_loop_control(value),
+            Statement::While { condition, .. } => find_loop_control(condition),
+            Statement::For { iterable, .. } => find_loop_control(iterable),
+            Statement::Break { span } => Some((TokenType::BREAK, *span)),
+            Statement::Continue { span } => Some((TokenType::CONTINUE, *span)),
+            Statement::Expression(expression) => find_loop_control(expression),
+            Statement::Block(block) => find_block_loop_control(block),
+        })
+}
+
+/// returns the arithmetic operator a compound assignment applies, ie: `PLUS`
+/// for `+=`, `None` for other tokens
+fn compound_operator(token_type: TokenType) -> Option<TokenType> {
+    match token_type {
+        TokenType::PlusAssign => Some(TokenType::PLUS),
+        TokenType::MinusAssign => Some(TokenType::MINUS),
+        TokenType::AsteriskAssign => Some(TokenType::ASTERISK),
+        TokenType::SlashAssign => Some(TokenType::SLASH),
+        TokenType::PercentAssign => Some(TokenType::PERCENT),
+        _ => None,
+    }
+}
+
+//
+// Tests
+//
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn parse(input: &str) -> Program {
+        let mut parser: Parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert_eq!(parser.errors(), &[], "parser reported errors");
+        program
+    }
+
+    fn parse_expression(input: &str) -> Expression {
+        let mut program = parse(input);
+        assert_eq!(program.statements.len(), 1);
+        match program.statements.remove(0) {
+            Statement::Expression(expression) => expression,
+            statement => panic!("expected expression statement, got {:?}", statement),
+        }
+    }
+
     #[test]
-    fn test_let_statements() {
-        let test_input: String = "let x = 5;
+    fn let_statements() {
+        let program = parse(
+            "let x = 5;
 let foobar = 12345;
-let y = 5;".to_string();
+let y = true;",
+        );
+
+        assert_eq!(program.statements.len(), 3);
+        for (statement, expected) in program.statements.iter().zip(["x", "foobar", "y"].iter()) {
+            match statement {
+                Statement::Let { name, .. } => assert_eq!(name, expected),
+                other => panic!("expected let statement, got {:?}", other),
+            }
+        }
+        assert_eq!(
+            program.to_string(),
+            "let x = 5;let foobar = 12345;let y = true;"
+        );
+    }
 
-        let mut lexer: Lexer = Lexer.new(test_input);
-        let mut parser: Parser = Parser.new(&mut lexer);
-        let mut program: Result<Program> = parser.parse_program();
+    #[test]
+    fn return_statements() {
+        let program = parse("return 5; return x;");
 
-        let prog = match program {
-            Err() => eprintln!("parse_program returned None")
-            Ok(prog) => println!("parse_program data: {:?}", data)
-        };
+        assert_eq!(program.statements.len(), 2);
+        assert!(program
+            .statements
+            .iter()
+            .all(|statement| matches!(statement, Statement::Return { .. })));
+        assert_eq!(program.to_string(), "return 5;return x;");
+    }
 
-        assert_eq!(program.statements.len(), 3);
+    #[test]
+    fn prefix_expressions() {
+        match parse_expression("!5;") {
+            Expression::Prefix {
+                operator, right, ..
+            } => {
+                assert_eq!(operator, TokenType::BANG);
+                assert!(matches!(
+                    *right,
+                    Expression::IntegerLiteral { value: 5, .. }
+                ));
+            }
+            other => panic!("expected prefix expression, got {:?}", other),
+        }
+        assert_eq!(parse_expression("-foobar;").to_string(), "(-foobar)");
+    }
+
+    #[test]
+    fn infix_expressions() {
+        let tests = [
+            ("5 + 5;", TokenType::PLUS),
+            ("5 - 5;", TokenType::MINUS),
+            ("5 * 5;", TokenType::ASTERISK),
+            ("5 / 5;", TokenType::SLASH),
+            ("5 > 5;", TokenType::GT),
+            ("5 < 5;", TokenType::LT),
+            ("5 == 5;", TokenType::EQ),
+            ("5 != 5;", TokenType::NotEq),
+            ("5 <= 5;", TokenType::LtEq),
+            ("5 >= 5;", TokenType::GtEq),
+            ("5 % 5;", TokenType::PERCENT),
+            ("5 ** 5;", TokenType::POWER),
+            ("5 && 5;", TokenType::AND),
+            ("5 || 5;", TokenType::OR),
+        ];
+
+        for (input, expected) in tests.iter() {
+            match parse_expression(input) {
+                Expression::Infix {
+                    left,
+                    operator,
+                    right,
+                    ..
+                } => {
+                    assert_eq!(operator, *expected);
+                    assert!(matches!(*left, Expression::IntegerLiteral { value: 5, .. }));
+                    assert!(matches!(
+                        *right,
+                        Expression::IntegerLiteral { value: 5, .. }
+                    ));
+                }
+                other => panic!("expected infix expression, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn if_else_expression() {
+        match parse_expression("if (x < y) { x } else { y }") {
+            Expression::If {
+                condition,
+                consequence,
+                alternative,
+                ..
+            } => {
+                assert_eq!(condition.to_string(), "(x < y)");
+                assert_eq!(consequence.to_string(), "{ x }");
+                assert_eq!(alternative.unwrap().to_string(), "{ y }");
+            }
+            other => panic!("expected if expression, got {:?}", other),
+        }
+        assert_eq!(parse_expression("if (x) { y }").to_string(), "if x { y }");
+    }
+
+    #[test]
+    fn function_literal_and_call() {
+        match parse_expression("fn(x, y) { x + y; }") {
+            Expression::FunctionLiteral {
+                parameters, body, ..
+            } => {
+                assert_eq!(parameters, vec!["x".to_string(), "y".to_string()]);
+                assert_eq!(body.to_string(), "{ (x + y) }");
+            }
+            other => panic!("expected function literal, got {:?}", other),
+        }
+        assert_eq!(
+            parse_expression("add(1, 2 * 3, fn() {})").to_string(),
+            "add(1, (2 * 3), fn() {})"
+        );
+    }
+
+    #[test]
+    fn operator_precedence_display() {
+        let tests = [
+            ("-a * b", "((-a) * b)"),
+            ("!-a", "(!(-a))"),
+            ("a + b / c", "(a + (b / c))"),
+            ("a * b * c", "((a * b) * c)"),
+            ("5 > 4 == 3 < 4", "((5 > 4) == (3 < 4))"),
+            (
+                "3 + 4 * 5 == 3 * 1 + 4 * 5",
+                "((3 + (4 * 5)) == ((3 * 1) + (4 * 5)))",
+            ),
+            ("-(5 + 5)", "(-(5 + 5))"),
+            ("!(true == true)", "(!(true == true))"),
+            ("a + add(b * c) + d", "((a + add((b * c))) + d)"),
+            ("add(a + b, c)(d)", "add((a + b), c)(d)"),
+            (
+                "a * [1, 2, 3, 4][b * c] * d",
+                "((a * ([1, 2, 3, 4][(b * c)])) * d)",
+            ),
+            (
+                "add(a * b[2], b[1], 2 * [1, 2][1])",
+                "add((a * (b[2])), (b[1]), (2 * ([1, 2][1])))",
+            ),
+            ("a || b && c || d", "((a || (b && c)) || d)"),
+            ("a == b && c <= d", "((a == b) && (c <= d))"),
+            ("!a && b", "((!a) && b)"),
+            ("a + b % c >= d", "((a + (b % c)) >= d)"),
+            ("a ** b ** c", "(a ** (b ** c))"),
+            ("-a ** b", "(-(a ** b))"),
+            ("a ** -b", "(a ** (-b))"),
+            ("a * b ** c[0]", "(a * (b ** (c[0])))"),
+            ("f(a) ** 2 * 3", "((f(a) ** 2) * 3)"),
+        ];
+
+        for (input, expected) in tests.iter() {
+            assert_eq!(parse(input).to_string(), *expected);
+        }
+    }
+
+    #[test]
+    fn block_statement() {
+        let program = parse("{ let x = 1; x }");
+
+        match &program.statements[..] {
+            [Statement::Block(block)] => assert_eq!(block.statements.len(), 2),
+            other => panic!("expected a single block statement, got {:?}", other),
+        }
+        assert_eq!(program.to_string(), "{ let x = 1; x }");
+    }
+
+    #[test]
+    fn node_spans() {
+        let program = parse("let add = fn(a, b) {\n  a + b;\n};\nadd(1, -2)");
+
+        assert_eq!(program.statements[0].span(), Span::new(0, 32, 1, 1));
+        match &program.statements[0] {
+            Statement::Let { value, .. } => {
+                assert_eq!(value.span(), Span::new(10, 31, 1, 11));
+                match value {
+                    Expression::FunctionLiteral { body, .. } => {
+                        assert_eq!(body.span, Span::new(19, 31, 1, 20));
+                        assert_eq!(body.statements[0].span(), Span::new(23, 28, 2, 3));
+                    }
+                    other => panic!("expected function literal, got {:?}", other),
+                }
+            }
+            other => panic!("expected let statement, got {:?}", other),
+        }
+
+        match &program.statements[1] {
+            Statement::Expression(Expression::Call {
+                arguments, span, ..
+            }) => {
+                assert_eq!(*span, Span::new(33, 43, 4, 1));
+                assert_eq!(arguments[1].span(), Span::new(40, 42, 4, 8));
+            }
+            other => panic!("expected call expression, got {:?}", other),
+        }
+    }
+
+    /// returns the parsed program with the line, column & kind of every error
+    fn parse_with_errors(input: &str) -> (Program, Vec<(usize, usize, ParseErrorKind)>) {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        let errors = parser
+            .errors
+            .into_iter()
+            .map(|err| (err.span.line, err.span.column, err.kind))
+            .collect();
+        (program, errors)
+    }
+
+    fn error(line: usize, column: usize, kind: ParseErrorKind) -> (usize, usize, ParseErrorKind) {
+        (line, column, kind)
+    }
+
+    #[test]
+    fn parse_errors() {
+        let (_, errors) = parse_with_errors("let = 5;");
+        assert_eq!(
+            errors,
+            vec![error(
+                1,
+                5,
+                ParseErrorKind::UnexpectedToken {
+                    expected: TokenType::IDENT,
+                    found: TokenType::ASSIGN
+                }
+            )]
+        );
+
+        let (_, errors) = parse_with_errors("5 + ;");
+        assert_eq!(
+            errors,
+            vec![error(
+                1,
+                5,
+                ParseErrorKind::NoPrefixParseFn(TokenType::SEMICOLON)
+            )]
+        );
+
+        let (_, errors) = parse_with_errors("if (x) { x");
+        assert_eq!(
+            errors,
+            vec![error(
+                1,
+                11,
+                ParseErrorKind::UnexpectedToken {
+                    expected: TokenType::RBRACE,
+                    found: TokenType::EOF
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn recovers_at_statement_boundaries() {
+        let (program, errors) = parse_with_errors(
+            "let x 5;
+let y = 10;
+let = 1;
+y + ;
+x * y",
+        );
+
+        assert_eq!(
+            errors,
+            vec![
+                error(
+                    1,
+                    7,
+                    ParseErrorKind::UnexpectedToken {
+                        expected: TokenType::ASSIGN,
+                        found: TokenType::INT
+                    }
+                ),
+                error(
+                    3,
+                    5,
+                    ParseErrorKind::UnexpectedToken {
+                        expected: TokenType::IDENT,
+                        found: TokenType::ASSIGN
+                    }
+                ),
+                error(4, 5, ParseErrorKind::NoPrefixParseFn(TokenType::SEMICOLON)),
+            ]
+        );
+        assert_eq!(program.to_string(), "let y = 10;(x * y)");
+    }
+
+    #[test]
+    fn recovers_inside_blocks() {
+        let (program, errors) = parse_with_errors(
+            "let f = fn(a) {
+    let = a;
+    a +
+};
+f(1)",
+        );
+
+        assert_eq!(
+            errors,
+            vec![
+                error(
+                    2,
+                    9,
+                    ParseErrorKind::UnexpectedToken {
+                        expected: TokenType::IDENT,
+                        found: TokenType::ASSIGN
+                    }
+                ),
+                error(4, 1, ParseErrorKind::NoPrefixParseFn(TokenType::RBRACE)),
+            ]
+        );
+        assert_eq!(program.to_string(), "let f = fn(a) {};f(1)");
+    }
+
+    #[test]
+    fn string_literals() {
+        match parse_expression("\"hello\\n\\\"world\\\"\"") {
+            Expression::StringLiteral { value, span } => {
+                assert_eq!(value, "hello\n\"world\"");
+                assert_eq!(span, Span::new(0, 18, 1, 1));
+            }
+            other => panic!("expected string literal, got {:?}", other),
+        }
+        assert_eq!(
+            parse_expression("\"a\" + \"b\\tc\"").to_string(),
+            "(\"a\" + \"b\\tc\")"
+        );
+    }
+
+    #[test]
+    fn array_literals_and_index_expressions() {
+        match parse_expression("[1, 2 * 2, []]") {
+            Expression::ArrayLiteral { elements, span } => {
+                assert_eq!(elements.len(), 3);
+                assert_eq!(elements[1].to_string(), "(2 * 2)");
+                assert_eq!(elements[2].to_string(), "[]");
+                assert_eq!(span, Span::new(0, 14, 1, 1));
+            }
+            other => panic!("expected array literal, got {:?}", other),
+        }
+        match parse_expression("myArray[1 + 1]") {
+            Expression::Index { left, index, span } => {
+                assert_eq!(left.to_string(), "myArray");
+                assert_eq!(index.to_string(), "(1 + 1)");
+                assert_eq!(span, Span::new(0, 14, 1, 1));
+            }
+            other => panic!("expected index expression, got {:?}", other),
+        }
+
+        let (_, errors) = parse_with_errors("[1, 2");
+        assert_eq!(
+            errors,
+            vec![error(
+                1,
+                6,
+                ParseErrorKind::UnexpectedToken {
+                    expected: TokenType::RBRACKET,
+                    found: TokenType::EOF
+                }
+            )]
+        );
+    }
+
+    #[test]
+    fn hash_literals() {
+        match parse_expression("{\"one\": 1, true: 2 * 3, 4: [5]}") {
+            Expression::HashLiteral { pairs, span } => {
+                let pairs: Vec<(String, String)> = pairs
+                    .iter()
+                    .map(|(key, value)| (key.to_string(), value.to_string()))
+                    .collect();
+                assert_eq!(
+                    pairs,
+                    vec![
+                        ("\"one\"".to_string(), "1".to_string()),
+                        ("true".to_string(), "(2 * 3)".to_string()),
+                        ("4".to_string(), "[5]".to_string()),
+                    ]
+                );
+                assert_eq!(span, Span::new(0, 31, 1, 1));
+            }
+            other => panic!("expected hash literal, got {:?}", other),
+        }
+        assert_eq!(
+            parse("let h = {}; h[\"a\"]").to_string(),
+            "let h = {};(h[\"a\"])"
+        );
+    }
+
+    #[test]
+    fn hash_literal_or_block() {
+        let tests = [
+            ("{\"a\": 1}", "{\"a\": 1}", false),
+            ("{f(1): {\"b\": 2}}[1]", "({f(1): {\"b\": 2}}[1])", false),
+            ("{}", "{}", true),
+            ("{ x }", "{ x }", true),
+            ("{ let h = {1: 2}; h }", "{ let h = {1: 2}; h }", true),
+            ("{ f({1: 2}) }", "{ f({1: 2}) }", true),
+        ];
+
+        for (input, expected, is_block) in tests.iter() {
+            let program = parse(input);
+            assert_eq!(program.to_string(), *expected);
+            assert_eq!(
+                matches!(program.statements[0], Statement::Block(_)),
+                *is_block,
+                "input: {}",
+                input
+            );
+        }
+
+        let (_, errors) = parse_with_errors("let h = {\"a\" 1}");
+        assert_eq!(
+            errors,
+            vec![error(
+                1,
+                14,
+                ParseErrorKind::UnexpectedToken {
+                    expected: TokenType::COLON,
+                    found: TokenType::INT
+                }
+            )]
+        );
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn lex_errors() {
+        let (program, errors) = parse_with_errors("let a = \"\\x\";\nlet b = 1 +;\nlet c = \"open");
+
+        assert_eq!(
+            errors,
+            vec![
+                error(
+                    1,
+                    10,
+                    ParseErrorKind::Lex(LexErrorKind::InvalidEscape("\\x".to_string()))
+                ),
+                error(2, 12, ParseErrorKind::NoPrefixParseFn(TokenType::SEMICOLON)),
+                error(3, 9, ParseErrorKind::Lex(LexErrorKind::UnterminatedString)),
+            ]
+        );
+        assert_eq!(program.to_string(), "let a = \"\\\\x\";let c = \"open\";");
+    }
+
+    #[test]
+    fn number_literals() {
+        let tests = [
+            ("0xFF", "255"),
+            ("0o17 + 0b1010", "(15 + 10)"),
+            ("1_000_000", "1000000"),
+            ("3.14", "3.14"),
+            ("1e-9 * 2.0", "(1e-9 * 2.0)"),
+            ("-1_0.5", "(-10.5)"),
+        ];
+
+        for (input, expected) in tests.iter() {
+            assert_eq!(parse(input).to_string(), *expected);
+        }
+
+        let (_, errors) = parse_with_errors("let a = 12abc;\nlet b = 99999999999999999999;");
+        assert_eq!(
+            errors,
+            vec![
+                error(
+                    1,
+                    9,
+                    ParseErrorKind::Lex(LexErrorKind::MalformedNumber("12abc".to_string()))
+                ),
+                error(
+                    2,
+                    9,
+                    ParseErrorKind::InvalidInteger("99999999999999999999".to_string())
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn macro_literals() {
+        match parse_expression("macro(x, y) { x + y; }") {
+            Expression::MacroLiteral {
+                parameters,
+                body,
+                span,
+            } => {
+                assert_eq!(parameters, ["x", "y"]);
+                assert_eq!(body.to_string(), "{ (x + y) }");
+                assert_eq!(span, Span::new(0, 22, 1, 1));
+            }
+            other => panic!("expected macro literal, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn assign_statements() {
+        let program = parse("x = 5; y += x; z %= 2; z");
+        assert_eq!(program.statements.len(), 4);
+        match &program.statements[1] {
+            Statement::Assign {
+                name,
+                operator,
+                span,
+                ..
+            } => {
+                assert_eq!(name, "y");
+                assert_eq!(*operator, Some(TokenType::PLUS));
+                assert_eq!(*span, Span::new(7, 14, 1, 8));
+            }
+            other => panic!("expected assign statement, got {:?}", other),
+        }
+        assert_eq!(program.to_string(), "x = 5;y += x;z %= 2;z");
+    }
+
+    #[test]
+    fn loop_statements() {
+        let program = parse(
+            "while (i < 10) { i += 1; if (i == 5) { continue; } }
+for (x in [1, 2]) { break; }",
+        );
+
+        assert_eq!(program.statements.len(), 2);
+        assert!(matches!(program.statements[0], Statement::While { .. }));
+        match &program.statements[1] {
+            Statement::For {
+                variable,
+                iterable,
+                body,
+                span,
+            } => {
+                assert_eq!(variable, "x");
+                assert_eq!(iterable.to_string(), "[1, 2]");
+                assert!(matches!(body.statements[..], [Statement::Break { .. }]));
+                assert_eq!(*span, Span::new(53, 81, 2, 1));
+            }
+            other => panic!("expected for statement, got {:?}", other),
+        }
+        assert_eq!(
+            program.to_string(),
+            "while (i < 10) { i += 1; if (i == 5) { continue; } }for (x in [1, 2]) { break; }"
+        );
+    }
+
+    #[test]
+    fn loop_control_outside_loop() {
+        let (_, errors) = parse_with_errors(
+            "break;
+while (true) { let f = fn() { continue; }; break; }",
+        );
+
+        assert_eq!(
+            errors,
+            vec![
+                error(1, 1, ParseErrorKind::OutsideLoop(TokenType::BREAK)),
+                error(2, 31, ParseErrorKind::OutsideLoop(TokenType::CONTINUE)),
+            ]
+        );
+    }
+
+    #[test]
+    fn loop_control_in_expression() {
+        let (_, errors) = parse_with_errors(
+            "while (true) { let x = if (true) { break; }; }
+while (true) { [1, if (true) { if (false) { continue; } }]; }
+while (true) { if (true) { break; } + 1; }
+while (if (true) { break; }) { }
+for (x in [1]) { x = if (true) { 1 } else { continue; }; }
+while (true) { return if (true) { break; }; }",
+        );
+
+        assert_eq!(
+            errors,
+            vec![
+                error(1, 36, ParseErrorKind::InExpression(TokenType::BREAK)),
+                error(2, 45, ParseErrorKind::InExpression(TokenType::CONTINUE)),
+                error(3, 28, ParseErrorKind::InExpression(TokenType::BREAK)),
+                error(4, 20, ParseErrorKind::OutsideLoop(TokenType::BREAK)),
+                error(5, 45, ParseErrorKind::InExpression(TokenType::CONTINUE)),
+                error(6, 35, ParseErrorKind::InExpression(TokenType::BREAK)),
+            ]
+        );
+
+        let program = parse(
+            "while (true) { if (true) { if (false) { continue; } break; } }
+while (true) { let x = if (true) { for (y in [1]) { break; } 1 }; let f = fn() { 2 }; }",
+        );
+        assert_eq!(program.statements.len(), 2);
+    }
+}
@@ -1,36 +1,294 @@
-use crate::lexer::lexer::Lexer;
-use crate::tokens::tokens::{Token, TokenType};
-use std::io::*;
+use crate::ast::ast::Statement;
+use crate::evaluator::environment::{Env, Environment};
+use crate::evaluator::evaluator::eval;
+use crate::evaluator::macro_expansion::{define_macros, expand_macros};
+use crate::lexer::lexer::{LexErrorKind, Lexer};
+use crate::parser::parser::Parser;
+use crate::tokens::tokens::TokenType;
+use std::cell::RefCell;
+use std::fmt;
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::rc::Rc;
+use std::time::Instant;
 
 const PROMPT: &[u8] = b">> ";
+const CONTINUATION_PROMPT: &[u8] = b".. ";
 
-pub fn start(stdin: Stdin, stdout: Stdout) {
-    let mut buffer = String::new();
-    let mut out_handle = stdout.lock();
-    let mut in_handle = stdin.lock();
+const COMMANDS: &str = ":tokens <src>, :ast <src>, :env, :load <file>, :reset, :time, :quit";
 
-    println!("Welcome to monkey lang!");
-    println!("Ctl+C to stop the interpreter\n");
+/// runs a read-eval-print loop over `input`. every input is parsed, has its
+/// macros expanded and is evaluated against the bindings and macros kept for
+/// the whole session, input with unclosed brackets, strings or block comments
+/// continues on the next line.
+///
+/// lines starting with `:` are meta-commands:
+///
+/// * `:tokens <src>` - writes the tokens `<src>` lexes to
+///
+/// * `:ast <src>` - writes the parse tree of `<src>`
+///
+/// * `:env` - writes the bindings of the session, sorted by name
+///
+/// * `:load <file>` - evaluates the script at `<file>` into the session
+///
+/// * `:reset` - drops every binding and macro of the session
+///
+/// * `:time` - toggles writing how long each input took to evaluate
+///
+/// * `:quit` - ends the session
+///
+/// returns once `input` reaches EOF (Ctrl+D) or `:quit` is entered, or with the
+/// first error reading `input` or writing `output` or `errors`. input that
+/// isn't valid UTF-8 is reported to `errors` and skipped.
+///
+/// # Arguments
+///
+/// * `input` - where source is read from, a line at a time
+///
+/// * `output` - where the prompts and the values of evaluated input are
+///   written to
+///
+/// * `errors` - where lex, parse and runtime errors are written to
+///
+pub fn start<R, W, E>(mut input: R, output: W, errors: E) -> io::Result<()>
+where
+    R: BufRead,
+    W: Write,
+    E: Write,
+{
+    let mut session = Session {
+        env: Rc::new(RefCell::new(Environment::new())),
+        macros: Rc::new(RefCell::new(Environment::new())),
+        timing: false,
+        output,
+        errors,
+    };
+    let mut source = String::new();
+
+    writeln!(session.output, "Welcome to monkey lang!")?;
+    writeln!(session.output, "Ctrl+D or :quit to exit the interpreter\n")?;
 
     loop {
-        out_handle.write(PROMPT).unwrap();
-        out_handle.flush().unwrap();
-        match in_handle.read_line(&mut buffer) {
-            Ok(_buf) => {
-                let mut l = Lexer::new(buffer.clone());
-                let mut current_token: Token = l.next_token();
-
-                while current_token.token_type != TokenType::EOF {
-                    println!("{:?}", current_token);
-                    current_token = l.next_token();
+        let prompt = if source.is_empty() {
+            PROMPT
+        } else {
+            CONTINUATION_PROMPT
+        };
+        session.output.write_all(prompt)?;
+        session.output.flush()?;
+
+        match input.read_line(&mut source) {
+            Ok(0) => return writeln!(session.output),
+            Ok(_) if source.starts_with(':') => {
+                if let Flow::Quit = session.command(source.trim())? {
+                    return Ok(());
                 }
+            }
+            Ok(_) if is_incomplete(&source) => continue,
+            Ok(_) => session.evaluate(&source)?,
+            // a line that isn't UTF-8 is reported and dropped with the rest of
+            // the input
+            Err(err) if err.kind() == io::ErrorKind::InvalidData => session.error(err)?,
+            Err(err) => return Err(err),
+        }
+        source.clear();
+    }
+}
+
+/// whether the session goes on after a meta-command
+enum Flow {
+    Continue,
+    Quit,
+}
+
+/// the state of a repl session
+///
+/// # Parameters
+///
+/// * `env` - the bindings made so far
+///
+/// * `macros` - the macros defined so far
+///
+/// * `timing` - whether evaluation time is written after each input
+///
+/// * `output` - where prompts and values are written to
+///
+/// * `errors` - where errors are written to
+///
+struct Session<W, E> {
+    env: Env,
+    macros: Env,
+    timing: bool,
+    output: W,
+    errors: E,
+}
+
+impl<W: Write, E: Write> Session<W, E> {
+    /// runs the meta-command `line`, a line starting with `:`
+    fn command(&mut self, line: &str) -> io::Result<Flow> {
+        let (name, argument) = match line.find(char::is_whitespace) {
+            Some(at) => (&line[..at], line[at..].trim()),
+            None => (line, ""),
+        };
+
+        match name {
+            ":tokens" => self.tokens(argument)?,
+            ":ast" => self.ast(argument)?,
+            ":env" => self.bindings()?,
+            ":load" if argument.is_empty() => self.error("usage: :load <file>")?,
+            ":load" => match fs::read_to_string(argument) {
+                Ok(script) => self.evaluate(&script)?,
+                Err(err) => self.error(format!("could not read {}: {}", argument, err))?,
+            },
+            ":reset" => {
+                self.env = Rc::new(RefCell::new(Environment::new()));
+                self.macros = Rc::new(RefCell::new(Environment::new()));
+            }
+            ":time" => {
+                self.timing = !self.timing;
+                let state = if self.timing { "on" } else { "off" };
+                writeln!(self.output, "timing {}", state)?;
+            }
+            ":quit" => return Ok(Flow::Quit),
+            _ => self.error(format!(
+                "unknown command {}, expected one of {}",
+                name, COMMANDS
+            ))?,
+        }
+        Ok(Flow::Continue)
+    }
+
+    /// writes the tokens of `source`, then any lex errors
+    fn tokens(&mut self, source: &str) -> io::Result<()> {
+        let mut lexer = Lexer::new(source);
+        for token in lexer.by_ref() {
+            writeln!(self.output, "{:?}", token)?;
+        }
+        for err in lexer.take_errors() {
+            self.error(err)?;
+        }
+        Ok(())
+    }
+
+    /// writes the parse tree of every statement of `source`, or its parse
+    /// errors
+    fn ast(&mut self, source: &str) -> io::Result<()> {
+        let mut parser = Parser::new(Lexer::new(source));
+        let program = parser.parse_program();
 
-                out_handle.flush().unwrap();
+        if !parser.errors().is_empty() {
+            for err in parser.errors() {
+                self.error(err)?;
             }
-            Err(error) => {
-                eprintln!("{}", error);
+            return Ok(());
+        }
+        for statement in &program.statements {
+            writeln!(self.output, "{:#?}", statement)?;
+        }
+        Ok(())
+    }
+
+    /// writes every binding of the session as `name = value`
+    fn bindings(&mut self) -> io::Result<()> {
+        for (name, value) in self.env.borrow().bindings() {
+            writeln!(self.output, "{} = {}", name, value)?;
+        }
+        Ok(())
+    }
+
+    /// parses, expands and evaluates `source` in the session, writing its
+    /// value to `output`, or its errors to `errors`. the value of a trailing
+    /// `let`, assignment or loop isn't written.
+    fn evaluate(&mut self, source: &str) -> io::Result<()> {
+        let started = Instant::now();
+        let mut parser = Parser::new(Lexer::new(source));
+        let mut program = parser.parse_program();
+
+        if !parser.errors().is_empty() {
+            for err in parser.errors() {
+                self.error(err)?;
             }
+            return Ok(());
+        }
+
+        let quiet = matches!(
+            program.statements.last(),
+            None | Some(Statement::Let { .. })
+                | Some(Statement::Assign { .. })
+                | Some(Statement::While { .. })
+                | Some(Statement::For { .. })
+        );
+        define_macros(&mut program, &self.macros);
+        let result = match expand_macros(program, &self.macros) {
+            Ok(program) => eval(&program, &self.env),
+            Err(err) => err,
+        };
+        if result.is_error() {
+            self.error(result)?;
+        } else if !quiet {
+            writeln!(self.output, "{}", result)?;
+        }
+
+        if self.timing {
+            writeln!(self.output, "time: {:?}", started.elapsed())?;
+        }
+        Ok(())
+    }
+
+    /// writes `err` to the error sink
+    fn error(&mut self, err: impl fmt::Display) -> io::Result<()> {
+        writeln!(self.errors, "{}", err)?;
+        self.errors.flush()
+    }
+}
+
+/// returns whether `source` ends inside an unclosed bracket, string or block
+/// comment, and needs more lines before it can be parsed
+fn is_incomplete(source: &str) -> bool {
+    let mut lexer = Lexer::new(source);
+    let mut depth = 0i64;
+
+    for token in lexer.by_ref() {
+        match token.token_type {
+            TokenType::LPAREN | TokenType::LBRACE | TokenType::LBRACKET => depth += 1,
+            TokenType::RPAREN | TokenType::RBRACE | TokenType::RBRACKET => depth -= 1,
+            _ => {}
+        }
+    }
+
+    depth > 0
+        || lexer.errors().iter().any(|err| {
+            err.kind == LexErrorKind::UnterminatedString
+                || err.kind == LexErrorKind::UnterminatedComment
+        })
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn incomplete_input() {
+        let tests = [
+            ("let a = 1;", false),
+            ("let f = fn(x) {", true),
+            ("let f = fn(x) {\n  x\n}", false),
+            ("add(1,", true),
+            ("[1, [2]", true),
+            ("}", false),
+            ("\"multi\nline", true),
+            ("\"a\" + \"b\"", false),
+            ("/* a\n/* b */", true),
+            ("1 // {", false),
+        ];
+
+        for (source, incomplete) in tests.iter() {
+            assert_eq!(is_incomplete(source), *incomplete, "source: {:?}", source);
         }
-        buffer = String::new();
     }
 }
@@ -1,33 +1,51 @@
-use ascii::*;
+use std::fmt;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
 pub enum TokenType {
     ILLEGAL,
     EOF,
     // Identifiers + Literals
     IDENT,
     INT,
+    FLOAT,
+    STRING,
     // Operators
-    ASSIGN, 
+    ASSIGN,
+    PlusAssign,
+    MinusAssign,
+    AsteriskAssign,
+    SlashAssign,
+    PercentAssign,
     PLUS,
     MINUS,
     BANG,
     ASTERISK,
     SLASH,
+    PERCENT,
+    POWER,
 
     // comparison operators
     LT,
     GT,
+    LtEq,
+    GtEq,
     EQ,
     NotEq,
 
+    // logical operators
+    AND,
+    OR,
+
     // Delimiters
     COMMA,
     SEMICOLON,
+    COLON,
     LPAREN,
     RPAREN,
     LBRACE,
     RBRACE,
+    LBRACKET,
+    RBRACKET,
 
     // Keywords
     FUNCTION,
@@ -36,52 +54,175 @@ pub enum TokenType {
     FALSE,
     IF,
     ELSE,
-    RETURN
+    RETURN,
+    WHILE,
+    FOR,
+    IN,
+    BREAK,
+    CONTINUE,
+    MACRO,
+
+    // Trivia, only returned by lexers built `with_comments()`
+    COMMENT,
+}
+
+impl fmt::Display for TokenType {
+    /// writes the source form of operators, delimiters and keywords, and the
+    /// variant name for every other token type
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let symbol = match self {
+            TokenType::ASSIGN => "=",
+            TokenType::PlusAssign => "+=",
+            TokenType::MinusAssign => "-=",
+            TokenType::AsteriskAssign => "*=",
+            TokenType::SlashAssign => "/=",
+            TokenType::PercentAssign => "%=",
+            TokenType::PLUS => "+",
+            TokenType::MINUS => "-",
+            TokenType::BANG => "!",
+            TokenType::ASTERISK => "*",
+            TokenType::SLASH => "/",
+            TokenType::PERCENT => "%",
+            TokenType::POWER => "**",
+            TokenType::LT => "<",
+            TokenType::GT => ">",
+            TokenType::LtEq => "<=",
+            TokenType::GtEq => ">=",
+            TokenType::EQ => "==",
+            TokenType::NotEq => "!=",
+            TokenType::AND => "&&",
+            TokenType::OR => "||",
+            TokenType::COMMA => ",",
+            TokenType::SEMICOLON => ";",
+            TokenType::COLON => ":",
+            TokenType::LPAREN => "(",
+            TokenType::RPAREN => ")",
+            TokenType::LBRACE => "{",
+            TokenType::RBRACE => "}",
+            TokenType::LBRACKET => "[",
+            TokenType::RBRACKET => "]",
+            TokenType::FUNCTION => "fn",
+            TokenType::LET => "let",
+            TokenType::TRUE => "true",
+            TokenType::FALSE => "false",
+            TokenType::IF => "if",
+            TokenType::ELSE => "else",
+            TokenType::RETURN => "return",
+            TokenType::WHILE => "while",
+            TokenType::FOR => "for",
+            TokenType::IN => "in",
+            TokenType::BREAK => "break",
+            TokenType::CONTINUE => "continue",
+            TokenType::MACRO => "macro",
+            other => return write!(f, "{:?}", other),
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+/// represents the location of a piece of source code
+///
+/// # Parameters
+///
+/// * `start` - `usize` - byte offset of the first character
+///
+/// * `end` - `usize` - byte offset one past the last character
+///
+/// * `line` - `usize` - the 1-based line `start` is on, `0` when the span
+///   doesn't come from source
+///
+/// * `column` - `usize` - the 1-based column of `start` on its line
+///
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    /// returns a new Span
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+
+    /// returns a span running from the start of `self` to the end of `other`
+    pub fn to(self, other: Span) -> Span {
+        Span {
+            end: other.end,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
 }
 
 /// represents a monkey lang Token for the interpreter to parse
-/// 
+///
 /// # Parameters
-/// 
+///
 /// * `token_type` - `TokenType` - item describing what type of identifier the
-/// lexer has read
-/// 
-/// * `literal` - `Vec<AsciiChar>` - the characters parsed to create the 
-/// identifier/keyword read by the lexer. Represented as a vector of `AsciiChar` 
-/// items.
-/// 
-#[derive(Debug, PartialEq)]
-pub struct Token {
+///   lexer has read
+///
+/// * `literal` - `&str` - the characters parsed to create the
+///   identifier/keyword read by the lexer, or the offending character of an
+///   `ILLEGAL` token. borrowed from the lexer input.
+///
+/// * `span` - `Span` - where the token was read from, `Span::default()` when
+///   the token wasn't read from source
+///
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Token<'a> {
     pub token_type: TokenType,
-    pub literal: Vec<AsciiChar>,
+    pub literal: &'a str,
+    pub span: Span,
 }
 
-impl Token {
-    /// takes in a token type, & literal value as a vector of AsciiChar items, 
-    /// and returns a new Token
-    pub fn new(token_type: TokenType, literal: Vec<AsciiChar>) -> Token {
+impl<'a> Token<'a> {
+    /// takes in a token type, & literal value, and returns a new Token without
+    /// a source position
+    pub fn new(token_type: TokenType, literal: &'a str) -> Token<'a> {
         Token {
             token_type,
             literal,
+            span: Span::default(),
         }
     }
 
-    /// takes in a vector of asciichar items representing a literal value from 
-    /// input, and returns a tokentype matching the value given.
-    pub fn look_up_ident(literal: Vec<AsciiChar>) -> TokenType {
-        if literal[0].is_ascii_digit() {
-            TokenType::INT
-        } else {
-            match literal.as_slice() {
-                [AsciiChar::f, AsciiChar::n] => TokenType::FUNCTION,
-                [AsciiChar::l, AsciiChar::e, AsciiChar::t] => TokenType::LET,
-                [AsciiChar::t, AsciiChar::r, AsciiChar::u, AsciiChar::e] => TokenType::TRUE,
-                [AsciiChar::f, AsciiChar::a, AsciiChar::l, AsciiChar::s, AsciiChar::e] => TokenType::FALSE,
-                [AsciiChar::i, AsciiChar::f] => TokenType::IF,
-                [AsciiChar::e, AsciiChar::l, AsciiChar::s, AsciiChar::e] => TokenType::ELSE,
-                [AsciiChar::r, AsciiChar::e, AsciiChar::t, AsciiChar::u, AsciiChar::r, AsciiChar::n] => TokenType::RETURN,
-                _ => TokenType::IDENT,
-            }
+    /// returns the token with its location set to `span`
+    pub fn at(self, span: Span) -> Token<'a> {
+        Token { span, ..self }
+    }
+
+    /// takes in the literal of an identifier, and returns the keyword's
+    /// tokentype if it is one, `IDENT` otherwise. numbers are read separately
+    /// by the lexer.
+    pub fn look_up_ident(literal: &str) -> TokenType {
+        match literal {
+            "fn" => TokenType::FUNCTION,
+            "let" => TokenType::LET,
+            "true" => TokenType::TRUE,
+            "false" => TokenType::FALSE,
+            "if" => TokenType::IF,
+            "else" => TokenType::ELSE,
+            "return" => TokenType::RETURN,
+            "while" => TokenType::WHILE,
+            "for" => TokenType::FOR,
+            "in" => TokenType::IN,
+            "break" => TokenType::BREAK,
+            "continue" => TokenType::CONTINUE,
+            "macro" => TokenType::MACRO,
+            _ => TokenType::IDENT,
         }
     }
 }
@@ -92,24 +233,36 @@ mod tests {
 
     #[test]
     fn new_token() {
-        let token = Token::new(TokenType::PLUS, vec![AsciiChar::Plus]);
+        let token = Token::new(TokenType::PLUS, "+");
 
         assert_eq!(
             token,
             Token {
                 token_type: TokenType::PLUS,
-                literal: vec![AsciiChar::Plus]
+                literal: "+",
+                span: Span::default(),
             }
         );
     }
 
+    #[test]
+    fn span_to() {
+        let start = Span::new(4, 7, 2, 1);
+        let end = Span::new(10, 12, 2, 7);
+
+        assert_eq!(start.to(end), Span::new(4, 12, 2, 1));
+        assert_eq!(start.to(end).to_string(), "2:1");
+    }
+
     #[test]
     fn look_up_ident() {
-        let number_test = vec![AsciiChar::_3, AsciiChar::_6];
-        let string_test = vec![AsciiChar::f, AsciiChar::i, AsciiChar::v, AsciiChar::e];
-        let keyword_test = vec![AsciiChar::l, AsciiChar::e, AsciiChar::t];
-        assert_eq!(Token::look_up_ident(number_test), TokenType::INT);
+        let string_test = "five";
+        let keyword_test = "let";
         assert_eq!(Token::look_up_ident(string_test), TokenType::IDENT);
         assert_eq!(Token::look_up_ident(keyword_test), TokenType::LET);
+        assert_eq!(Token::look_up_ident("while"), TokenType::WHILE);
+        assert_eq!(Token::look_up_ident("in"), TokenType::IN);
+        assert_eq!(Token::look_up_ident("inner"), TokenType::IDENT);
+        assert_eq!(Token::look_up_ident("macro"), TokenType::MACRO);
     }
 }
@@ -0,0 +1 @@
+pub mod vm;
\ No newline at end of file
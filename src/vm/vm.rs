@@ -0,0 +1,715 @@
+use crate::code::code::{line_at, read_u16, Opcode};
+use crate::compiler::compiler::Bytecode;
+use crate::evaluator::builtins::BUILTINS;
+use crate::evaluator::evaluator::{
+    eval_index_expression, eval_infix_expression, eval_prefix_expression, unusable_as_hash_key,
+};
+use crate::evaluator::object::{Closure, CompiledFunction, HashPair, Object};
+use crate::tokens::tokens::TokenType;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt;
+use std::mem;
+use std::rc::Rc;
+
+/// the maximum number of values on the stack
+pub const STACK_SIZE: usize = 2048;
+
+/// the maximum depth of nested function calls
+pub const MAX_FRAMES: usize = 1024;
+
+/// represents a runtime error of the vm, carrying the same messages the
+/// evaluator reports
+///
+/// # Parameters
+///
+/// * `message` - what went wrong
+///
+/// * `line` - the source line of the failing instruction, `None` when the
+///   bytecode has no line for it
+///
+#[derive(Debug, PartialEq, Clone)]
+pub struct VmError {
+    pub message: String,
+    pub line: Option<usize>,
+}
+
+impl VmError {
+    fn new(message: impl Into<String>) -> VmError {
+        VmError {
+            message: message.into(),
+            line: None,
+        }
+    }
+}
+
+impl fmt::Display for VmError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+impl std::error::Error for VmError {}
+
+/// returns the value of a local or free variable, read through its cell once
+/// a closure captured it
+fn deref(variable: &Object) -> Object {
+    match variable {
+        Object::Cell(cell) => cell.borrow().clone(),
+        value => value.clone(),
+    }
+}
+
+/// turns a local or global variable into a cell, unless a closure captured
+/// it already, and returns the cell
+fn capture(variable: &mut Object) -> Object {
+    if !matches!(variable, Object::Cell(_)) {
+        let value = mem::replace(variable, Object::Null);
+        *variable = Object::Cell(Rc::new(RefCell::new(value)));
+    }
+    variable.clone()
+}
+
+/// returns the error of bytecode the compiler wouldn't produce, ie: read from
+/// a corrupted `.mkc` file
+fn malformed(message: impl fmt::Display) -> VmError {
+    VmError::new(format!("malformed bytecode: {}", message))
+}
+
+/// turns an `Object::Error` produced by the shared operator helpers into a
+/// `VmError`
+fn check(result: Object) -> Result<Object, VmError> {
+    match result {
+        Object::Error { message, .. } => Err(VmError::new(message)),
+        result => Ok(result),
+    }
+}
+
+/// the call of a closure being executed
+///
+/// # Parameters
+///
+/// * `closure` - the closure being executed
+///
+/// * `ip` - the position of the next instruction to execute
+///
+/// * `base_pointer` - the stack position of the first local of the call
+///
+#[derive(Debug)]
+struct Frame {
+    closure: Rc<Closure>,
+    ip: usize,
+    base_pointer: usize,
+}
+
+/// Represents a stack based virtual machine executing compiled bytecode
+///
+/// # Parameters
+///
+/// * `constants` - the constant pool of the program
+///
+/// * `globals` - the values of the global bindings, by symbol index
+///
+/// * `stack` - the operand stack, also holding the locals of every call
+///
+/// * `frame` - the call being executed
+///
+/// * `callers` - the calls waiting on a return, the top level of the program
+///   first
+///
+/// * `last_popped` - the value most recently popped off the stack, the result
+///   of the last expression statement
+///
+pub struct Vm {
+    constants: Vec<Object>,
+    globals: Vec<Object>,
+    stack: Vec<Object>,
+    frame: Frame,
+    callers: Vec<Frame>,
+    last_popped: Object,
+}
+
+impl Vm {
+    /// returns a new Vm ready to run the given bytecode
+    ///
+    /// # Arguments
+    ///
+    /// * `bytecode` - the compiled program to run
+    ///
+    pub fn new(bytecode: Bytecode) -> Vm {
+        let main = Closure {
+            function: Rc::new(CompiledFunction {
+                instructions: bytecode.instructions,
+                num_locals: 0,
+                num_parameters: 0,
+                lines: bytecode.lines,
+            }),
+            free: vec![],
+        };
+
+        Vm {
+            constants: bytecode.constants,
+            globals: vec![],
+            stack: Vec::with_capacity(STACK_SIZE),
+            frame: Frame {
+                closure: Rc::new(main),
+                ip: 0,
+                base_pointer: 0,
+            },
+            callers: vec![],
+            last_popped: Object::Null,
+        }
+    }
+}
+
+impl Vm {
+    /// returns the value most recently popped off the stack, which after `run`
+    /// is the value of the program
+    pub fn last_popped(&self) -> &Object {
+        &self.last_popped
+    }
+
+    /// executes the program until its last instruction or a top level
+    /// `return`, stopping at the first runtime error, located at the line of
+    /// the instruction that raised it
+    ///
+    /// # Arguments
+    ///
+    /// * `&mut self` - a mutable reference to the Vm construct being used
+    ///
+    pub fn run(&mut self) -> Result<(), VmError> {
+        self.execute().map_err(|err| VmError {
+            line: err.line.or_else(|| self.line()),
+            ..err
+        })
+    }
+
+    fn execute(&mut self) -> Result<(), VmError> {
+        while let Some(op) = self.fetch()? {
+            match op {
+                Opcode::Constant => {
+                    let index = self.read_u16()?;
+                    let constant = self.constant(index)?.clone();
+                    self.push(constant)?;
+                }
+                Opcode::Pop => self.last_popped = self.pop()?,
+                Opcode::True => self.push(Object::Boolean(true))?,
+                Opcode::False => self.push(Object::Boolean(false))?,
+                Opcode::Null => self.push(Object::Null)?,
+                Opcode::Add
+                | Opcode::Sub
+                | Opcode::Mul
+                | Opcode::Div
+                | Opcode::Mod
+                | Opcode::Pow
+                | Opcode::Equal
+                | Opcode::NotEqual
+                | Opcode::GreaterThan
+                | Opcode::GreaterEqual
+                | Opcode::LessEqual
+                | Opcode::LessThan => {
+                    let operator = match op {
+                        Opcode::Add => TokenType::PLUS,
+                        Opcode::Sub => TokenType::MINUS,
+                        Opcode::Mul => TokenType::ASTERISK,
+                        Opcode::Div => TokenType::SLASH,
+                        Opcode::Mod => TokenType::PERCENT,
+                        Opcode::Pow => TokenType::POWER,
+                        Opcode::Equal => TokenType::EQ,
+                        Opcode::NotEqual => TokenType::NotEq,
+                        Opcode::GreaterThan => TokenType::GT,
+                        Opcode::GreaterEqual => TokenType::GtEq,
+                        Opcode::LessEqual => TokenType::LtEq,
+                        _ => TokenType::LT,
+                    };
+                    let right = self.pop()?;
+                    let left = self.pop()?;
+                    self.push(check(eval_infix_expression(operator, left, right))?)?;
+                }
+                Opcode::Minus | Opcode::Bang => {
+                    let operator = match op {
+                        Opcode::Minus => TokenType::MINUS,
+                        _ => TokenType::BANG,
+                    };
+                    let right = self.pop()?;
+                    self.push(check(eval_prefix_expression(operator, right))?)?;
+                }
+                Opcode::Jump => {
+                    let position = self.read_u16()?;
+                    self.frame.ip = position;
+                }
+                Opcode::JumpNotTruthy => {
+                    let position = self.read_u16()?;
+                    if !self.pop()?.is_truthy() {
+                        self.frame.ip = position;
+                    }
+                }
+                Opcode::GetGlobal => {
+                    let index = self.read_u16()?;
+                    let value = self.globals.get(index).map_or(Object::Null, deref);
+                    self.push(value)?;
+                }
+                Opcode::SetGlobal => {
+                    let index = self.read_u16()?;
+                    if index >= self.globals.len() {
+                        self.globals.resize(index + 1, Object::Null);
+                    }
+                    self.globals[index] = self.pop()?;
+                }
+                Opcode::AssignGlobal => {
+                    let index = self.read_u16()?;
+                    let value = self.pop()?;
+                    match self.global(index)? {
+                        Object::Cell(cell) => *cell.borrow_mut() = value,
+                        slot => *slot = value,
+                    }
+                }
+                Opcode::CaptureGlobal => {
+                    let index = self.read_u16()?;
+                    let cell = capture(self.global(index)?);
+                    self.push(cell)?;
+                }
+                Opcode::GetLocal => {
+                    let index = self.read_u8()?;
+                    let value = deref(self.local(index)?);
+                    self.push(value)?;
+                }
+                Opcode::SetLocal => {
+                    let index = self.read_u8()?;
+                    let value = self.pop()?;
+                    *self.local(index)? = value;
+                }
+                Opcode::AssignLocal => {
+                    let index = self.read_u8()?;
+                    let value = self.pop()?;
+                    match self.local(index)? {
+                        Object::Cell(cell) => *cell.borrow_mut() = value,
+                        slot => *slot = value,
+                    }
+                }
+                Opcode::CaptureLocal => {
+                    let index = self.read_u8()?;
+                    let cell = capture(self.local(index)?);
+                    self.push(cell)?;
+                }
+                Opcode::GetBuiltin => {
+                    let index = self.read_u8()?;
+                    let (name, function) = *BUILTINS
+                        .get(index)
+                        .ok_or_else(|| malformed(format!("no builtin {}", index)))?;
+                    self.push(Object::Builtin { name, function })?;
+                }
+                Opcode::GetFree => {
+                    let index = self.read_u8()?;
+                    let value = deref(self.free(index)?);
+                    self.push(value)?;
+                }
+                Opcode::SetFree => {
+                    let index = self.read_u8()?;
+                    let value = self.pop()?;
+                    match self.free(index)? {
+                        Object::Cell(cell) => *cell.borrow_mut() = value,
+                        _ => {
+                            let err = format!("free variable {} can't be assigned", index);
+                            return Err(malformed(err));
+                        }
+                    }
+                }
+                Opcode::CaptureFree => {
+                    let index = self.read_u8()?;
+                    let cell = self.free(index)?.clone();
+                    self.push(cell)?;
+                }
+                Opcode::CurrentClosure => {
+                    let closure = Rc::clone(&self.frame.closure);
+                    self.push(Object::Closure(closure))?;
+                }
+                Opcode::Array => {
+                    let len = self.read_u16()?;
+                    let elements = self.pop_many(len)?;
+                    self.push(Object::Array(elements))?;
+                }
+                Opcode::Hash => {
+                    let len = self.read_u16()?;
+                    let hash = self.build_hash(len)?;
+                    self.push(hash)?;
+                }
+                Opcode::Index => {
+                    let index = self.pop()?;
+                    let left = self.pop()?;
+                    self.push(check(eval_index_expression(left, index))?)?;
+                }
+                Opcode::IterNext => {
+                    let position = self.read_u16()?;
+                    let index = self.pop()?;
+                    let iterable = self.pop()?;
+                    let elements = match &iterable {
+                        Object::Array(elements) => elements,
+                        other => {
+                            return Err(VmError::new(format!(
+                                "not iterable: {}",
+                                other.type_name()
+                            )))
+                        }
+                    };
+                    let next = match index {
+                        Object::Integer(index) => elements.get(index as usize).cloned(),
+                        _ => None,
+                    };
+                    match next {
+                        Some(element) => self.push(element)?,
+                        None => self.frame.ip = position,
+                    }
+                }
+                Opcode::Call => {
+                    let num_args = self.read_u8()?;
+                    self.call(num_args)?;
+                }
+                Opcode::ReturnValue | Opcode::Return => {
+                    let value = match op {
+                        Opcode::ReturnValue => self.pop()?,
+                        _ => Object::Null,
+                    };
+
+                    let caller = match self.callers.pop() {
+                        Some(caller) => caller,
+                        None => {
+                            // a top level return ends the program
+                            self.last_popped = value;
+                            self.frame.ip = usize::MAX;
+                            continue;
+                        }
+                    };
+                    let frame = mem::replace(&mut self.frame, caller);
+                    self.stack.truncate(frame.base_pointer - 1);
+                    self.push(value)?;
+                }
+                Opcode::Closure => {
+                    let index = self.read_u16()?;
+                    let num_free = self.read_u8()?;
+                    let function = match self.constant(index)? {
+                        Object::CompiledFunction(function) => Rc::clone(function),
+                        other => {
+                            return Err(VmError::new(format!(
+                                "not a function: {}",
+                                other.type_name()
+                            )))
+                        }
+                    };
+                    let free = self.pop_many(num_free)?;
+                    self.push(Object::Closure(Rc::new(Closure { function, free })))?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// returns the source line of the instruction being executed
+    fn line(&self) -> Option<usize> {
+        let frame = &self.frame;
+        line_at(&frame.closure.function.lines, frame.ip.saturating_sub(1))
+    }
+
+    /// returns the opcode at the instruction pointer and advances past it,
+    /// `None` once the top level instructions are exhausted. a function
+    /// always returns before running out of instructions.
+    fn fetch(&mut self) -> Result<Option<Opcode>, VmError> {
+        let frame = &mut self.frame;
+        let byte = match frame.closure.function.instructions.get(frame.ip) {
+            Some(byte) => *byte,
+            None if self.callers.is_empty() => return Ok(None),
+            None => return Err(malformed("function ends without returning")),
+        };
+        frame.ip += 1;
+
+        Opcode::try_from(byte)
+            .map(Some)
+            .map_err(|byte| VmError::new(format!("unknown opcode: {}", byte)))
+    }
+
+    fn read_u16(&mut self) -> Result<usize, VmError> {
+        let frame = &mut self.frame;
+        let operand = frame
+            .closure
+            .function
+            .instructions
+            .get(frame.ip..frame.ip + 2)
+            .map(read_u16)
+            .ok_or_else(|| malformed("truncated operand"))?;
+        frame.ip += 2;
+        Ok(operand as usize)
+    }
+
+    fn read_u8(&mut self) -> Result<usize, VmError> {
+        let frame = &mut self.frame;
+        let operand = *frame
+            .closure
+            .function
+            .instructions
+            .get(frame.ip)
+            .ok_or_else(|| malformed("truncated operand"))?;
+        frame.ip += 1;
+        Ok(operand as usize)
+    }
+
+    fn constant(&self, index: usize) -> Result<&Object, VmError> {
+        self.constants
+            .get(index)
+            .ok_or_else(|| malformed(format!("no constant {}", index)))
+    }
+
+    fn global(&mut self, index: usize) -> Result<&mut Object, VmError> {
+        self.globals
+            .get_mut(index)
+            .ok_or_else(|| malformed(format!("no global {}", index)))
+    }
+
+    fn free(&self, index: usize) -> Result<&Object, VmError> {
+        self.frame
+            .closure
+            .free
+            .get(index)
+            .ok_or_else(|| malformed(format!("no free variable {}", index)))
+    }
+
+    /// returns the stack slot of the local `index` of the current call
+    fn local(&mut self, index: usize) -> Result<&mut Object, VmError> {
+        let frame = &self.frame;
+        if index >= frame.closure.function.num_locals {
+            return Err(malformed(format!("no local {}", index)));
+        }
+        self.stack
+            .get_mut(frame.base_pointer + index)
+            .ok_or_else(|| malformed(format!("no local {}", index)))
+    }
+
+    fn push(&mut self, value: Object) -> Result<(), VmError> {
+        if self.stack.len() >= STACK_SIZE {
+            return Err(VmError::new("stack overflow"));
+        }
+        self.stack.push(value);
+        Ok(())
+    }
+
+    /// pops the top of the stack, never below the locals of the current call
+    fn pop(&mut self) -> Result<Object, VmError> {
+        if self.stack.len() <= self.stack_bottom() {
+            return Err(malformed("stack underflow"));
+        }
+        self.stack.pop().ok_or_else(|| malformed("stack underflow"))
+    }
+
+    /// pops the top `len` values of the stack, in the order they were pushed
+    fn pop_many(&mut self, len: usize) -> Result<Vec<Object>, VmError> {
+        if self.stack.len() < self.stack_bottom() + len {
+            return Err(malformed("stack underflow"));
+        }
+        Ok(self.stack.split_off(self.stack.len() - len))
+    }
+
+    /// returns the stack position right above the locals of the current call
+    fn stack_bottom(&self) -> usize {
+        self.frame.base_pointer + self.frame.closure.function.num_locals
+    }
+
+    /// builds a hash from the top `len` values of the stack, alternating keys
+    /// and values
+    fn build_hash(&mut self, len: usize) -> Result<Object, VmError> {
+        let values = self.pop_many(len)?;
+        let mut hash = BTreeMap::new();
+        let mut values = values.into_iter();
+
+        while let (Some(key), Some(value)) = (values.next(), values.next()) {
+            let hash_key = match key.hash_key() {
+                Some(hash_key) => hash_key,
+                None => return check(unusable_as_hash_key(&key)),
+            };
+            hash.insert(hash_key, HashPair { key, value });
+        }
+
+        Ok(Object::Hash(hash))
+    }
+
+    /// calls the function below the top `num_args` values of the stack
+    fn call(&mut self, num_args: usize) -> Result<(), VmError> {
+        if self.stack.len() < self.stack_bottom() + 1 + num_args {
+            return Err(malformed("stack underflow"));
+        }
+        let callee = self.stack.len() - 1 - num_args;
+
+        match &self.stack[callee] {
+            Object::Closure(closure) => {
+                let closure = Rc::clone(closure);
+                let function = &closure.function;
+                if function.num_parameters != num_args {
+                    return Err(VmError::new(format!(
+                        "wrong number of arguments: want={}, got={}",
+                        function.num_parameters, num_args
+                    )));
+                }
+                if self.callers.len() + 1 >= MAX_FRAMES {
+                    return Err(VmError::new("stack overflow"));
+                }
+
+                let base_pointer = callee + 1;
+                let stack_top = base_pointer + function.num_locals;
+                if stack_top > STACK_SIZE {
+                    return Err(VmError::new("stack overflow"));
+                }
+                self.stack.resize(stack_top, Object::Null);
+                let caller = mem::replace(
+                    &mut self.frame,
+                    Frame {
+                        closure,
+                        ip: 0,
+                        base_pointer,
+                    },
+                );
+                self.callers.push(caller);
+                Ok(())
+            }
+            Object::Builtin { function, .. } => {
+                let function = *function;
+                let args = self.stack.split_off(callee + 1);
+                self.stack.pop();
+                let result = check(function(args))?;
+                self.push(result)
+            }
+            other => Err(VmError::new(format!(
+                "not a function: {}",
+                other.type_name()
+            ))),
+        }
+    }
+}
+
+//
+// Tests
+//
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::code::code::make;
+    use crate::compiler::compiler::Compiler;
+    use crate::lexer::lexer::Lexer;
+    use crate::parser::parser::Parser;
+
+    fn run(input: &str) -> Result<Object, VmError> {
+        let mut parser = Parser::new(Lexer::new(input));
+        let program = parser.parse_program();
+        assert_eq!(parser.errors(), &[], "input should parse");
+
+        let mut compiler = Compiler::new();
+        compiler.compile(&program).expect("input should compile");
+
+        let mut vm = Vm::new(compiler.bytecode());
+        vm.run()?;
+        Ok(vm.last_popped().clone())
+    }
+
+    #[test]
+    fn stack_is_balanced() {
+        let mut parser = Parser::new(Lexer::new(
+            "let f = fn(a) { let b = a * 2; if (b > 2) { [b, {1: b}] } }; f(1); f(2)[1][1]; 3",
+        ));
+        let mut compiler = Compiler::new();
+        compiler.compile(&parser.parse_program()).unwrap();
+
+        let mut vm = Vm::new(compiler.bytecode());
+        vm.run().unwrap();
+        assert_eq!(vm.last_popped(), &Object::Integer(3));
+        assert!(vm.stack.is_empty());
+        assert!(vm.callers.is_empty());
+    }
+
+    #[test]
+    fn closures() {
+        assert_eq!(
+            run("let newAdder = fn(a, b) { fn(c) { a + b + c } }; newAdder(1, 2)(8)"),
+            Ok(Object::Integer(11))
+        );
+        assert_eq!(
+            run("let wrapper = fn() { let countDown = fn(x) { if (x == 0) { return 0; } countDown(x - 1) }; countDown(5) }; wrapper()"),
+            Ok(Object::Integer(0))
+        );
+        assert_eq!(
+            run("let counter = fn() { let n = 0; [fn() { n += 1; n }, fn() { n }] }; let c = counter(); c[0](); c[0](); c[1]()"),
+            Ok(Object::Integer(2))
+        );
+    }
+
+    #[test]
+    fn runtime_errors() {
+        assert_eq!(
+            run("fn(a) { a }()").unwrap_err().message,
+            "wrong number of arguments: want=1, got=0"
+        );
+        assert_eq!(
+            run("let f = fn() { f() }; f()").unwrap_err().message,
+            "stack overflow"
+        );
+        assert_eq!(
+            run("1 + \"a\"").unwrap_err().to_string(),
+            "type mismatch: INTEGER + STRING"
+        );
+    }
+
+    #[test]
+    fn runtime_error_lines() {
+        assert_eq!(
+            run("let a = 1;\nlet f = fn(x) {\n  x / 0\n};\nf(a)"),
+            Err(VmError {
+                message: "division by zero".to_string(),
+                line: Some(3),
+            })
+        );
+        assert_eq!(run("let a = 1;\n\n[a, a / 0]").unwrap_err().line, Some(3));
+    }
+
+    #[test]
+    fn malformed_bytecode() {
+        let run_bytecode = |instructions: Vec<Vec<u8>>, constants: Vec<Object>| {
+            let mut vm = Vm::new(Bytecode {
+                instructions: instructions.concat(),
+                constants,
+                lines: vec![],
+            });
+            vm.run().unwrap_err().message
+        };
+
+        assert_eq!(
+            run_bytecode(vec![make(Opcode::Pop, &[])], vec![]),
+            "malformed bytecode: stack underflow"
+        );
+        assert_eq!(
+            run_bytecode(vec![make(Opcode::Constant, &[0])], vec![]),
+            "malformed bytecode: no constant 0"
+        );
+        assert_eq!(
+            run_bytecode(vec![make(Opcode::Constant, &[0])[..2].to_vec()], vec![]),
+            "malformed bytecode: truncated operand"
+        );
+        assert_eq!(
+            run_bytecode(
+                vec![
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Constant, &[0]),
+                    make(Opcode::Array, &[3]),
+                ],
+                vec![Object::Integer(1)]
+            ),
+            "malformed bytecode: stack underflow"
+        );
+        assert_eq!(
+            run_bytecode(vec![make(Opcode::GetLocal, &[0])], vec![]),
+            "malformed bytecode: no local 0"
+        );
+        assert_eq!(
+            run_bytecode(vec![make(Opcode::GetBuiltin, &[200])], vec![]),
+            "malformed bytecode: no builtin 200"
+        );
+    }
+}
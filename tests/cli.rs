@@ -0,0 +1,194 @@
+//! runs the `monkey` binary and checks its output and exit status
+
+use std::fs;
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn monkey(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_monkey"))
+        .args(args)
+        .output()
+        .expect("monkey should run")
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+#[test]
+fn evaluates_inline_source() {
+    let output = monkey(&["-e", "let a = [1, 2]; push(a, len(a))"]);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "[1, 2, 2]\n");
+
+    let output = monkey(&["-e", "let a = 1;"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "");
+
+    let output = monkey(&[
+        "-e",
+        "let swap = macro(a, b) { quote(unquote(b) - unquote(a)) }; swap(1, 10)",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "9\n");
+}
+
+#[test]
+fn runs_scripts() {
+    let path = std::env::temp_dir().join(format!("monkey-cli-{}.mk", std::process::id()));
+    fs::write(&path, "let double = fn(x) { x * 2 };\ndouble(21)\n").unwrap();
+    let output = monkey(&[path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(stdout(&output), "42\n");
+
+    let output = monkey(&["missing.mk"]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(stderr(&output).starts_with("could not read missing.mk: "));
+}
+
+#[test]
+fn exit_status_tells_errors_apart() {
+    let tests = [
+        (
+            vec!["-e", "\"open"],
+            3,
+            "error: unterminated string literal\n --> <eval>:1:1\n",
+        ),
+        (
+            vec!["-e", "let = 1"],
+            4,
+            "error: expected next token to be IDENT, got ASSIGN instead\n --> <eval>:1:5\n",
+        ),
+        (
+            vec!["-e", "1 + true"],
+            5,
+            "error: type mismatch: INTEGER + BOOLEAN\n --> <eval>:1:1\n",
+        ),
+        (
+            vec!["-e", "let a = 1;\na / (a - 1)"],
+            5,
+            "error: division by zero\n --> <eval>:2:1\n  |\n2 | a / (a - 1)\n  | ^^^^^^^^^^\n",
+        ),
+        (
+            vec!["--dump", "bytecode", "-e", "x"],
+            5,
+            "error: identifier not found: x\n --> <eval>:1:1\n",
+        ),
+        (
+            vec!["-e", "let m = macro() { 1 };\nm()"],
+            5,
+            "error: macro m returned INTEGER, expected a quote\n --> <eval>:2:1\n",
+        ),
+        (
+            vec!["--dump", "tokens", "-e", "\"a\\q\""],
+            3,
+            "error: invalid escape sequence \\q in string literal\n --> <eval>:1:3\n",
+        ),
+    ];
+
+    for (args, code, error) in tests.iter() {
+        let output = monkey(args);
+        assert_eq!(output.status.code(), Some(*code), "args: {:?}", args);
+        assert!(
+            stderr(&output).starts_with(error),
+            "args: {:?}, stderr: {}",
+            args,
+            stderr(&output)
+        );
+    }
+}
+
+#[test]
+fn renders_diagnostics() {
+    let output = monkey(&["-e", "let a = 1;\nlet b = a +;"]);
+
+    assert_eq!(output.status.code(), Some(4));
+    assert_eq!(
+        stderr(&output),
+        "error: no prefix parse function for SEMICOLON found
+ --> <eval>:2:12
+  |
+2 | let b = a +;
+  |            ^
+"
+    );
+}
+
+#[test]
+fn rejects_invalid_arguments() {
+    let tests: [&[&str]; 5] = [
+        &["--dump"],
+        &["--dump", "llvm", "-e", "1"],
+        &["--dump=ast"],
+        &["-e", "1", "script.mk"],
+        &["--verbose"],
+    ];
+
+    for args in tests.iter() {
+        let output = monkey(args);
+        assert_eq!(output.status.code(), Some(2), "args: {:?}", args);
+        assert!(stderr(&output)
+            .ends_with("usage: monkey [--dump tokens|ast|bytecode] [script.mk | -e <src>]\n"));
+    }
+}
+
+#[test]
+fn dumps_stages() {
+    let output = monkey(&["--dump", "tokens", "-e", "let a = 1;"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "1:1\tLET\tlet\n1:5\tIDENT\ta\n1:7\tASSIGN\t=\n1:9\tINT\t1\n1:10\tSEMICOLON\t;\n"
+    );
+
+    let output = monkey(&["--dump=ast", "-e", "-a"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).starts_with("Expression(\n    Prefix {"));
+
+    let output = monkey(&["--dump", "bytecode", "-e", "1 + 2"]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "== main ==\n0000 OpConstant 0\n0003 OpConstant 1\n0006 OpAdd\n0007 OpPop\n"
+    );
+
+    // bytecode is compiled from the program with its macros expanded
+    let output = monkey(&[
+        "--dump",
+        "bytecode",
+        "-e",
+        "let add = macro(a, b) { quote(unquote(a) + unquote(b)) }; add(1, 2)",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        stdout(&output),
+        "== main ==\n0000 OpConstant 0\n0003 OpConstant 1\n0006 OpAdd\n0007 OpPop\n"
+    );
+}
+
+#[test]
+fn starts_the_repl() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monkey"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("monkey should run");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(b"let a = 2;\na * 3\n")
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(stdout(&output).ends_with(">> >> 6\n>> \n"));
+}
@@ -0,0 +1,404 @@
+//! runs the same programs through the evaluator and the compiler + vm and
+//! checks both backends produce the same value or error
+
+use monkey_lang_lib::compiler::compiler::Compiler;
+use monkey_lang_lib::evaluator::environment::Environment;
+use monkey_lang_lib::evaluator::evaluator::eval;
+use monkey_lang_lib::evaluator::macro_expansion::{define_macros, expand_macros};
+use monkey_lang_lib::lexer::lexer::Lexer;
+use monkey_lang_lib::parser::parser::Parser;
+use monkey_lang_lib::vm::vm::Vm;
+use monkey_lang_lib::Program;
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// parses `input` and expands its macros, both backends run the expanded
+/// program
+fn parse(input: &str) -> Program {
+    let mut parser = Parser::new(Lexer::new(input));
+    let mut program = parser.parse_program();
+    assert_eq!(parser.errors(), &[], "input should parse: {}", input);
+
+    let macros = Rc::new(RefCell::new(Environment::new()));
+    define_macros(&mut program, &macros);
+    expand_macros(program, &macros).expect("macros should expand")
+}
+
+/// returns the evaluator's result, errors displayed as `ERROR: <message>`
+fn run_evaluator(program: &Program) -> String {
+    eval(program, &Rc::new(RefCell::new(Environment::new()))).to_string()
+}
+
+/// returns the vm's result, errors displayed as `ERROR: <message>`
+fn run_vm(program: &Program) -> String {
+    let mut compiler = Compiler::new();
+    if let Err(err) = compiler.compile(program) {
+        return format!("ERROR: {}", err.kind);
+    }
+
+    let mut vm = Vm::new(compiler.bytecode());
+    match vm.run() {
+        Ok(()) => vm.last_popped().to_string(),
+        Err(err) => format!("ERROR: {}", err),
+    }
+}
+
+fn assert_conforms(tests: &[(&str, &str)]) {
+    for (input, expected) in tests {
+        let program = parse(input);
+        assert_eq!(
+            run_evaluator(&program),
+            *expected,
+            "evaluator, input: {}",
+            input
+        );
+        assert_eq!(run_vm(&program), *expected, "vm, input: {}", input);
+    }
+}
+
+#[test]
+fn integer_arithmetic() {
+    assert_conforms(&[
+        ("1", "1"),
+        ("1 + 2 * 3 - 4 / 2", "5"),
+        ("-50 + 100 + -50", "0"),
+        ("(5 + 10 * 2 + 15 / 3) * 2 + -10", "50"),
+        ("0xFF + 0o7 + 0b11 + 1_000", "1265"),
+    ]);
+}
+
+#[test]
+fn checked_arithmetic() {
+    assert_conforms(&[
+        ("1 / 0", "ERROR: division by zero"),
+        ("let f = fn(n) { 100 / n }; f(0)", "ERROR: division by zero"),
+        ("-9223372036854775807 - 1", "-9223372036854775808"),
+    ]);
+
+    #[cfg(not(feature = "bigint"))]
+    assert_conforms(&[
+        (
+            "9223372036854775807 + 1",
+            "ERROR: integer overflow: 9223372036854775807 + 1",
+        ),
+        (
+            "let min = -9223372036854775807 - 1; -min",
+            "ERROR: integer overflow: --9223372036854775808",
+        ),
+    ]);
+
+    #[cfg(feature = "bigint")]
+    assert_conforms(&[
+        ("9223372036854775807 + 1", "9223372036854775808"),
+        ("4294967296 * 4294967296 / 4294967296", "4294967296"),
+    ]);
+}
+
+#[test]
+fn extended_operators() {
+    assert_conforms(&[
+        ("7 % 3", "1"),
+        ("-7 % 3", "-1"),
+        ("7.5 % 2", "1.5"),
+        ("5 % 0", "ERROR: division by zero"),
+        ("2 ** 10", "1024"),
+        ("2 ** 3 ** 2", "512"),
+        ("-2 ** 2", "-4"),
+        ("(-2) ** 3", "-8"),
+        ("2 ** 0.5 > 1.41", "true"),
+        ("4.0 ** -1", "0.25"),
+        (
+            "2 ** -1",
+            "ERROR: negative exponent: 2 ** -1, use a float base for fractions",
+        ),
+        ("1 ** 5000000000", "1"),
+        ("(-1) ** 5000000001", "-1"),
+        ("1 <= 1", "true"),
+        ("2 <= 1", "false"),
+        ("1 >= 1.5", "false"),
+        ("1.5 >= 1", "true"),
+        (
+            "true <= false",
+            "ERROR: unknown operator: BOOLEAN <= BOOLEAN",
+        ),
+    ]);
+}
+
+#[test]
+fn logical_operators() {
+    assert_conforms(&[
+        ("true && true", "true"),
+        ("true && false", "false"),
+        ("false || true", "true"),
+        ("false || false", "false"),
+        ("1 && \"a\"", "true"),
+        ("first([]) || 0", "true"),
+        ("1 < 2 && 2 < 3 || false", "true"),
+        // the right operand isn't evaluated once the left decides the result
+        ("true || 1 / 0", "true"),
+        ("true && 1 / 0", "ERROR: division by zero"),
+        (
+            "let calls = fn(x) { if (x > 0) { x > 1 && calls(x - 1) } else { true } }; calls(5)",
+            "false",
+        ),
+    ]);
+}
+
+#[test]
+fn assignments_and_loops() {
+    assert_conforms(&[
+        ("let x = 1; x = x + 1; x", "2"),
+        ("let x = 7; x += 1; x *= 3; x -= 4; x /= 5; x %= 3; x", "1"),
+        ("let s = \"a\"; s += \"b\"; s", "ab"),
+        (
+            "let i = 0; let sum = 0; while (i < 10) { i += 1; if (i % 2 == 0) { continue; } sum += i; } sum",
+            "25",
+        ),
+        ("let i = 0; while (true) { i += 1; if (i == 3) { break; } } i", "3"),
+        (
+            "let sum = 0; for (x in [1, 2, 3]) { for (y in [10, 20]) { if (y > 10) { break; } sum += x * y; } } sum",
+            "60",
+        ),
+        (
+            "let count = fn(xs) { let n = 0; for (x in xs) { n += 1; } n }; count([1, 2, 3]) + count([])",
+            "3",
+        ),
+        (
+            "let find = fn(xs, y) { for (x in xs) { if (x == y) { return true; } } false }; find([1, 2], 2)",
+            "true",
+        ),
+        ("for (x in \"ab\") { x }", "ERROR: not iterable: STRING"),
+        // a loop evaluates to null, whatever its body evaluated to last
+        ("for (x in [1, 2]) { x }", "null"),
+        ("let i = 0; while (i < 2) { i += 1; i }", "null"),
+        ("let f = fn() { for (x in [1]) { x } }; f()", "null"),
+        ("let i = 0; while (true) { i += 1; if (i == 2) { break; } i }", "null"),
+        ("let x = 5; for (x in [1, 2]) {} x", "5"),
+        // every iteration binds the variable anew, at the top level too
+        (
+            "let fs = []; for (x in [1, 2]) { fs = push(fs, fn() { x }); } fs[0]() + fs[1]()",
+            "3",
+        ),
+        (
+            "let fs = []; for (x in [1, 2]) { let y = x * 10; fs = push(fs, fn() { y }); } fs[0]() + fs[1]()",
+            "30",
+        ),
+        (
+            "let fs = []; for (x in [1, 2]) { let f = fn() { x += 10; x }; fs = push(fs, f); x = x * 100; } fs[0]() + fs[1]()",
+            "320",
+        ),
+        (
+            "let f = fn() { let x = 5; for (x in [1, 2]) { x = x * 10; } x }; f()",
+            "5",
+        ),
+        (
+            "let last = 0; for (x in [1, 2]) { let y = x; last = y; } last",
+            "2",
+        ),
+        ("for (x in [1]) { let y = x; } y", "ERROR: identifier not found: y"),
+        ("let x = 1; x += true", "ERROR: type mismatch: INTEGER + BOOLEAN"),
+        (
+            "let f = fn() { let x = 1; let g = fn() { x = x + 1; x }; g() }; f()",
+            "2",
+        ),
+        (
+            "let counter = fn() { let n = 0; fn() { n += 1; n } }; let c = counter(); c(); c(); c()",
+            "3",
+        ),
+        (
+            "let f = fn() { let x = 1; let g = fn() { x }; x = 5; g() }; f()",
+            "5",
+        ),
+        (
+            "let f = fn(x) { let g = fn() { fn() { x *= 2 } }; g()(); g()(); x }; f(3)",
+            "12",
+        ),
+        (
+            "let f = fn() { let fs = []; for (x in [1, 2]) { fs = push(fs, fn() { x }); } fs[0]() + fs[1]() }; f()",
+            "3",
+        ),
+    ]);
+}
+
+#[test]
+fn macros() {
+    assert_conforms(&[
+        (
+            "let unless = macro(cond, a, b) { quote(if (!(unquote(cond))) { unquote(a) } else { unquote(b) }) }; unless(10 > 5, 1, 2)",
+            "2",
+        ),
+        (
+            "let square = macro(x) { quote(unquote(x) * unquote(x)) }; let f = fn(n) { square(n + 1) }; f(2)",
+            "9",
+        ),
+        (
+            "let times = macro(n, body) { quote(fn() { let i = 0; while (i < unquote(n)) { unquote(body); i += 1; } i }()) }; times(2 + 1, len([]))",
+            "3",
+        ),
+        (
+            "let constant = macro() { quote(unquote(6 * 7) + 0.5) }; constant()",
+            "42.5",
+        ),
+    ]);
+}
+
+#[test]
+fn operand_limits() {
+    // the largest constant index and local slot the bytecode can encode
+    let strings: String = (0..65_535).map(|i| format!("\"s{}\";", i)).collect();
+    let constants = strings + "let a = 123456; a";
+    let lets: String = (0..256).map(|i| format!("let v{} = {};", i, i)).collect();
+    let locals = format!("fn() {{ {} v0 + v255 }}()", lets);
+
+    assert_conforms(&[(&constants, "123456"), (&locals, "255")]);
+}
+
+#[test]
+fn float_arithmetic() {
+    assert_conforms(&[
+        ("1.5", "1.5"),
+        ("-0.25 * 4", "-1.0"),
+        ("1 + 2.5", "3.5"),
+        ("10 / 4.0", "2.5"),
+        ("1e-3 * 1000", "1.0"),
+        ("1 < 1.5", "true"),
+        ("3 == 3.0", "true"),
+        ("1.0 / 0", "inf"),
+        ("let half = fn(x) { x / 2.0 }; half(3)", "1.5"),
+        ("1.5 - \"a\"", "ERROR: type mismatch: FLOAT - STRING"),
+    ]);
+}
+
+#[test]
+fn booleans_and_conditionals() {
+    assert_conforms(&[
+        ("1 < 2", "true"),
+        ("1 > 2 == false", "true"),
+        ("true != false", "true"),
+        ("!5", "false"),
+        ("!!true", "true"),
+        ("if (1 < 2) { 10 } else { 20 }", "10"),
+        ("if (false) { 10 }", "null"),
+        ("if (1) { }", "null"),
+        ("if (true) { let a = 1; }", "null"),
+        ("!(if (false) { 5; })", "true"),
+        ("if ((if (false) { 10 })) { 10 } else { 20 }", "20"),
+    ]);
+}
+
+#[test]
+fn strings_arrays_and_hashes() {
+    assert_conforms(&[
+        ("\"mon\" + \"key\"", "monkey"),
+        ("\"a\" == \"a\"", "true"),
+        ("[1, 2 * 2, \"three\"]", "[1, 4, three]"),
+        ("[1, 2, 3][1]", "2"),
+        ("[1, 2, 3][3]", "null"),
+        ("[[1, 2]][0][1]", "2"),
+        ("{\"a\": 1, 2: true}", "{2: true, a: 1}"),
+        ("{1: 1, 1: 2}[1]", "2"),
+        ("let h = {\"a\": [1]}; h[\"a\"][0]", "1"),
+        ("let h = {}; h[1]", "null"),
+    ]);
+}
+
+#[test]
+fn bindings_and_returns() {
+    assert_conforms(&[
+        ("let a = 5; let b = a * 2; a + b", "15"),
+        ("let a = 1; let a = a + 1; a", "2"),
+        ("return 10; 9;", "10"),
+        ("9; return 2 * 5; 9;", "10"),
+        ("if (10 > 1) { if (10 > 1) { return 10; } return 1; }", "10"),
+        ("{ return 3; } 4", "3"),
+        ("{ let x = 2; } x", "2"),
+    ]);
+}
+
+#[test]
+fn functions_and_closures() {
+    assert_conforms(&[
+        ("let add = fn(a, b) { a + b }; add(1, 2)", "3"),
+        ("let early = fn() { return 1; 2 }; early()", "1"),
+        ("let noop = fn() { }; noop()", "null"),
+        ("let f = fn() { let a = 1; }; f()", "null"),
+        ("fn(x) { x * 2 }(5)", "10"),
+        (
+            "let newAdder = fn(x) { fn(y) { x + y } }; let addTwo = newAdder(2); addTwo(3)",
+            "5",
+        ),
+        (
+            "let a = 1; let f = fn(b) { fn(c) { fn(d) { a + b + c + d } } }; f(2)(3)(4)",
+            "10",
+        ),
+        (
+            "let fib = fn(n) { if (n < 2) { n } else { fib(n - 1) + fib(n - 2) } }; fib(15)",
+            "610",
+        ),
+        (
+            "let wrapper = fn() { let countDown = fn(x) { if (x == 0) { 0 } else { countDown(x - 1) } }; countDown(3) }; wrapper()",
+            "0",
+        ),
+        (
+            "let x = 1; let f = fn() { x }; let x = 2; f()",
+            "2",
+        ),
+    ]);
+}
+
+#[test]
+fn builtins() {
+    assert_conforms(&[
+        ("len(\"four\")", "4"),
+        ("len([1, 2, 3])", "3"),
+        ("first([1, 2])", "1"),
+        ("last([1, 2])", "2"),
+        ("rest([1, 2, 3])", "[2, 3]"),
+        ("push([1], 2)", "[1, 2]"),
+        ("first([])", "null"),
+        ("let len = fn(x) { 42 }; len([])", "42"),
+        (
+            "let map = fn(arr, f) { let iter = fn(arr, acc) { if (len(arr) == 0) { acc } else { iter(rest(arr), push(acc, f(first(arr)))) } }; iter(arr, []) }; map([1, 2, 3], fn(x) { x * 2 })",
+            "[2, 4, 6]",
+        ),
+    ]);
+}
+
+#[test]
+fn runtime_errors() {
+    assert_conforms(&[
+        ("5 + true;", "ERROR: type mismatch: INTEGER + BOOLEAN"),
+        ("5 + true; 5;", "ERROR: type mismatch: INTEGER + BOOLEAN"),
+        ("-true", "ERROR: unknown operator: -BOOLEAN"),
+        ("true + false", "ERROR: unknown operator: BOOLEAN + BOOLEAN"),
+        ("\"a\" - \"b\"", "ERROR: unknown operator: STRING - STRING"),
+        (
+            "if (10 > 1) { true + false; 5 }",
+            "ERROR: unknown operator: BOOLEAN + BOOLEAN",
+        ),
+        ("foobar", "ERROR: identifier not found: foobar"),
+        (
+            "let f = fn(x) { x }; f(1, 2)",
+            "ERROR: wrong number of arguments: want=1, got=2",
+        ),
+        ("5(1)", "ERROR: not a function: INTEGER"),
+        (
+            "1[0]",
+            "ERROR: index operator not supported: INTEGER[INTEGER]",
+        ),
+        ("{[1]: 2}", "ERROR: unusable as hash key: ARRAY"),
+        (
+            "{1: 2}[fn() { 1 }]",
+            "ERROR: unusable as hash key: FUNCTION",
+        ),
+        (
+            "len(1)",
+            "ERROR: argument to `len` not supported, got INTEGER",
+        ),
+        (
+            "first(1)",
+            "ERROR: argument to `first` must be ARRAY, got INTEGER",
+        ),
+        ("len()", "ERROR: wrong number of arguments: want=1, got=0"),
+    ]);
+}
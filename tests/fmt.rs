@@ -0,0 +1,95 @@
+//! runs the `monkeyfmt` binary and checks its output and exit status
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+const UNFORMATTED: &str = "let add=fn(a,b){a+b};\nadd(1,2)";
+const FORMATTED: &str = "let add = fn(a, b) {\n    a + b\n};\nadd(1, 2)\n";
+
+fn monkeyfmt(args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_monkeyfmt"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("monkeyfmt should run");
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(stdin.as_bytes())
+        .unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// returns a path in the temp directory unique to this test process
+fn temp_file(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("monkeyfmt-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+#[test]
+fn formats_stdin() {
+    let output = monkeyfmt(&[], UNFORMATTED);
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), FORMATTED);
+}
+
+#[test]
+fn formats_files_in_place() {
+    let unformatted = temp_file("in-place.mk", UNFORMATTED);
+    let output = monkeyfmt(&[unformatted.to_str().unwrap()], "");
+    let formatted = fs::read_to_string(&unformatted).unwrap();
+    fs::remove_file(&unformatted).unwrap();
+
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(formatted, FORMATTED);
+}
+
+#[test]
+fn checks_formatting() {
+    let unformatted = temp_file("check-unformatted.mk", UNFORMATTED);
+    let formatted = temp_file("check-formatted.mk", FORMATTED);
+    let output = monkeyfmt(
+        &[
+            "--check",
+            unformatted.to_str().unwrap(),
+            formatted.to_str().unwrap(),
+        ],
+        "",
+    );
+    let untouched = fs::read_to_string(&unformatted).unwrap();
+    fs::remove_file(&unformatted).unwrap();
+    fs::remove_file(&formatted).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        format!("{}\n", unformatted.display())
+    );
+    assert_eq!(untouched, UNFORMATTED);
+
+    let output = monkeyfmt(&["--check"], FORMATTED);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(output.stdout, b"");
+}
+
+#[test]
+fn reports_errors() {
+    let output = monkeyfmt(&[], "let = 1");
+    assert_eq!(output.status.code(), Some(3));
+    assert!(String::from_utf8(output.stderr).unwrap().starts_with(
+        "error: expected next token to be IDENT, got ASSIGN instead\n --> <stdin>:1:5\n"
+    ));
+
+    let output = monkeyfmt(&["missing.mk"], "");
+    assert_eq!(output.status.code(), Some(3));
+
+    let output = monkeyfmt(&["--write"], "");
+    assert_eq!(output.status.code(), Some(2));
+}
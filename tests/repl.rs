@@ -0,0 +1,151 @@
+//! drives the repl from in-memory buffers
+
+use monkey_lang_lib::repl::repl::start;
+use std::fs;
+use std::io::{self, Write};
+
+const BANNER: &str = "Welcome to monkey lang!\nCtrl+D or :quit to exit the interpreter\n\n";
+
+/// runs a session over `input`, returning what was written to the output
+/// (without the banner) and to the error sink
+fn session(input: &[u8]) -> (String, String) {
+    let mut output = vec![];
+    let mut errors = vec![];
+    start(input, &mut output, &mut errors).expect("session should end at EOF");
+
+    let output = String::from_utf8(output).unwrap();
+    assert!(output.starts_with(BANNER), "output: {:?}", output);
+    (
+        output[BANNER.len()..].to_string(),
+        String::from_utf8(errors).unwrap(),
+    )
+}
+
+#[test]
+fn evaluates_in_a_persistent_environment() {
+    let (output, errors) = session(b"let a = 5;\nlet b = a * 2;\na + b\n\"a\" + \"b\"\n");
+
+    assert_eq!(output, ">> >> >> 15\n>> ab\n>> \n");
+    assert_eq!(errors, "");
+}
+
+#[test]
+fn continues_unbalanced_input() {
+    let (output, errors) = session(b"let add = fn(x, y) {\n  x + y\n};\nadd(1,\n2)\n[1,\n2]\n");
+
+    assert_eq!(output, ">> .. .. >> .. 3\n>> .. [1, 2]\n>> \n");
+    assert_eq!(errors, "");
+}
+
+#[test]
+fn reports_errors_to_the_error_sink() {
+    let (output, errors) = session(b"let b = ;\nc\n5 / \"x\"\n1\n");
+
+    assert_eq!(output, ">> >> >> >> 1\n>> \n");
+    assert_eq!(
+        errors,
+        "1:9: no prefix parse function for SEMICOLON found\n\
+         ERROR: identifier not found: c\n\
+         ERROR: type mismatch: INTEGER / STRING\n"
+    );
+}
+
+#[test]
+fn skips_invalid_utf8() {
+    let (output, errors) = session(b"\xff\n1\n");
+
+    assert_eq!(output, ">> >> 1\n>> \n");
+    assert_eq!(errors, "stream did not contain valid UTF-8\n");
+}
+
+#[test]
+fn returns_write_errors() {
+    struct Closed;
+
+    impl Write for Closed {
+        fn write(&mut self, _: &[u8]) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::BrokenPipe, "closed"))
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    let err = start(&b"1\n"[..], Closed, vec![]).unwrap_err();
+    assert_eq!(err.kind(), io::ErrorKind::BrokenPipe);
+}
+
+#[test]
+fn lists_and_resets_bindings() {
+    let (output, errors) = session(b"let b = [1];\nlet a = fn(x) { x };\n:env\n:reset\n:env\na\n");
+
+    assert_eq!(output, ">> >> >> a = fn(x) { x }\nb = [1]\n>> >> >> >> \n");
+    assert_eq!(errors, "ERROR: identifier not found: a\n");
+}
+
+#[test]
+fn expands_macros_across_inputs() {
+    let (output, errors) = session(
+        b"let unless = macro(cond, a, b) { quote(if (!(unquote(cond))) { unquote(a) } else { unquote(b) }) };\n\
+          unless(1 > 2, \"yes\", \"no\")\n\
+          :reset\n\
+          unless(true, 1, 2)\n",
+    );
+
+    assert_eq!(output, ">> >> yes\n>> >> >> \n");
+    assert_eq!(errors, "ERROR: identifier not found: unless\n");
+}
+
+#[test]
+fn shows_tokens_and_parse_trees() {
+    let (output, errors) = session(b":tokens 1 \"a\n:ast -x\n:ast let = 1\n");
+
+    let lines: Vec<_> = output.lines().collect();
+    assert!(lines[0].starts_with(">> Token { token_type: INT, literal: \"1\""));
+    assert!(lines[1].starts_with("Token { token_type: STRING, literal: \"\\\"a\""));
+    assert!(lines[2].starts_with(">> Expression("), "line: {}", lines[2]);
+    assert!(output.contains("operator: MINUS"), "output: {}", output);
+    assert!(output.contains("Identifier {"), "output: {}", output);
+    assert_eq!(
+        errors,
+        "1:3: unterminated string literal\n1:5: expected next token to be IDENT, got ASSIGN instead\n"
+    );
+}
+
+#[test]
+fn loads_scripts() {
+    let path = std::env::temp_dir().join(format!("monkey-repl-{}.mk", std::process::id()));
+    fs::write(&path, "let double = fn(x) {\n  x * 2\n};\ndouble(4)\n").unwrap();
+
+    let input = format!(
+        ":load {}\ndouble(5)\n:load\n:load missing.mk\n",
+        path.display()
+    );
+    let (output, errors) = session(input.as_bytes());
+    fs::remove_file(&path).unwrap();
+
+    assert_eq!(output, ">> 8\n>> 10\n>> >> >> \n");
+    assert!(errors.starts_with("usage: :load <file>\ncould not read missing.mk: "));
+}
+
+#[test]
+fn times_evaluation() {
+    let (output, errors) = session(b":time\n1 + 1\n:time\n2\n");
+
+    let lines: Vec<_> = output.lines().collect();
+    assert_eq!(lines[0], ">> timing on");
+    assert_eq!(lines[1], ">> 2");
+    assert!(lines[2].starts_with("time: "), "line: {}", lines[2]);
+    assert_eq!(lines[3], ">> timing off");
+    assert_eq!(lines[4], ">> 2");
+    assert_eq!(errors, "");
+}
+
+#[test]
+fn quits_and_rejects_unknown_commands() {
+    let (output, errors) = session(b":nope\n:quit\n1\n");
+
+    assert_eq!(output, ">> >> ");
+    assert!(errors.starts_with("unknown command :nope, expected one of :tokens"));
+}